@@ -39,8 +39,7 @@ fn install(schedule: Option<Schedule>) {
 
 fn main() {
     if env::args()
-        .skip(1)
-        .next()
+        .nth(1)
         .is_some_and(|arg| arg == "--simulate-service")
     {
         thread::sleep(Duration::from_secs(920));
@@ -1,4 +1,6 @@
 mod builder;
+mod hardening;
+mod limits;
 
 /// Errors and settings related to installing files
 pub mod files;
@@ -7,11 +9,14 @@ pub mod init;
 
 use std::ffi::OsString;
 use std::fmt::Display;
+use std::path::PathBuf;
 
-pub use builder::Spec;
+pub use builder::{CredentialSource, Listen, NotifyAccess, Restart, Section, Spec, WatchPath};
 use files::MoveBackError;
+pub use hardening::{Hardening, ProtectHome, ProtectSystem};
 use init::systemd;
 use itertools::{Either, Itertools};
+pub use limits::IoSchedulingClass;
 
 use crate::Tense;
 
@@ -76,6 +81,62 @@ pub enum PrepareInstallError {
     UserDoesNotExist(String),
     #[error("All supported init systems found failed, errors: {0:?}")]
     SupportedInitSystemFailed(Vec<InitSystemFailure>),
+    #[error("Running a container image requires systemd, which is not available or not allowed")]
+    ContainerNeedsSystemd,
+    #[error("Only systemd can sandbox a service, it is not available or not allowed")]
+    HardeningNeedsSystemd,
+    #[error("Only systemd can apply `{0}`, it is not available or not allowed")]
+    LimitNeedsSystemd(&'static str),
+    #[error("Only systemd can wait for a service to report it is ready, it is not available or not allowed")]
+    NotifyNeedsSystemd,
+    #[error("Only systemd can start a service on connection, it is not available or not allowed")]
+    SocketActivationNeedsSystemd,
+    #[error("A service started on connection needs at least one address to listen on")]
+    NoListenAddress,
+    #[error("Can not listen on `{0}`, the address can not be empty or span multiple lines")]
+    InvalidListenAddress(String),
+    #[error("Only systemd can start a service on path change, it is not available or not allowed")]
+    PathTriggerNeedsSystemd,
+    #[error("Only systemd and cron can keep environment variables secret, neither is allowed")]
+    SecretsUnsupported,
+    #[error("`{0}` is not a valid name for a secret environment variable, use only ASCII letters, digits and `_`")]
+    InvalidSecretName(String),
+    #[error("Only systemd can pass credentials to a service, it is not available or not allowed")]
+    CredentialsNeedSystemd,
+    #[error(
+        "`{0}` is not a valid credential name, use only ASCII letters, digits, `_`, `-` and `.`"
+    )]
+    InvalidCredentialName(String),
+    #[error("Can not load a credential from `{0}`, the path must be absolute and fit on one line")]
+    InvalidCredentialPath(PathBuf),
+    #[error("A service started on path change needs at least one path to watch")]
+    NoWatchPath,
+    #[error("Can not watch `{0}`, the path must be absolute and fit on one line")]
+    InvalidWatchPath(PathBuf),
+    #[error("Niceness must be between -20 and 19, got: {0}")]
+    NiceOutOfRange(i8),
+    #[error("`{0}` is not a valid systemd unit name, it needs a unit type suffix like `.service` or `.target`")]
+    InvalidUnitName(String),
+    #[error("Directive `{0}` is invalid, keys may only contain ASCII letters and digits and values must fit on one line")]
+    InvalidDirective(String),
+    #[error("Only scheduled services have a timer unit to add `[Timer]` directives to")]
+    TimerDirectiveWithoutSchedule,
+    #[error(
+        "Only services started on connection have a socket unit to add `[Socket]` directives to"
+    )]
+    SocketDirectiveWithoutConnection,
+    #[error("Only services started on path change have a path unit to add `[Path]` directives to")]
+    PathDirectiveWithoutPathChange,
+    #[error("Timezone `{0}` is not in the system's timezone database")]
+    UnknownTimezone(String),
+    #[error("A scheduled service can not restart whenever it exits, it would never stop running. Use `Restart::OnFailure` instead")]
+    RestartAlwaysOnSchedule,
+    #[error("The schedule can never run")]
+    InvalidSchedule(
+        #[from]
+        #[source]
+        crate::schedule::Error,
+    ),
 }
 
 /// The init system was found and we tried to set up the service but ran into an
@@ -133,12 +194,54 @@ pub enum InstallError {
         #[source]
         init::cron::SetCrontabError,
     ),
+    #[error("Could not write or remove the cron.d file")]
+    CronD(
+        #[from]
+        #[source]
+        init::cron::drop_in::Error,
+    ),
     #[error("Something went wrong interacting with systemd")]
     Systemd(
         #[from]
         #[source]
         init::systemd::Error,
     ),
+    #[error("Something went wrong interacting with OpenRC")]
+    OpenRc(
+        #[from]
+        #[source]
+        init::openrc::Error,
+    ),
+    #[error("Something went wrong interacting with runit/s6")]
+    ServiceDir(
+        #[from]
+        #[source]
+        init::service_dir::Error,
+    ),
+    #[error("Something went wrong interacting with SysV init")]
+    SysV(
+        #[from]
+        #[source]
+        init::sysv::Error,
+    ),
+    #[error("Something went wrong interacting with dinit")]
+    Dinit(
+        #[from]
+        #[source]
+        init::dinit::Error,
+    ),
+    #[error("Something went wrong writing or removing the XDG autostart entry")]
+    XdgAutostart(
+        #[from]
+        #[source]
+        init::xdg_autostart::Error,
+    ),
+    #[error("Something went wrong writing or removing the secret environment file")]
+    Secrets(
+        #[from]
+        #[source]
+        init::secrets::Error,
+    ),
     #[error("Could not set the owner of the installed executable to be root")]
     SetRootOwner(#[source] std::io::Error),
     #[error("Could not make the installed executable read only")]
@@ -237,12 +340,54 @@ pub enum RemoveError {
         #[source]
         files::DeleteError,
     ),
+    #[error("Could not write or remove the cron.d file")]
+    CronD(
+        #[from]
+        #[source]
+        init::cron::drop_in::Error,
+    ),
     #[error("Something went wrong interacting with systemd")]
     Systemd(
         #[from]
         #[source]
         init::systemd::Error,
     ),
+    #[error("Something went wrong interacting with OpenRC")]
+    OpenRc(
+        #[from]
+        #[source]
+        init::openrc::Error,
+    ),
+    #[error("Something went wrong interacting with runit/s6")]
+    ServiceDir(
+        #[from]
+        #[source]
+        init::service_dir::Error,
+    ),
+    #[error("Something went wrong interacting with SysV init")]
+    SysV(
+        #[from]
+        #[source]
+        init::sysv::Error,
+    ),
+    #[error("Something went wrong interacting with dinit")]
+    Dinit(
+        #[from]
+        #[source]
+        init::dinit::Error,
+    ),
+    #[error("Something went wrong writing or removing the XDG autostart entry")]
+    XdgAutostart(
+        #[from]
+        #[source]
+        init::xdg_autostart::Error,
+    ),
+    #[error("Something went wrong writing or removing the secret environment file")]
+    Secrets(
+        #[from]
+        #[source]
+        init::secrets::Error,
+    ),
 }
 
 /// One step in the remove process. Can be executed or described.
@@ -297,6 +442,18 @@ pub enum RollbackError {
         #[source]
         systemd::Error,
     ),
+    #[error("error restarting OpenRC service")]
+    ReEnablingOpenRc(#[source] init::openrc::Error),
+    #[error("error restarting runit/s6 service")]
+    ReEnablingServiceDir(#[source] init::service_dir::Error),
+    #[error("error restarting SysV service")]
+    ReEnablingSysV(#[source] init::sysv::Error),
+    #[error("error restarting dinit service")]
+    ReEnablingDinit(#[source] init::dinit::Error),
+    #[error("Could not restore the original cron.d file")]
+    RestoringCronD(#[source] init::cron::drop_in::Error),
+    #[error("Could not restore the original secret environment file")]
+    RestoringSecrets(#[source] init::secrets::Error),
     #[error("Can not rollback setting up cron, must be done manually")]
     Impossible,
     #[error("Crontab changed undoing changes might overwrite the change")]
@@ -431,20 +588,32 @@ impl<T: ToAssign> Spec<builder::PathIsSet, builder::NameIsSet, builder::TriggerI
     pub fn prepare_install(self) -> Result<InstallSteps, PrepareInstallError> {
         let builder::Spec {
             mode,
-            path: Some(source),
+            path,
+            container_image,
             service_name: Some(name),
             bin_name,
             args,
-            environment,
+            mut environment,
+            secret_environment,
             trigger: Some(trigger),
             overwrite_existing,
+            run_if_missed,
+            jitter,
+            timezone,
+            restart,
+            hardening,
+            limits,
+            dependencies,
+            extra_directives,
+            readiness,
+            credentials,
             working_dir,
             run_as,
             description,
             ..
         } = self
         else {
-            unreachable!("type sys guarantees path, name and trigger set")
+            unreachable!("type sys guarantees name and trigger set")
         };
 
         let not_root = matches!(sudo::check(), sudo::RunningAs::User);
@@ -454,6 +623,146 @@ impl<T: ToAssign> Spec<builder::PathIsSet, builder::NameIsSet, builder::TriggerI
             }
         }
 
+        if let Some(schedule) = trigger.schedule() {
+            schedule.validate()?;
+        }
+        if let Some(timezone) = &timezone {
+            if !crate::schedule::timezone::is_known(timezone) {
+                return Err(PrepareInstallError::UnknownTimezone(timezone.clone()));
+            }
+        }
+        if restart.when == Some(builder::Restart::Always) && trigger.schedule().is_some() {
+            return Err(PrepareInstallError::RestartAlwaysOnSchedule);
+        }
+        if let Some(nice) = limits.nice.filter(|nice| !(-20..=19).contains(nice)) {
+            return Err(PrepareInstallError::NiceOutOfRange(nice));
+        }
+        if let Some(unit) = dependencies
+            .units()
+            .find(|unit| !systemd::valid_unit_name(unit))
+        {
+            return Err(PrepareInstallError::InvalidUnitName(unit.to_owned()));
+        }
+        if let Some(key) = extra_directives.invalid() {
+            return Err(PrepareInstallError::InvalidDirective(key.to_owned()));
+        }
+        let has_directives_for = |wanted| {
+            extra_directives
+                .directives
+                .iter()
+                .any(|(section, ..)| *section == wanted)
+        };
+        if has_directives_for(builder::Section::Timer) && trigger.schedule().is_none() {
+            return Err(PrepareInstallError::TimerDirectiveWithoutSchedule);
+        }
+        let listen = match &trigger {
+            builder::Trigger::OnConnection(listen) => Some(listen),
+            _ => None,
+        };
+        if has_directives_for(builder::Section::Socket) && listen.is_none() {
+            return Err(PrepareInstallError::SocketDirectiveWithoutConnection);
+        }
+        if listen.is_some_and(Vec::is_empty) {
+            return Err(PrepareInstallError::NoListenAddress);
+        }
+        if let Some(invalid) = listen
+            .into_iter()
+            .flatten()
+            .find(|l| l.address().trim().is_empty() || l.address().contains('\n'))
+        {
+            return Err(PrepareInstallError::InvalidListenAddress(
+                invalid.address().to_owned(),
+            ));
+        }
+        let watch = match &trigger {
+            builder::Trigger::OnPathChange(watch) => Some(watch),
+            _ => None,
+        };
+        if has_directives_for(builder::Section::Path) && watch.is_none() {
+            return Err(PrepareInstallError::PathDirectiveWithoutPathChange);
+        }
+        if watch.is_some_and(Vec::is_empty) {
+            return Err(PrepareInstallError::NoWatchPath);
+        }
+        if let Some(invalid) = watch.into_iter().flatten().find(|w| {
+            !w.path().is_absolute() || w.path().as_os_str().as_encoded_bytes().contains(&b'\n')
+        }) {
+            return Err(PrepareInstallError::InvalidWatchPath(
+                invalid.path().to_owned(),
+            ));
+        }
+
+        if let Some(invalid) = credentials.invalid_name() {
+            return Err(PrepareInstallError::InvalidCredentialName(
+                invalid.to_owned(),
+            ));
+        }
+        if let Some(invalid) = credentials.invalid_path() {
+            return Err(PrepareInstallError::InvalidCredentialPath(
+                invalid.to_owned(),
+            ));
+        }
+
+        if let Some(invalid) = secret_environment.invalid_name() {
+            return Err(PrepareInstallError::InvalidSecretName(invalid.to_owned()));
+        }
+        environment.retain(|var, _| !secret_environment.0.contains_key(var));
+        let secrets = if secret_environment.0.is_empty() {
+            None
+        } else {
+            Some(init::secrets::Secrets {
+                path: init::secrets::path(&name, mode).map_err(init::SetupError::from)?,
+                vars: secret_environment,
+            })
+        };
+
+        if let Some(image) = container_image {
+            if run_as.is_some() {
+                return Err(PrepareInstallError::Init(
+                    systemd::quadlet::Error::RunAsUnsupported.into(),
+                ));
+            }
+            if !hardening.is_empty() {
+                return Err(PrepareInstallError::Init(
+                    systemd::quadlet::Error::HardeningUnsupported.into(),
+                ));
+            }
+            if !credentials.is_empty() {
+                return Err(PrepareInstallError::Init(
+                    systemd::quadlet::Error::CredentialsUnsupported.into(),
+                ));
+            }
+            let systemd_allowed = self
+                .init_systems
+                .as_ref()
+                .is_none_or(|allowed| allowed.iter().any(|i| matches!(i, init::System::Systemd)));
+            if !systemd_allowed || init::System::Systemd.not_available()? {
+                return Err(PrepareInstallError::ContainerNeedsSystemd);
+            }
+            let params = systemd::quadlet::Params {
+                name,
+                bin_name,
+                description,
+                image,
+                args,
+                environment,
+                working_dir,
+                trigger,
+                restart,
+                limits,
+                dependencies,
+                extra_directives,
+                readiness,
+                secrets,
+                mode,
+            };
+            let steps = systemd::quadlet::set_up_steps(&params)?;
+            return Ok(InstallSteps(steps));
+        }
+        let Some(source) = path else {
+            unreachable!("type sys guarantees path or container image set")
+        };
+
         if let Some(ref user) = run_as {
             let curr_user = uzers::get_current_username()
                 .ok_or_else(|| PrepareInstallError::UserDoesNotExist(user.clone()))?;
@@ -462,7 +771,39 @@ impl<T: ToAssign> Spec<builder::PathIsSet, builder::NameIsSet, builder::TriggerI
             }
         }
 
-        let init_systems = self.init_systems.unwrap_or_else(init::System::all);
+        let mut init_systems = self.init_systems.unwrap_or_else(init::System::all);
+        let needs_systemd = if !hardening.is_empty() {
+            Some(PrepareInstallError::HardeningNeedsSystemd)
+        } else if matches!(trigger, builder::Trigger::OnConnection(_)) {
+            Some(PrepareInstallError::SocketActivationNeedsSystemd)
+        } else if matches!(trigger, builder::Trigger::OnPathChange(_)) {
+            Some(PrepareInstallError::PathTriggerNeedsSystemd)
+        } else if readiness.notify {
+            Some(PrepareInstallError::NotifyNeedsSystemd)
+        } else if !credentials.is_empty() {
+            Some(PrepareInstallError::CredentialsNeedSystemd)
+        } else {
+            limits
+                .needs_cgroup()
+                .map(PrepareInstallError::LimitNeedsSystemd)
+        };
+        if let Some(err) = needs_systemd {
+            init_systems.retain(|init| matches!(init, init::System::Systemd));
+            if init_systems.is_empty() || init::System::Systemd.not_available()? {
+                return Err(err);
+            }
+        }
+        if secrets.is_some() {
+            init_systems.retain(|init| {
+                matches!(
+                    init,
+                    init::System::Systemd | init::System::CronD | init::System::Cron
+                )
+            });
+            if init_systems.is_empty() {
+                return Err(PrepareInstallError::SecretsUnsupported);
+            }
+        }
         let (mut steps, exe_path) = files::move_files(
             source,
             mode,
@@ -481,6 +822,17 @@ impl<T: ToAssign> Spec<builder::PathIsSet, builder::NameIsSet, builder::TriggerI
             working_dir,
 
             trigger,
+            run_if_missed,
+            jitter,
+            timezone,
+            restart,
+            hardening,
+            limits,
+            dependencies,
+            extra_directives,
+            readiness,
+            credentials,
+            secrets,
             run_as,
             mode,
         };
@@ -643,7 +995,13 @@ impl<M: ToAssign, P: ToAssign, T: ToAssign, I: ToAssign> Spec<M, P, T, I> {
         let mut inits = self.init_systems.unwrap_or(init::System::all()).into_iter();
         let (mut steps, path) = loop {
             let Some(init) = inits.next() else {
-                return Err(PrepareRemoveError::NoInstallFound);
+                // no executable installed, maybe a container is
+                return match systemd::quadlet::tear_down_steps(bin_name, mode)
+                    .map_err(init::TearDownError::from)?
+                {
+                    Some(steps) => Ok(RemoveSteps(steps)),
+                    None => Err(PrepareRemoveError::NoInstallFound),
+                };
             };
 
             if let Some(install) = init.tear_down_steps(bin_name, mode, run_as.as_deref())? {
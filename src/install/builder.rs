@@ -2,10 +2,14 @@ use std::collections::HashMap;
 use std::fmt::Display;
 use std::marker::PhantomData;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::schedule::Schedule;
 
-use super::{init, Mode};
+use super::init::secrets::SecretEnvironment;
+use super::init::systemd::credentials::Credentials;
+use super::limits::{IoSchedulingClass, Limits};
+use super::{init, Hardening, Mode};
 
 pub struct PathIsSet;
 pub struct PathNotSet;
@@ -33,9 +37,273 @@ impl ToAssign for UserInstall {}
 pub trait ToAssign {}
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum Trigger {
     OnSchedule(Schedule),
     OnBoot,
+    /// Once on boot, optionally after a delay, and on the schedule from then
+    /// on
+    OnBootAndSchedule {
+        schedule: Schedule,
+        boot_delay: Option<Duration>,
+    },
+    /// A socket unit listens and starts the service on the first connection
+    OnConnection(Vec<Listen>),
+    /// A path unit starts the service when one of the paths changes
+    OnPathChange(Vec<WatchPath>),
+}
+
+/// What the socket of a socket activated service listens on, see
+/// [`Spec::on_connection`]. The address is a port, `address:port` or the path
+/// of a unix socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    /// TCP or a unix stream socket, `ListenStream=`
+    Stream(String),
+    /// UDP or a unix datagram socket, `ListenDatagram=`
+    Datagram(String),
+}
+
+/// A path watched by a service started on path change, see
+/// [`Spec::on_path_change`]. The path must be absolute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchPath {
+    /// Start the service when a file is closed after writing or is created,
+    /// deleted or moved, `PathChanged=`
+    Changed(PathBuf),
+    /// Start the service while the directory contains files,
+    /// `DirectoryNotEmpty=`
+    DirectoryNotEmpty(PathBuf),
+}
+
+impl WatchPath {
+    /// The `[Path]` setting
+    pub(crate) fn directive(&self) -> String {
+        match self {
+            WatchPath::Changed(path) => format!("PathChanged={}", path.display()),
+            WatchPath::DirectoryNotEmpty(path) => {
+                format!("DirectoryNotEmpty={}", path.display())
+            }
+        }
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        match self {
+            WatchPath::Changed(path) | WatchPath::DirectoryNotEmpty(path) => path,
+        }
+    }
+}
+
+/// Where the value of a credential comes from, see [`Spec::credential`]
+#[derive(Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    /// Read from this file when the service starts, `LoadCredential=`. The
+    /// path must be absolute.
+    File(PathBuf),
+    /// Stored in the unit file, `SetCredentialEncrypted=` if `systemd-creds`
+    /// can encrypt it otherwise `SetCredential=`
+    Inline(Vec<u8>),
+}
+
+/// Never print inline values
+impl std::fmt::Debug for CredentialSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CredentialSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            CredentialSource::Inline(_) => f.write_str("Inline(..)"),
+        }
+    }
+}
+
+impl Listen {
+    /// The `[Socket]` setting
+    pub(crate) fn directive(&self) -> String {
+        match self {
+            Listen::Stream(address) => format!("ListenStream={address}"),
+            Listen::Datagram(address) => format!("ListenDatagram={address}"),
+        }
+    }
+
+    pub(crate) fn address(&self) -> &str {
+        match self {
+            Listen::Stream(address) | Listen::Datagram(address) => address,
+        }
+    }
+}
+
+impl Trigger {
+    pub(crate) fn schedule(&self) -> Option<&Schedule> {
+        match self {
+            Trigger::OnSchedule(schedule) | Trigger::OnBootAndSchedule { schedule, .. } => {
+                Some(schedule)
+            }
+            Trigger::OnBoot | Trigger::OnConnection(_) | Trigger::OnPathChange(_) => None,
+        }
+    }
+}
+
+/// Random delay added to each scheduled run
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Jitter {
+    pub(crate) max: Duration,
+    /// The same delay every run instead of a new one each run
+    pub(crate) fixed: bool,
+}
+
+/// When the init system should restart the service after it exits, set using
+/// [`Spec::restart`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restart {
+    /// Only if it exits with a non zero exit code or is killed by a signal
+    OnFailure,
+    /// Whenever it exits, even if it exited successfully
+    Always,
+}
+
+/// How and how often the init system restarts the service, by default it is
+/// not restarted
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RestartPolicy {
+    pub(crate) when: Option<Restart>,
+    /// Time to wait before restarting
+    pub(crate) delay: Option<Duration>,
+    /// Give up after this many starts within the interval
+    pub(crate) limit: Option<StartLimit>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StartLimit {
+    pub(crate) burst: u32,
+    pub(crate) interval: Duration,
+}
+
+/// Which processes of the service may send readiness and watchdog
+/// notifications, set using [`Spec::notify_access`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyAccess {
+    /// Only the main process, the default
+    Main,
+    /// The main process and the processes it starts directly
+    Exec,
+    /// Every process of the service
+    All,
+}
+
+/// How the service tells systemd it is ready and still alive, by default it
+/// is ready as soon as it is started. Only used by systemd.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Readiness {
+    /// `Type=notify`, the service is ready once it sends `READY=1`
+    pub(crate) notify: bool,
+    /// Kill the service if it does not send `WATCHDOG=1` within this time
+    pub(crate) watchdog: Option<Duration>,
+    pub(crate) access: Option<NotifyAccess>,
+}
+
+impl Readiness {
+    /// The `[Service]` settings besides `Type=`, one per line
+    pub(crate) fn directives(&self) -> Vec<String> {
+        let mut directives = Vec::new();
+        if let Some(watchdog) = self.watchdog {
+            directives.push(format!("WatchdogSec={}ms", watchdog.as_millis()));
+        }
+        if let Some(access) = self.access {
+            let access = match access {
+                NotifyAccess::Main => "main",
+                NotifyAccess::Exec => "exec",
+                NotifyAccess::All => "all",
+            };
+            directives.push(format!("NotifyAccess={access}"));
+        }
+        directives
+    }
+}
+
+/// A section of a systemd unit file, see [`Spec::directive`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Unit,
+    Service,
+    /// Only exists if the service runs on a schedule
+    Timer,
+    /// Only exists if the service is started on connection
+    Socket,
+    /// Only exists if the service is started on path change
+    Path,
+    /// Goes into the timer, socket or path unit if the service is started by
+    /// one
+    Install,
+}
+
+impl Section {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Section::Unit => "Unit",
+            Section::Service => "Service",
+            Section::Timer => "Timer",
+            Section::Socket => "Socket",
+            Section::Path => "Path",
+            Section::Install => "Install",
+        }
+    }
+}
+
+/// Directives the builder does not model, added as is
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExtraDirectives {
+    pub(crate) directives: Vec<(Section, String, String)>,
+    /// Write them to `override.conf` drop-ins instead of the units
+    pub(crate) drop_in: bool,
+}
+
+impl ExtraDirectives {
+    /// `\nKey=value` for each directive in the section
+    pub(crate) fn lines(&self, section: Section) -> String {
+        self.directives
+            .iter()
+            .filter(|(s, ..)| *s == section)
+            .map(|(_, key, value)| format!("\n{key}={value}"))
+            .collect()
+    }
+
+    /// A directive that would corrupt the unit file
+    pub(crate) fn invalid(&self) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|(_, key, value)| {
+                key.is_empty()
+                    || !key.chars().all(|c| c.is_ascii_alphanumeric())
+                    || value.contains('\n')
+            })
+            .map(|(_, key, _)| key.as_str())
+    }
+}
+
+/// Units the service depends on or is ordered after, and the target that
+/// starts it on boot. Only used by systemd.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Dependencies {
+    /// Empty means `network.target`
+    pub(crate) after: Vec<String>,
+    pub(crate) wants: Vec<String>,
+    pub(crate) requires: Vec<String>,
+    pub(crate) binds_to: Vec<String>,
+    pub(crate) requires_mounts_for: Vec<PathBuf>,
+    /// None means `default.target` for user and `multi-user.target` for
+    /// system services
+    pub(crate) wanted_by: Option<String>,
+}
+
+impl Dependencies {
+    pub(crate) fn units(&self) -> impl Iterator<Item = &str> {
+        self.after
+            .iter()
+            .chain(&self.wants)
+            .chain(&self.requires)
+            .chain(&self.binds_to)
+            .chain(&self.wanted_by)
+            .map(String::as_str)
+    }
 }
 
 /// The configuration for the current install, needed to perform the
@@ -53,6 +321,8 @@ where
 {
     pub(crate) mode: Mode,
     pub(crate) path: Option<PathBuf>,
+    /// OCI image to run using podman instead of installing an executable
+    pub(crate) container_image: Option<String>,
     pub(crate) service_name: Option<String>,
     pub(crate) trigger: Option<Trigger>,
     pub(crate) description: Option<String>,
@@ -61,8 +331,21 @@ where
     pub(crate) args: Vec<String>,
     /// key: Environmental variable, value: the value for that variable
     pub(crate) environment: HashMap<String, String>,
+    pub(crate) secret_environment: SecretEnvironment,
     pub(crate) bin_name: &'static str,
     pub(crate) overwrite_existing: bool,
+    /// Catch up on scheduled runs missed while the system was off or asleep
+    pub(crate) run_if_missed: bool,
+    pub(crate) jitter: Option<Jitter>,
+    /// IANA timezone of the schedule, None means local time
+    pub(crate) timezone: Option<String>,
+    pub(crate) restart: RestartPolicy,
+    pub(crate) hardening: Hardening,
+    pub(crate) limits: Limits,
+    pub(crate) dependencies: Dependencies,
+    pub(crate) extra_directives: ExtraDirectives,
+    pub(crate) readiness: Readiness,
+    pub(crate) credentials: Credentials,
     /// None means all
     pub(crate) init_systems: Option<Vec<init::System>>,
 
@@ -133,6 +416,7 @@ impl Spec<PathNotSet, NameNotSet, TriggerNotSet, InstallTypeNotSet> {
         Spec {
             mode: Mode::System,
             path: None,
+            container_image: None,
             service_name: None,
             trigger: None,
             description: None,
@@ -140,8 +424,19 @@ impl Spec<PathNotSet, NameNotSet, TriggerNotSet, InstallTypeNotSet> {
             run_as: None,
             args: Vec::new(),
             environment: HashMap::new(),
+            secret_environment: SecretEnvironment::default(),
             bin_name,
             overwrite_existing: false,
+            run_if_missed: false,
+            jitter: None,
+            timezone: None,
+            restart: RestartPolicy::default(),
+            hardening: Hardening::default(),
+            limits: Limits::default(),
+            dependencies: Dependencies::default(),
+            extra_directives: ExtraDirectives::default(),
+            readiness: Readiness::default(),
+            credentials: Credentials::default(),
             init_systems: None,
 
             path_set: PhantomData {},
@@ -159,6 +454,7 @@ impl Spec<PathNotSet, NameNotSet, TriggerNotSet, InstallTypeNotSet> {
         Spec {
             mode: Mode::User,
             path: None,
+            container_image: None,
             service_name: None,
             trigger: None,
             description: None,
@@ -166,8 +462,19 @@ impl Spec<PathNotSet, NameNotSet, TriggerNotSet, InstallTypeNotSet> {
             run_as: None,
             args: Vec::new(),
             environment: HashMap::new(),
+            secret_environment: SecretEnvironment::default(),
             bin_name,
             overwrite_existing: false,
+            run_if_missed: false,
+            jitter: None,
+            timezone: None,
+            restart: RestartPolicy::default(),
+            hardening: Hardening::default(),
+            limits: Limits::default(),
+            dependencies: Dependencies::default(),
+            extra_directives: ExtraDirectives::default(),
+            readiness: Readiness::default(),
+            credentials: Credentials::default(),
             init_systems: None,
 
             path_set: PhantomData {},
@@ -242,6 +549,7 @@ where
         Spec {
             mode: self.mode,
             path: Some(path.into()),
+            container_image: None,
             service_name: self.service_name,
             trigger: self.trigger,
             description: self.description,
@@ -249,8 +557,19 @@ where
             run_as: self.run_as,
             args: self.args,
             environment: self.environment,
+            secret_environment: self.secret_environment,
             bin_name: self.bin_name,
             overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
             init_systems: self.init_systems,
 
             path_set: PhantomData {},
@@ -291,6 +610,7 @@ where
         Ok(Spec {
             mode: self.mode,
             path: Some(std::env::current_exe()?),
+            container_image: None,
             service_name: self.service_name,
             trigger: self.trigger,
             description: self.description,
@@ -298,8 +618,19 @@ where
             run_as: self.run_as,
             args: self.args,
             environment: self.environment,
+            secret_environment: self.secret_environment,
             bin_name: self.bin_name,
             overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
             init_systems: self.init_systems,
 
             path_set: PhantomData {},
@@ -309,6 +640,71 @@ where
         })
     }
 
+    /// Run an OCI image using podman instead of installing an executable. A
+    /// Podman Quadlet `.container` file is written to
+    /// `/etc/containers/systemd` (or `~/.config/containers/systemd` for user
+    /// installs). Systemd turns that into a service which is then started.
+    ///
+    /// This requires systemd and can only be combined with
+    /// [`on_boot`](Self::on_boot). The [`args`](Self::args) are passed to the
+    /// container and the [`working_dir`](Self::working_dir) is the directory
+    /// inside the container. Replaces any previously set path.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_user!()
+    ///     .container_image("ghcr.io/example/weather_checker:latest")
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn container_image(
+        self,
+        image: impl Into<String>,
+    ) -> Spec<PathIsSet, Name, TriggerSet, InstallType> {
+        Spec {
+            mode: self.mode,
+            path: None,
+            container_image: Some(image.into()),
+            service_name: self.service_name,
+            trigger: self.trigger,
+            description: self.description,
+            working_dir: self.working_dir,
+            run_as: self.run_as,
+            args: self.args,
+            environment: self.environment,
+            secret_environment: self.secret_environment,
+            bin_name: self.bin_name,
+            overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
+            init_systems: self.init_systems,
+
+            path_set: PhantomData {},
+            name_set: PhantomData {},
+            trigger_set: PhantomData {},
+            install_type: PhantomData {},
+        }
+    }
+
     /// Name to give the systemd service or cron job
     ///
     /// Only needed for *install*. During uninstall we recognize
@@ -340,6 +736,7 @@ where
         Spec {
             mode: self.mode,
             path: self.path,
+            container_image: self.container_image,
             service_name: Some(service_name.to_string()),
             trigger: self.trigger,
             description: self.description,
@@ -347,8 +744,19 @@ where
             run_as: self.run_as,
             args: self.args,
             environment: self.environment,
+            secret_environment: self.secret_environment,
             bin_name: self.bin_name,
             overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
             init_systems: self.init_systems,
 
             path_set: PhantomData {},
@@ -387,6 +795,7 @@ where
         Spec {
             mode: self.mode,
             path: self.path,
+            container_image: self.container_image,
             service_name: self.service_name,
             trigger: Some(Trigger::OnSchedule(schedule)),
             description: self.description,
@@ -394,8 +803,19 @@ where
             run_as: self.run_as,
             args: self.args,
             environment: self.environment,
+            secret_environment: self.secret_environment,
             bin_name: self.bin_name,
             overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
             init_systems: self.init_systems,
 
             path_set: PhantomData {},
@@ -431,6 +851,7 @@ where
         Spec {
             mode: self.mode,
             path: self.path,
+            container_image: self.container_image,
             service_name: self.service_name,
             trigger: Some(Trigger::OnBoot),
             description: self.description,
@@ -438,8 +859,19 @@ where
             run_as: self.run_as,
             args: self.args,
             environment: self.environment,
+            secret_environment: self.secret_environment,
             bin_name: self.bin_name,
             overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
             init_systems: self.init_systems,
 
             path_set: PhantomData {},
@@ -449,7 +881,15 @@ where
         }
     }
 
-    /// The description for the installed service
+    /// Start the service when a client connects to one of the addresses. A
+    /// `<name>.socket` unit listens on them, it is enabled instead of the
+    /// service. The service is started once and then gets all connections.
+    /// Take over the listening sockets using [`runtime::listen_fds`], they are
+    /// passed in the order given here.
+    ///
+    /// Only systemd supports this: installing fails unless systemd is
+    /// available and allowed.
+    ///
     /// # Example
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -459,53 +899,131 @@ where
     /// #     };
     /// # }
     /// #
+    /// use service_install::install::Listen;
+    ///
     /// install_user!()
     ///     .current_exe()?
-    ///     .service_name("weather_checker")
-    ///     .description("Sends a notification if a storm is coming")
-    ///     .on_boot()
+    ///     .service_name("weather_server")
+    ///     .on_connection([
+    ///         Listen::Stream("127.0.0.1:8080".to_owned()),
+    ///         Listen::Datagram("8125".to_owned()),
+    ///     ])
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn description(mut self, description: impl Display) -> Self {
-        self.description = Some(description.to_string());
-        self
+    ///
+    /// [`runtime::listen_fds`]: crate::runtime::listen_fds
+    pub fn on_connection(
+        self,
+        listen: impl IntoIterator<Item = Listen>,
+    ) -> Spec<Path, Name, TriggerIsSet, InstallType> {
+        Spec {
+            mode: self.mode,
+            path: self.path,
+            container_image: self.container_image,
+            service_name: self.service_name,
+            trigger: Some(Trigger::OnConnection(listen.into_iter().collect())),
+            description: self.description,
+            working_dir: self.working_dir,
+            run_as: self.run_as,
+            args: self.args,
+            environment: self.environment,
+            secret_environment: self.secret_environment,
+            bin_name: self.bin_name,
+            overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
+            init_systems: self.init_systems,
+
+            path_set: PhantomData {},
+            name_set: PhantomData {},
+            trigger_set: PhantomData {},
+            install_type: PhantomData {},
+        }
     }
 
-    /// Should the installer overwrite existing files? Default is false
+    /// Start the service whenever one of the paths changes, for example when a
+    /// file lands in a spool directory. A `<name>.path` unit watches them, it
+    /// is enabled instead of the service. The service is not started again
+    /// while it is running.
     ///
-    /// Note: we do not even try replace a value if the installed and to be installed
-    /// files are identical. This setting only applies to scenarios where there are
-    /// files taking up the install location that are different to what would be installed.
+    /// Only systemd supports this: installing fails unless systemd is
+    /// available and allowed.
     ///
     /// # Example
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// # macro_rules! install_user {
+    /// # macro_rules! install_system {
     /// #     () => {
-    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
     /// #     };
     /// # }
     /// #
-    /// install_user!()
+    /// use service_install::install::WatchPath;
+    ///
+    /// install_system!()
     ///     .current_exe()?
-    ///     .service_name("weather_checker")
-    ///     .overwrite_existing(true)
-    ///     .on_boot()
+    ///     .service_name("importer")
+    ///     .on_path_change([WatchPath::DirectoryNotEmpty("/var/spool/importer".into())])
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn overwrite_existing(mut self, overwrite: bool) -> Self {
-        self.overwrite_existing = overwrite;
-        self
+    pub fn on_path_change(
+        self,
+        watch: impl IntoIterator<Item = WatchPath>,
+    ) -> Spec<Path, Name, TriggerIsSet, InstallType> {
+        Spec {
+            mode: self.mode,
+            path: self.path,
+            container_image: self.container_image,
+            service_name: self.service_name,
+            trigger: Some(Trigger::OnPathChange(watch.into_iter().collect())),
+            description: self.description,
+            working_dir: self.working_dir,
+            run_as: self.run_as,
+            args: self.args,
+            environment: self.environment,
+            secret_environment: self.secret_environment,
+            bin_name: self.bin_name,
+            overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
+            init_systems: self.init_systems,
+
+            path_set: PhantomData {},
+            name_set: PhantomData {},
+            trigger_set: PhantomData {},
+            install_type: PhantomData {},
+        }
     }
 
-    /// The args will be shell escaped. If any arguments where already set
-    /// this adds to them
+    /// Start the job on boot and then on a schedule. Use `boot_delay` to wait
+    /// a while after boot before the first run. See the [Schedule] docs for
+    /// how to configure the schedule.
+    ///
+    /// On systemd the timer gets an `OnBootSec=` next to the schedule. On cron
+    /// two rules are added: an `@reboot` rule and one for the schedule.
+    ///
     /// # Example
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -515,23 +1033,61 @@ where
     /// #     };
     /// # }
     /// #
+    /// use std::time::Duration;
+    /// use service_install::Schedule;
+    ///
+    /// let schedule = Schedule::Every(Duration::from_secs(60 * 60));
     /// install_user!()
     ///     .current_exe()?
     ///     .service_name("weather_checker")
-    ///     .on_boot()
-    ///     .args(["check", "--location", "North Holland"])
+    ///     .on_boot_and_schedule(schedule, Some(Duration::from_secs(120)))
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
-        self.args.extend(args.into_iter().map(Into::into));
-        self
+    pub fn on_boot_and_schedule(
+        self,
+        schedule: Schedule,
+        boot_delay: Option<Duration>,
+    ) -> Spec<Path, Name, TriggerIsSet, InstallType> {
+        Spec {
+            mode: self.mode,
+            path: self.path,
+            container_image: self.container_image,
+            service_name: self.service_name,
+            trigger: Some(Trigger::OnBootAndSchedule {
+                schedule,
+                boot_delay,
+            }),
+            description: self.description,
+            working_dir: self.working_dir,
+            run_as: self.run_as,
+            args: self.args,
+            environment: self.environment,
+            secret_environment: self.secret_environment,
+            bin_name: self.bin_name,
+            overwrite_existing: self.overwrite_existing,
+            run_if_missed: self.run_if_missed,
+            jitter: self.jitter,
+            timezone: self.timezone,
+            restart: self.restart,
+            hardening: self.hardening,
+            limits: self.limits,
+            dependencies: self.dependencies,
+            extra_directives: self.extra_directives,
+            readiness: self.readiness,
+            credentials: self.credentials,
+            init_systems: self.init_systems,
+
+            path_set: PhantomData {},
+            name_set: PhantomData {},
+            trigger_set: PhantomData {},
+            install_type: PhantomData {},
+        }
     }
 
-    /// The argument will be shell escaped. This does not clear previous set
-    /// arguments but adds to it
+    /// The description for the installed service
     /// # Example
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -544,25 +1100,23 @@ where
     /// install_user!()
     ///     .current_exe()?
     ///     .service_name("weather_checker")
+    ///     .description("Sends a notification if a storm is coming")
     ///     .on_boot()
-    ///     .arg("check")
-    ///     .arg("--location")
-    ///     .arg("North Holland")
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn arg(mut self, arg: impl Into<String>) -> Self {
-        self.args.push(arg.into());
+    pub fn description(mut self, description: impl Display) -> Self {
+        self.description = Some(description.to_string());
         self
     }
 
-    /// Environmental variables passed to the program when it runs. If you set the
-    /// same variable multiple times only the last value will be set for the program.
+    /// Should the installer overwrite existing files? Default is false
     ///
-    /// # Panics
-    /// If any part of any of the environmental variables pairs contains an equal sign.
+    /// Note: we do not even try replace a value if the installed and to be installed
+    /// files are identical. This setting only applies to scenarios where there are
+    /// files taking up the install location that are different to what would be installed.
     ///
     /// # Example
     /// ```no_run
@@ -575,33 +1129,29 @@ where
     /// #
     /// install_user!()
     ///     .current_exe()?
-    ///     .service_name("cli")
+    ///     .service_name("weather_checker")
+    ///     .overwrite_existing(true)
     ///     .on_boot()
-    ///     .env_vars([("WAYLAND_display","wayland-1"), ("SHELL", "/bin/bash")])
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn env_vars<S: Into<String>>(mut self, args: impl IntoIterator<Item = (S, S)>) -> Self {
-        let vars = args
-            .into_iter()
-            .map(|(a, b)| (a.into(), b.into()))
-            .inspect(|(var, _)| {
-                assert!(
-                    var.contains(['=']),
-                    "The 'key' of environmental variables may not contain an equal sign"
-                )
-            });
-        self.environment.extend(vars);
+    pub fn overwrite_existing(mut self, overwrite: bool) -> Self {
+        self.overwrite_existing = overwrite;
         self
     }
 
-    /// Environmental variable passed to the program when it runs. If you set the
-    /// same variable multiple times only the last value will be set for the program.
+    /// Run a scheduled job as soon as possible if its last scheduled time
+    /// passed while the system was off or asleep. Only applies to
+    /// [`Schedule::Daily`], an interval simply continues after wake up.
     ///
-    /// # Panics
-    /// If any part of any of the environmental variables pairs contains an equal sign.
+    /// On systemd this makes the timer persistent. On cron a system wide
+    /// install registers an anacron job if anacron is available. Otherwise the
+    /// cron rule runs every minute and only starts the service once per day at
+    /// or after the scheduled time, remembering the last run in a file in
+    /// `~/.local/state/service-install`. The install steps tell you which
+    /// mechanism was picked.
     ///
     /// # Example
     /// ```no_run
@@ -612,18 +1162,682 @@ where
     /// #     };
     /// # }
     /// #
+    /// use service_install::Schedule;
+    /// use time::Time;
+    ///
+    /// let schedule = Schedule::Daily(Time::from_hms(10, 42, 0).unwrap());
     /// install_user!()
     ///     .current_exe()?
-    ///     .service_name("cli")
-    ///     .on_boot()
-    ///     .env_var("SHELL", "/bin/bash")
+    ///     .service_name("weather_checker")
+    ///     .on_schedule(schedule)
+    ///     .run_if_missed()
     ///     .prepare_install()?
     ///     .install()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn env_var(mut self, variable: impl Into<String>, value: impl Into<String>) -> Self {
-        self.environment.insert(variable.into(), value.into());
+    pub fn run_if_missed(mut self) -> Self {
+        self.run_if_missed = true;
+        self
+    }
+
+    /// The IANA timezone the schedule is in, for example `Europe/Amsterdam`.
+    /// By default schedules are in local time. Must be known to the system's
+    /// timezone database (`/usr/share/zoneinfo`).
+    ///
+    /// Systemd adds the timezone to `OnCalendar=`. Cron installs in
+    /// `/etc/cron.d` use `CRON_TZ=` if cron is cronie, otherwise the schedule
+    /// is converted to local time using the current offsets. A converted
+    /// schedule is off by an hour whenever only one of the two timezones
+    /// observes daylight saving time. Cron expressions and monthly runs moving
+    /// to another day can not be converted.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use service_install::Schedule;
+    /// use time::Time;
+    ///
+    /// let schedule = Schedule::Daily(Time::from_hms(9, 0, 0).unwrap());
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_schedule(schedule)
+    ///     .timezone("Europe/Amsterdam")
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Delay each scheduled run by a random time up to `max`. Use this to
+    /// spread the load when many machines install the same schedule. Has no
+    /// effect when starting on boot.
+    ///
+    /// On systemd this sets `RandomizedDelaySec=`, a new delay is picked every
+    /// run. Cron can not do that, there the command sleeps for a delay derived
+    /// from the hostname and service name. That delay is the same every run
+    /// and never longer than the interval of a [`Schedule::Every`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use std::time::Duration;
+    /// use service_install::Schedule;
+    /// use time::Time;
+    ///
+    /// let schedule = Schedule::Daily(Time::from_hms(3, 0, 0).unwrap());
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_schedule(schedule)
+    ///     .jitter(Duration::from_secs(30 * 60))
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn jitter(mut self, max: Duration) -> Self {
+        self.jitter = Some(Jitter { max, fixed: false });
+        self
+    }
+
+    /// Like [`jitter`](Self::jitter) but every run is delayed by the same
+    /// amount. The delay differs between machines and services. On systemd
+    /// this adds `FixedRandomDelay=true`, on cron it is the same as `jitter`.
+    pub fn fixed_jitter(mut self, max: Duration) -> Self {
+        self.jitter = Some(Jitter { max, fixed: true });
+        self
+    }
+
+    /// Restart the service when it exits. By default a service that crashes
+    /// stays down until it is started again, for a service started on boot
+    /// that means the next reboot.
+    ///
+    /// On systemd this sets `Restart=`, on dinit `restart =`. OpenRC's
+    /// `supervise-daemon`, runit and s6 restart services whenever they exit,
+    /// they only support [`Restart::Always`]. runit and s6 are skipped without
+    /// it as they can not leave the service stopped. The other init systems
+    /// and cron can not restart a service, they are skipped and installing
+    /// fails if none of the available init systems can. [`Restart::Always`]
+    /// can not be combined with a schedule, the service would never stop
+    /// running.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use std::time::Duration;
+    /// use service_install::install::Restart;
+    ///
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .restart(Restart::OnFailure)
+    ///     .restart_delay(Duration::from_secs(5))
+    ///     .start_limit(5, Duration::from_secs(60))
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn restart(mut self, when: Restart) -> Self {
+        self.restart.when = Some(when);
+        self
+    }
+
+    /// Time to wait before restarting the service, see
+    /// [`restart`](Self::restart). Sets `RestartSec=` on systemd,
+    /// `restart-delay` on dinit and `respawn_delay` on OpenRC. Not supported
+    /// by runit and s6, they wait one second.
+    pub fn restart_delay(mut self, delay: Duration) -> Self {
+        self.restart.delay = Some(delay);
+        self
+    }
+
+    /// Stop restarting the service if it was started more than `burst` times
+    /// within `interval`, see [`restart`](Self::restart). Sets
+    /// `StartLimitBurst=` and `StartLimitIntervalSec=` on systemd,
+    /// `restart-limit-count` and `restart-limit-interval` on dinit and
+    /// `respawn_max` and `respawn_period` on OpenRC. Not supported by runit and
+    /// s6.
+    pub fn start_limit(mut self, burst: u32, interval: Duration) -> Self {
+        self.restart.limit = Some(StartLimit { burst, interval });
+        self
+    }
+
+    /// Only consider the service started once it reports it is ready, sets
+    /// `Type=notify`. Installing waits for this and fails if the service does
+    /// not report readiness. Call [`runtime::notify_ready`] from the service
+    /// once it is ready, for example after it opened its sockets.
+    ///
+    /// Only systemd supports this: if set installing fails unless systemd is
+    /// available and allowed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use std::time::Duration;
+    ///
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .notify_ready()
+    ///     .watchdog(Duration::from_secs(30))
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`runtime::notify_ready`]: crate::runtime::notify_ready
+    pub fn notify_ready(mut self) -> Self {
+        self.readiness.notify = true;
+        self
+    }
+
+    /// Kill the service if it does not call [`runtime::notify_watchdog`] at
+    /// least once every `interval`, combine with [`restart`](Self::restart)
+    /// to restart it. Sets `WatchdogSec=` and implies
+    /// [`notify_ready`](Self::notify_ready). The service can get the interval
+    /// using [`runtime::watchdog_interval`].
+    ///
+    /// [`runtime::notify_watchdog`]: crate::runtime::notify_watchdog
+    /// [`runtime::watchdog_interval`]: crate::runtime::watchdog_interval
+    pub fn watchdog(mut self, interval: Duration) -> Self {
+        self.readiness.notify = true;
+        self.readiness.watchdog = Some(interval);
+        self
+    }
+
+    /// Which processes of the service may send notifications, sets
+    /// `NotifyAccess=`. Needed if a child process reports readiness. Implies
+    /// [`notify_ready`](Self::notify_ready).
+    pub fn notify_access(mut self, access: NotifyAccess) -> Self {
+        self.readiness.notify = true;
+        self.readiness.access = Some(access);
+        self
+    }
+
+    /// Sandbox the service, see [`Hardening`] for the presets and settings.
+    /// Only systemd can do this: if hardening is set installing fails unless
+    /// systemd is available and allowed. The sandbox is listed in the install
+    /// steps.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use service_install::install::Hardening;
+    ///
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .hardening(Hardening::strict().read_write_path("/var/lib/weather_checker"))
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hardening(mut self, hardening: Hardening) -> Self {
+        self.hardening = hardening;
+        self
+    }
+
+    /// Limit the memory the service may use to `bytes`.
+    ///
+    /// Systemd sets `MemoryMax=`, the service is killed if it uses more. The
+    /// other init systems and cron limit the address space using `prlimit`
+    /// or `ulimit` instead. The address space is larger than the memory
+    /// actually used, leave some room.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use service_install::install::IoSchedulingClass;
+    ///
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("data_collector")
+    ///     .on_boot()
+    ///     .memory_max(256 * 1024 * 1024)
+    ///     .nice(10)
+    ///     .io_scheduling_class(IoSchedulingClass::Idle)
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn memory_max(mut self, bytes: u64) -> Self {
+        self.limits.memory_max = Some(bytes);
+        self
+    }
+
+    /// Limit the CPU time of the service to `percent` of a single CPU, use
+    /// more than 100 for multiple CPUs. Sets `CPUQuota=`, needs systemd.
+    pub fn cpu_quota(mut self, percent: u32) -> Self {
+        self.limits.cpu_quota = Some(percent);
+        self
+    }
+
+    /// Limit the number of processes and threads of the service. Sets
+    /// `TasksMax=`, needs systemd.
+    pub fn tasks_max(mut self, tasks: u32) -> Self {
+        self.limits.tasks_max = Some(tasks);
+        self
+    }
+
+    /// The niceness of the service from -20 (highest priority) to 19 (lowest
+    /// priority). Sets `Nice=` on systemd, the other init systems and cron
+    /// start the service using `nice`.
+    pub fn nice(mut self, niceness: i8) -> Self {
+        self.limits.nice = Some(niceness);
+        self
+    }
+
+    /// Sets `IOSchedulingClass=` on systemd, the other init systems and cron
+    /// start the service using `ionice`.
+    pub fn io_scheduling_class(mut self, class: IoSchedulingClass) -> Self {
+        self.limits.io_scheduling_class = Some(class);
+        self
+    }
+
+    /// The maximum number of files the service can have open. Sets
+    /// `LimitNOFILE=` on systemd, the other init systems and cron use
+    /// `prlimit` or `ulimit`.
+    pub fn open_files_limit(mut self, files: u64) -> Self {
+        self.limits.open_files = Some(files);
+        self
+    }
+
+    /// Start the service after this unit, for example `network-online.target`
+    /// or `postgresql.service`. Can be called multiple times. Replaces the
+    /// default: `After=network.target`.
+    ///
+    /// Dependencies are only used by systemd. Unit names are checked when
+    /// preparing the install.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("data_collector")
+    ///     .on_boot()
+    ///     .after("network-online.target")
+    ///     .wants("network-online.target")
+    ///     .after("postgresql.service")
+    ///     .requires("postgresql.service")
+    ///     .requires_mounts_for("/srv/data")
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn after(mut self, unit: impl Into<String>) -> Self {
+        self.dependencies.after.push(unit.into());
+        self
+    }
+
+    /// Also start this unit when starting the service, the service still
+    /// starts if it fails. Sets `Wants=`, combine with
+    /// [`after`](Self::after) to wait for it.
+    pub fn wants(mut self, unit: impl Into<String>) -> Self {
+        self.dependencies.wants.push(unit.into());
+        self
+    }
+
+    /// Also start this unit when starting the service, the service fails to
+    /// start if it fails and is stopped when it stops. Sets `Requires=`,
+    /// combine with [`after`](Self::after) to wait for it.
+    pub fn requires(mut self, unit: impl Into<String>) -> Self {
+        self.dependencies.requires.push(unit.into());
+        self
+    }
+
+    /// Like [`requires`](Self::requires) but the service also stops when
+    /// the unit disappears or stops for any reason. Sets `BindsTo=`.
+    pub fn binds_to(mut self, unit: impl Into<String>) -> Self {
+        self.dependencies.binds_to.push(unit.into());
+        self
+    }
+
+    /// Wait for the file systems needed to access this path to be mounted.
+    /// Sets `RequiresMountsFor=`.
+    pub fn requires_mounts_for(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dependencies.requires_mounts_for.push(path.into());
+        self
+    }
+
+    /// The target that starts the service on boot, sets `WantedBy=`. The
+    /// default is `default.target` for user and `multi-user.target` for
+    /// system services. Has no effect on services started by a timer, socket
+    /// or path unit.
+    pub fn wanted_by(mut self, target: impl Into<String>) -> Self {
+        self.dependencies.wanted_by = Some(target.into());
+        self
+    }
+
+    /// Add a directive the builder does not offer, for example
+    /// `OOMScoreAdjust=500`. It is added as is to the end of the section, for
+    /// settings that take a single value it overrides what the builder set.
+    /// Only used by systemd.
+    ///
+    /// Keys may only contain ASCII letters and digits and values can not span
+    /// multiple lines, this is checked when preparing the install.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use service_install::install::Section;
+    ///
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("data_collector")
+    ///     .on_boot()
+    ///     .directive(Section::Service, "OOMScoreAdjust", "500")
+    ///     .directive(Section::Unit, "Documentation", "https://example.org")
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn directive(
+        mut self,
+        section: Section,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.extra_directives
+            .directives
+            .push((section, key.into(), value.into()));
+        self
+    }
+
+    /// Write the [`directive`](Self::directive)s to drop-ins instead of the
+    /// units: `<name>.service.d/override.conf` and, for scheduled services,
+    /// `<name>.timer.d/override.conf`. The generated units stay as they would
+    /// be without the extra directives.
+    pub fn directives_in_drop_in(mut self) -> Self {
+        self.extra_directives.drop_in = true;
+        self
+    }
+
+    /// The args will be shell escaped. If any arguments where already set
+    /// this adds to them
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .args(["check", "--location", "North Holland"])
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// The argument will be shell escaped. This does not clear previous set
+    /// arguments but adds to it
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .arg("check")
+    ///     .arg("--location")
+    ///     .arg("North Holland")
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Environmental variables passed to the program when it runs. If you set the
+    /// same variable multiple times only the last value will be set for the program.
+    ///
+    /// # Panics
+    /// If any part of any of the environmental variables pairs contains an equal sign.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("cli")
+    ///     .on_boot()
+    ///     .env_vars([("WAYLAND_display","wayland-1"), ("SHELL", "/bin/bash")])
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env_vars<S: Into<String>>(mut self, args: impl IntoIterator<Item = (S, S)>) -> Self {
+        let vars = args
+            .into_iter()
+            .map(|(a, b)| (a.into(), b.into()))
+            .inspect(|(var, _)| {
+                assert!(
+                    var.contains(['=']),
+                    "The 'key' of environmental variables may not contain an equal sign"
+                )
+            });
+        self.environment.extend(vars);
+        self
+    }
+
+    /// Environmental variable passed to the program when it runs. If you set the
+    /// same variable multiple times only the last value will be set for the program.
+    ///
+    /// # Panics
+    /// If any part of any of the environmental variables pairs contains an equal sign.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("cli")
+    ///     .on_boot()
+    ///     .env_var("SHELL", "/bin/bash")
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env_var(mut self, variable: impl Into<String>, value: impl Into<String>) -> Self {
+        self.environment.insert(variable.into(), value.into());
+        self
+    }
+
+    /// Environmental variable passed to the program that should stay secret,
+    /// like an API token. Instead of inline in the world readable unit file or
+    /// crontab it is written to a separate file only the user running the
+    /// service can read: `/etc/<service_name>/secrets.env` for system and
+    /// `~/.config/<service_name>/secrets.env` for user installs. The file is
+    /// removed again together with the install.
+    ///
+    /// Systemd and Podman read the file using `EnvironmentFile=`, cron sources
+    /// it before running the command. The other init systems are skipped. A
+    /// secret variable replaces a normal one with the same name. Names may
+    /// only contain ASCII letters, digits and `_`, this is checked when
+    /// preparing the install.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_user {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_user("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// # let token = String::new();
+    /// install_user!()
+    ///     .current_exe()?
+    ///     .service_name("weather_checker")
+    ///     .on_boot()
+    ///     .env_var("RUST_LOG", "info")
+    ///     .secret_env_var("WEATHER_API_TOKEN", token)
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn secret_env_var(mut self, variable: impl Into<String>, value: impl Into<String>) -> Self {
+        self.secret_environment
+            .0
+            .insert(variable.into(), value.into());
+        self
+    }
+
+    /// Secret environmental variables, see
+    /// [`secret_env_var`](Self::secret_env_var).
+    pub fn secret_env_vars<S: Into<String>>(
+        mut self,
+        vars: impl IntoIterator<Item = (S, S)>,
+    ) -> Self {
+        self.secret_environment.0.extend(
+            vars.into_iter()
+                .map(|(var, value)| (var.into(), value.into())),
+        );
+        self
+    }
+
+    /// A password, key or other secret systemd hands to the service as the file
+    /// `$CREDENTIALS_DIRECTORY/<name>`. Read it using
+    /// [`runtime::credential`](crate::runtime::credential). Unlike environment
+    /// variables credentials are not inherited by child processes and only
+    /// readable by the service.
+    ///
+    /// A [`CredentialSource::File`] is loaded when the service starts, it may
+    /// be readable by root only. A [`CredentialSource::Inline`] value is
+    /// stored in the unit file. It is encrypted using `systemd-creds` when
+    /// that is installed, for a user install this needs systemd 256 or newer.
+    /// Otherwise it is stored as is in the world readable unit file. The
+    /// install steps never show unencrypted values.
+    ///
+    /// The name may only contain ASCII letters, digits, `_`, `-` and `.`. A
+    /// later credential with the same name replaces an earlier one. Requires
+    /// systemd.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # macro_rules! install_system {
+    /// #     () => {
+    /// #         service_install::install::Spec::__dont_use_use_the_macro_system("doctest")
+    /// #     };
+    /// # }
+    /// #
+    /// use service_install::install::CredentialSource;
+    ///
+    /// # let password = Vec::new();
+    /// install_system!()
+    ///     .current_exe()?
+    ///     .service_name("web_shop")
+    ///     .on_boot()
+    ///     .credential(
+    ///         "tls.key",
+    ///         CredentialSource::File("/etc/ssl/private/shop.key".into()),
+    ///     )
+    ///     .credential("db-password", CredentialSource::Inline(password))
+    ///     .prepare_install()?
+    ///     .install()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn credential(mut self, name: impl Into<String>, source: CredentialSource) -> Self {
+        self.credentials.insert(name.into(), source);
         self
     }
 
@@ -537,6 +537,16 @@ pub enum DisableError {
     #[error(transparent)]
     SystemD(#[from] init::systemd::DisableError),
     #[error(transparent)]
+    OpenRc(#[from] init::openrc::DisableError),
+    #[error(transparent)]
+    ServiceDir(#[from] init::service_dir::DisableError),
+    #[error(transparent)]
+    SysV(#[from] init::sysv::DisableError),
+    #[error(transparent)]
+    Dinit(#[from] init::dinit::DisableError),
+    #[error(transparent)]
+    CronD(#[from] init::cron::drop_in::Error),
+    #[error(transparent)]
     Cron(#[from] init::cron::disable::Error),
 }
 
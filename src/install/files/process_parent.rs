@@ -25,6 +25,7 @@ impl IdRes {
     fn from_tree_and_pid(
         tree: Vec<&Path>,
         pid: Pid,
+        target: &Path,
         init_systems: &[init::System],
     ) -> Result<IdRes, PathCheckError> {
         let Some(direct_parent) = tree.first() else {
@@ -32,7 +33,7 @@ impl IdRes {
         };
 
         for init in init_systems {
-            if init.is_init_path(direct_parent)? {
+            if init.is_init_path(direct_parent, target)? {
                 return Ok(IdRes::ParentIsInit {
                     init: init.clone(),
                     pid,
@@ -64,8 +65,7 @@ pub(crate) fn list(
 
     let using_target: Vec<_> = s
         .processes()
-        .iter()
-        .map(|(_, process)| process)
+        .values()
         .filter(|p| p.exe() == Some(target))
         .collect();
 
@@ -97,7 +97,7 @@ pub(crate) fn list(
             }
             (tree, p.pid())
         })
-        .map(|(tree, pid)| IdRes::from_tree_and_pid(tree, pid, init_systems))
+        .map(|(tree, pid)| IdRes::from_tree_and_pid(tree, pid, target, init_systems))
         .collect()
 }
 
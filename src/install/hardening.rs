@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use itertools::Itertools;
+
+use super::init::SystemdEscape;
+
+/// Sandbox settings for the service, set using
+/// [`Spec::hardening`](super::Spec::hardening). Start from a preset and adjust
+/// individual settings, or start from [`Hardening::default`] which changes
+/// nothing.
+///
+/// Only systemd can sandbox a service. For user services systemd applies most
+/// of these only if unprivileged user namespaces are available.
+///
+/// # Example
+/// ```
+/// use service_install::install::{Hardening, ProtectSystem};
+///
+/// let hardening = Hardening::basic()
+///     .protect_system(ProtectSystem::Strict)
+///     .read_write_path("/var/lib/weather_checker");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hardening {
+    no_new_privileges: bool,
+    protect_system: Option<ProtectSystem>,
+    protect_home: Option<ProtectHome>,
+    private_tmp: bool,
+    read_write_paths: Vec<PathBuf>,
+    /// Empty drops all capabilities
+    capabilities: Option<Vec<String>>,
+    system_call_filter: Vec<String>,
+}
+
+/// Which parts of the file system are mounted read only, `ProtectSystem=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectSystem {
+    /// `/usr` and the boot loader directories
+    Usr,
+    /// Like [`ProtectSystem::Usr`] and `/etc`
+    Full,
+    /// The entire file system except `/dev`, `/proc`, `/sys` and the paths
+    /// added using [`Hardening::read_write_path`]
+    Strict,
+}
+
+/// How the home directories are protected, `ProtectHome=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectHome {
+    /// `/home`, `/root` and `/run/user` appear empty and can not be accessed
+    Inaccessible,
+    /// They are mounted read only
+    ReadOnly,
+    /// An empty temporary file system is mounted over them
+    Tmpfs,
+}
+
+impl Hardening {
+    /// Protects the system without getting in the way of most services:
+    /// `NoNewPrivileges=true`, `ProtectSystem=full`, `ProtectHome=read-only`
+    /// and `PrivateTmp=true`.
+    #[must_use]
+    pub fn basic() -> Self {
+        Self {
+            no_new_privileges: true,
+            protect_system: Some(ProtectSystem::Full),
+            protect_home: Some(ProtectHome::ReadOnly),
+            private_tmp: true,
+            ..Self::default()
+        }
+    }
+
+    /// Only allows what a typical network service needs: like
+    /// [`basic`](Self::basic) but the entire file system is read only, the
+    /// home directories are hidden, all capabilities are dropped and only
+    /// system calls in the `@system-service` set are allowed.
+    ///
+    /// Add the paths the service writes to using
+    /// [`read_write_path`](Self::read_write_path). The executable of a user
+    /// install lives in the home directory, use
+    /// [`ProtectHome::ReadOnly`] for those.
+    #[must_use]
+    pub fn strict() -> Self {
+        Self {
+            protect_system: Some(ProtectSystem::Strict),
+            protect_home: Some(ProtectHome::Inaccessible),
+            capabilities: Some(Vec::new()),
+            system_call_filter: vec!["@system-service".to_owned()],
+            ..Self::basic()
+        }
+    }
+
+    /// `NoNewPrivileges=`, the service and its children can not gain
+    /// privileges through setuid binaries or file capabilities
+    #[must_use]
+    pub fn no_new_privileges(mut self, enabled: bool) -> Self {
+        self.no_new_privileges = enabled;
+        self
+    }
+
+    /// `ProtectSystem=`
+    #[must_use]
+    pub fn protect_system(mut self, protect: ProtectSystem) -> Self {
+        self.protect_system = Some(protect);
+        self
+    }
+
+    /// `ProtectHome=`
+    #[must_use]
+    pub fn protect_home(mut self, protect: ProtectHome) -> Self {
+        self.protect_home = Some(protect);
+        self
+    }
+
+    /// `PrivateTmp=`, gives the service its own `/tmp` and `/var/tmp`
+    #[must_use]
+    pub fn private_tmp(mut self, enabled: bool) -> Self {
+        self.private_tmp = enabled;
+        self
+    }
+
+    /// Adds a path to `ReadWritePaths=`, it stays writable even if
+    /// [`protect_system`](Self::protect_system) or
+    /// [`protect_home`](Self::protect_home) would make it read only.
+    #[must_use]
+    pub fn read_write_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_write_paths.push(path.into());
+        self
+    }
+
+    /// `CapabilityBoundingSet=`, the service can never gain capabilities
+    /// other than these. For example `CAP_NET_BIND_SERVICE`, pass an empty
+    /// list to drop all of them.
+    #[must_use]
+    pub fn capabilities(
+        mut self,
+        capabilities: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.capabilities = Some(capabilities.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// `SystemCallFilter=`, only allow these system calls or groups of system
+    /// calls like `@system-service`. Prefix with `~` to deny instead. See
+    /// `systemd-analyze syscall-filter` for the groups.
+    #[must_use]
+    pub fn system_call_filter(
+        mut self,
+        filter: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.system_call_filter = filter.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The `[Service]` settings, one per line
+    pub(crate) fn directives(&self) -> Vec<String> {
+        let mut directives = Vec::new();
+        if self.no_new_privileges {
+            directives.push("NoNewPrivileges=true".to_owned());
+        }
+        if let Some(protect) = self.protect_system {
+            let value = match protect {
+                ProtectSystem::Usr => "true",
+                ProtectSystem::Full => "full",
+                ProtectSystem::Strict => "strict",
+            };
+            directives.push(format!("ProtectSystem={value}"));
+        }
+        if let Some(protect) = self.protect_home {
+            let value = match protect {
+                ProtectHome::Inaccessible => "true",
+                ProtectHome::ReadOnly => "read-only",
+                ProtectHome::Tmpfs => "tmpfs",
+            };
+            directives.push(format!("ProtectHome={value}"));
+        }
+        if self.private_tmp {
+            directives.push("PrivateTmp=true".to_owned());
+        }
+        if !self.read_write_paths.is_empty() {
+            let paths = self
+                .read_write_paths
+                .iter()
+                .map(|p| p.systemd_escape())
+                .join(" ");
+            directives.push(format!("ReadWritePaths={paths}"));
+        }
+        if let Some(capabilities) = &self.capabilities {
+            directives.push(format!("CapabilityBoundingSet={}", capabilities.join(" ")));
+        }
+        if !self.system_call_filter.is_empty() {
+            directives.push(format!(
+                "SystemCallFilter={}",
+                self.system_call_filter.join(" ")
+            ));
+        }
+        directives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        assert!(Hardening::default().directives().is_empty());
+        assert_eq!(
+            Hardening::basic().directives(),
+            [
+                "NoNewPrivileges=true",
+                "ProtectSystem=full",
+                "ProtectHome=read-only",
+                "PrivateTmp=true"
+            ]
+        );
+        assert_eq!(
+            Hardening::strict()
+                .read_write_path("/var/lib/weather checker")
+                .directives()[4..],
+            [
+                "ReadWritePaths=\"/var/lib/weather checker\"",
+                "CapabilityBoundingSet=",
+                "SystemCallFilter=@system-service"
+            ]
+        );
+    }
+}
@@ -2,17 +2,28 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 pub mod cron;
+pub mod dinit;
 pub(crate) mod extract_path;
+pub mod openrc;
+pub mod script;
+pub mod secrets;
+pub mod service_dir;
 pub mod systemd;
+pub mod sysv;
+pub mod xdg_autostart;
 
 use sysinfo::Pid;
 
 use crate::install::RemoveStep;
 
+use self::service_dir::Flavor;
+use self::systemd::credentials::Credentials;
 use self::systemd::FindExeError;
 
-use super::builder::Trigger;
+use super::builder::{Dependencies, ExtraDirectives, Jitter, Readiness, RestartPolicy, Trigger};
 use super::files::{DisableError, NoHomeError, TargetInUseError};
+use super::limits::Limits;
+use super::Hardening;
 use super::{InstallStep, Mode};
 
 type Steps = Vec<Box<dyn InstallStep>>;
@@ -22,6 +33,32 @@ type RSteps = Vec<Box<dyn RemoveStep>>;
 #[derive(Debug, Clone)]
 pub enum System {
     Systemd,
+    /// Writes an init script to `/etc/init.d` (or `~/.config/rc/init.d` for
+    /// user services). Can only start services on boot.
+    OpenRc,
+    /// Creates a service directory in `/etc/sv` (or `~/.config/sv`) and links
+    /// it into the directory watched by the running `runsvdir`. Can only start
+    /// services on boot.
+    Runit,
+    /// Creates a service directory in `/etc/s6/sv` (or `~/.config/s6/sv`) and
+    /// links it into the directory watched by the running `s6-svscan`. Can only
+    /// start services on boot.
+    S6,
+    /// Writes an LSB init script to `/etc/init.d` and registers it using
+    /// `update-rc.d` or `chkconfig`. Can only start system wide services on
+    /// boot.
+    SysV,
+    /// Writes a service description to `/etc/dinit.d` (or `~/.config/dinit.d`)
+    /// and enables it using `dinitctl enable`. Can only start services on
+    /// boot.
+    Dinit,
+    /// Writes a desktop entry to `~/.config/autostart` which the desktop
+    /// session starts on login. Only supports user installs started on boot.
+    XdgAutostart,
+    /// Writes a dedicated file to `/etc/cron.d`, only supports system wide
+    /// installs. User installs fall through to [`System::Cron`].
+    CronD,
+    /// Adds a rule to the crontab of the user (or `run_as` user).
     Cron,
 }
 
@@ -30,12 +67,26 @@ impl System {
     pub(crate) fn name(&self) -> &'static str {
         match self {
             System::Systemd => "Systemd",
+            System::OpenRc => "OpenRC",
+            System::Runit => "runit",
+            System::S6 => "s6",
+            System::SysV => "SysV",
+            System::Dinit => "dinit",
+            System::XdgAutostart => "XDG autostart",
+            System::CronD => "cron.d",
             System::Cron => "Cron",
         }
     }
     pub(crate) fn not_available(&self) -> Result<bool, SetupError> {
         match self {
             System::Systemd => systemd::not_available(),
+            System::OpenRc => Ok(openrc::not_available()),
+            System::Runit => Ok(service_dir::not_available(Flavor::Runit)),
+            System::S6 => Ok(service_dir::not_available(Flavor::S6)),
+            System::SysV => Ok(sysv::not_available()),
+            System::Dinit => Ok(dinit::not_available()),
+            System::XdgAutostart => Ok(xdg_autostart::not_available()),
+            System::CronD => Ok(cron::drop_in::not_available()),
             System::Cron => Ok(cron::not_available()),
         }
     }
@@ -48,6 +99,24 @@ impl System {
     ) -> Result<Vec<Box<dyn InstallStep>>, TargetInUseError> {
         match self {
             System::Systemd => Ok(systemd::disable_step(target, mode).map_err(DisableError::from)?),
+            System::OpenRc => Ok(openrc::disable_step(target, mode).map_err(DisableError::from)?),
+            System::Runit => Ok(service_dir::disable_step(Flavor::Runit, target, mode)
+                .map_err(DisableError::from)?),
+            System::S6 => {
+                Ok(service_dir::disable_step(Flavor::S6, target, mode)
+                    .map_err(DisableError::from)?)
+            }
+            System::SysV => Ok(sysv::disable_step(target).map_err(DisableError::from)?),
+            System::Dinit => Ok(dinit::disable_step(target, mode).map_err(DisableError::from)?),
+            // never recognized as the parent, see `xdg_autostart::is_init_path`
+            System::XdgAutostart => Ok(Vec::new()),
+            // cron.d and crontab jobs share the same parent process
+            System::CronD => match cron::drop_in::disable_step(target, pid)
+                .map_err(DisableError::from)?
+            {
+                Some(steps) => Ok(steps),
+                None => Ok(cron::disable::step(target, pid, run_as).map_err(DisableError::from)?),
+            },
             System::Cron => {
                 Ok(cron::disable::step(target, pid, run_as).map_err(DisableError::from)?)
             }
@@ -56,6 +125,13 @@ impl System {
     pub(crate) fn set_up_steps(&self, params: &Params) -> Result<Steps, SetupError> {
         match self {
             System::Systemd => systemd::set_up_steps(params),
+            System::OpenRc => openrc::set_up_steps(params),
+            System::Runit => service_dir::set_up_steps(Flavor::Runit, params),
+            System::S6 => service_dir::set_up_steps(Flavor::S6, params),
+            System::SysV => sysv::set_up_steps(params),
+            System::Dinit => dinit::set_up_steps(params),
+            System::XdgAutostart => xdg_autostart::set_up_steps(params),
+            System::CronD => cron::drop_in::set_up_steps(params),
             System::Cron => cron::set_up_steps(params),
         }
     }
@@ -67,17 +143,43 @@ impl System {
     ) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
         match self {
             System::Systemd => systemd::tear_down_steps(mode),
+            System::OpenRc => openrc::tear_down_steps(mode),
+            System::Runit => service_dir::tear_down_steps(Flavor::Runit, mode),
+            System::S6 => service_dir::tear_down_steps(Flavor::S6, mode),
+            System::SysV => sysv::tear_down_steps(mode),
+            System::Dinit => dinit::tear_down_steps(mode),
+            System::XdgAutostart => xdg_autostart::tear_down_steps(mode),
+            System::CronD => cron::drop_in::tear_down_steps(bin_name, mode, user),
             System::Cron => cron::tear_down_steps(bin_name, mode, user),
         }
     }
 
     pub(crate) fn all() -> Vec<System> {
-        vec![Self::Systemd, Self::Cron]
+        vec![
+            Self::Systemd,
+            Self::OpenRc,
+            Self::Runit,
+            Self::S6,
+            Self::SysV,
+            Self::Dinit,
+            Self::XdgAutostart,
+            Self::CronD,
+            Self::Cron,
+        ]
     }
 
-    pub(crate) fn is_init_path(&self, path: &Path) -> Result<bool, PathCheckError> {
+    /// Whether `path`, the parent of the process running `target`, is this
+    /// init system
+    pub(crate) fn is_init_path(&self, path: &Path, target: &Path) -> Result<bool, PathCheckError> {
         match self {
             System::Systemd => systemd::path_is_systemd(path),
+            System::OpenRc => Ok(openrc::is_init_path(path)),
+            System::Runit => Ok(service_dir::is_init_path(Flavor::Runit, path)),
+            System::S6 => Ok(service_dir::is_init_path(Flavor::S6, path)),
+            System::SysV => Ok(sysv::is_init_path(path, target)),
+            System::Dinit => Ok(dinit::is_init_path(path)),
+            System::XdgAutostart => Ok(xdg_autostart::is_init_path(path)),
+            System::CronD => Ok(cron::is_init_path(path)),
             System::Cron => Ok(cron::is_init_path(path)),
         }
     }
@@ -95,6 +197,48 @@ pub enum SetupError {
         #[source]
         systemd::Error,
     ),
+    #[error("Could not set up podman quadlet")]
+    Quadlet(
+        #[from]
+        #[source]
+        systemd::quadlet::Error,
+    ),
+    #[error("OpenRC specific error")]
+    OpenRc(
+        #[from]
+        #[source]
+        openrc::Error,
+    ),
+    #[error("runit/s6 specific error")]
+    ServiceDir(
+        #[from]
+        #[source]
+        service_dir::Error,
+    ),
+    #[error("SysV init specific error")]
+    SysV(
+        #[from]
+        #[source]
+        sysv::Error,
+    ),
+    #[error("dinit specific error")]
+    Dinit(
+        #[from]
+        #[source]
+        dinit::Error,
+    ),
+    #[error("XDG autostart specific error")]
+    XdgAutostart(
+        #[from]
+        #[source]
+        xdg_autostart::Error,
+    ),
+    #[error("Error while writing cron.d file")]
+    CronD(
+        #[from]
+        #[source]
+        cron::drop_in::Error,
+    ),
     #[error("Error while setting up crontab rule")]
     Cron(
         #[from]
@@ -113,12 +257,54 @@ pub enum TearDownError {
         #[source]
         cron::teardown::Error,
     ),
+    #[error("Error while removing cron.d file")]
+    CronD(
+        #[from]
+        #[source]
+        cron::drop_in::Error,
+    ),
     #[error("Error while setting up systemd service")]
     Systemd(
         #[from]
         #[source]
         systemd::Error,
     ),
+    #[error("Error while removing podman quadlet")]
+    Quadlet(
+        #[from]
+        #[source]
+        systemd::quadlet::Error,
+    ),
+    #[error("Error while removing OpenRC service")]
+    OpenRc(
+        #[from]
+        #[source]
+        openrc::Error,
+    ),
+    #[error("Error while removing runit/s6 service")]
+    ServiceDir(
+        #[from]
+        #[source]
+        service_dir::Error,
+    ),
+    #[error("Error while removing SysV service")]
+    SysV(
+        #[from]
+        #[source]
+        sysv::Error,
+    ),
+    #[error("Error while removing dinit service")]
+    Dinit(
+        #[from]
+        #[source]
+        dinit::Error,
+    ),
+    #[error("Error while removing XDG autostart entry")]
+    XdgAutostart(
+        #[from]
+        #[source]
+        xdg_autostart::Error,
+    ),
     #[error("Could not find current users home dir")]
     NoHome(
         #[from]
@@ -133,6 +319,20 @@ pub enum TearDownError {
         #[source]
         FindExeError,
     ),
+    #[error("Could not find path to executable in init script")]
+    FindingScriptExePath(#[source] script::FindExeError),
+    #[error("Could not find path to executable in dinit service description")]
+    FindingDinitExePath(
+        #[from]
+        #[source]
+        dinit::FindExeError,
+    ),
+    #[error("Could not find path to executable in XDG autostart entry")]
+    FindingDesktopEntryExePath(
+        #[from]
+        #[source]
+        xdg_autostart::FindExeError,
+    ),
     #[error(
         "Found multiple different paths in services, do not know which to remove, paths: {0:?}"
     )]
@@ -151,6 +351,21 @@ pub(crate) struct Params {
     pub(crate) working_dir: Option<PathBuf>,
 
     pub(crate) trigger: Trigger,
+    /// Catch up on a missed run of a calendar schedule
+    pub(crate) run_if_missed: bool,
+    pub(crate) jitter: Option<Jitter>,
+    /// IANA timezone of the schedule, `None` for local time
+    pub(crate) timezone: Option<String>,
+    pub(crate) restart: RestartPolicy,
+    pub(crate) hardening: Hardening,
+    pub(crate) limits: Limits,
+    pub(crate) dependencies: Dependencies,
+    pub(crate) extra_directives: ExtraDirectives,
+    pub(crate) readiness: Readiness,
+    /// Only used by systemd
+    pub(crate) credentials: Credentials,
+    /// Written to a file only the user running the service can read
+    pub(crate) secrets: Option<secrets::Secrets>,
     pub(crate) run_as: Option<String>,
     pub(crate) mode: Mode,
 }
@@ -161,6 +376,35 @@ impl Params {
             .clone()
             .unwrap_or_else(|| format!("starts {}", self.name))
     }
+
+    /// A system service started on boot without any options, tests override
+    /// the fields they check
+    #[cfg(test)]
+    pub(crate) fn for_test(name: &'static str, exe_path: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            bin_name: name,
+            description: None,
+            exe_path: PathBuf::from(exe_path),
+            exe_args: Vec::new(),
+            environment: HashMap::new(),
+            working_dir: None,
+            trigger: Trigger::OnBoot,
+            run_if_missed: false,
+            jitter: None,
+            timezone: None,
+            restart: RestartPolicy::default(),
+            hardening: Hardening::default(),
+            limits: Limits::default(),
+            dependencies: Dependencies::default(),
+            extra_directives: ExtraDirectives::default(),
+            readiness: Readiness::default(),
+            credentials: Credentials::default(),
+            secrets: None,
+            run_as: None,
+            mode: Mode::System,
+        }
+    }
 }
 
 pub(crate) const COMMENT_PREAMBLE: &str = "# created by: ";
@@ -209,7 +453,7 @@ impl ShellEscape for String {
 // or the end of line), in which case everything until the next matching
 // quote becomes part of the same item. Quotes themselves are removed. C-style
 // escapes are supported.
-trait SystemdEscape {
+pub(crate) trait SystemdEscape {
     fn systemd_escape(&self) -> String;
 }
 
@@ -1,14 +1,19 @@
 use crate::install::init::cron::teardown::path_from_rule;
 use std::fmt;
 use std::io::Write;
+use std::iter;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
+use itertools::Itertools;
+
 use super::{Params, SetupError, Steps};
 use crate::install::{RollbackError, RollbackStep};
+use crate::schedule::Schedule;
 use crate::Tense;
 
 pub mod disable;
+pub mod drop_in;
 pub mod setup;
 pub mod teardown;
 
@@ -37,6 +42,109 @@ pub(crate) fn is_init_path(path: &Path) -> bool {
     path.ends_with("cron")
 }
 
+/// How missed runs of a daily job are caught up, cron itself simply skips
+/// them.
+#[derive(Debug, Clone)]
+pub(crate) enum CatchUp {
+    /// anacron starts the job if it did not run today. The cron rule marks
+    /// the job as done for today before starting it.
+    Anacron { job: String },
+    /// The rule fires every minute and starts the job once a day at or after
+    /// the scheduled time. The date of the last run is kept in a file.
+    StampFile { name: String, time: time::Time },
+}
+
+const ANACRON_GUARD: &str = "anacron -u ";
+const STAMP_GUARD: &str = "test \"$(date +\\%H\\%M)\" -ge ";
+const STAMP_DIR: &str = "\"$HOME/.local/state/service-install\"";
+
+impl CatchUp {
+    /// For daily schedules when the user asked to run missed jobs
+    pub(crate) fn needed(params: &Params) -> Option<time::Time> {
+        match params.trigger.schedule() {
+            Some(Schedule::Daily(time)) if params.run_if_missed => Some(*time),
+            _ => None,
+        }
+    }
+
+    /// Time and date fields of the rule, replaces the ones of the schedule
+    fn time_spec(&self) -> Option<&'static str> {
+        match self {
+            CatchUp::Anacron { .. } => None,
+            CatchUp::StampFile { .. } => Some("* * * * *"),
+        }
+    }
+
+    /// Goes in front of the command in the cron rule
+    fn guard(&self) -> String {
+        match self {
+            CatchUp::Anacron { job } => format!("{ANACRON_GUARD}{job}; "),
+            CatchUp::StampFile { name, time } => {
+                let stamp = format!("{STAMP_DIR}/{name}");
+                let hhmm = format!("{:02}{:02}", time.hour(), time.minute());
+                format!(
+                    "{STAMP_GUARD}{hhmm} && test \"$(cat {stamp} 2>/dev/null)\" != \"$(date +\\%F)\" && mkdir -p {STAMP_DIR} && date +\\%F > {stamp} && "
+                )
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            CatchUp::Anacron { job } => {
+                format!("\n\t| missed runs: started by anacron, job: {job}")
+            }
+            CatchUp::StampFile { name, .. } => format!(
+                "\n\t| missed runs: checked every minute, last run kept in: ~/.local/state/service-install/{name}"
+            ),
+        }
+    }
+}
+
+/// Upcoming runs for a detailed description, empty when started on boot
+fn next_runs(schedule: Option<&Schedule>, timezone: Option<&str>) -> String {
+    schedule
+        .map(|schedule| format!("\n| next runs:\n|\t{}", schedule.preview(timezone)))
+        .unwrap_or_default()
+}
+
+/// Warns the schedule was moved from `timezone` to local time
+fn converted_from(timezone: Option<&str>) -> String {
+    timezone
+        .map(|timezone| {
+            format!(
+                "\n\t| timezone: converted from {timezone} to local time, off by an hour while only one of them observes daylight saving time"
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Strips what [`CatchUp::guard`] put in front of the command
+fn without_catch_up_guard(command: &str) -> &str {
+    if command.starts_with(ANACRON_GUARD) {
+        command
+            .split_once("; ")
+            .map_or(command, |(_guard, command)| command)
+    } else if command.starts_with(STAMP_GUARD) {
+        command.splitn(5, " && ").nth(4).unwrap_or(command)
+    } else {
+        command
+    }
+}
+
+/// Name usable as file name and anacron job identifier
+fn job_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 struct RollbackImpossible;
 impl RollbackStep for RollbackImpossible {
     fn perform(&mut self) -> Result<(), RollbackError> {
@@ -80,6 +188,44 @@ impl fmt::Display for Line {
     }
 }
 
+/// A rule added by an install together with the landmark comment above it
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    comments: Vec<Line>,
+    rule: Line,
+}
+
+impl Entry {
+    /// All rules directly below the landmark comment, an install that starts
+    /// on boot and on a schedule adds two.
+    fn find_all(crontab: &[Line], landmark_comment: &str) -> Vec<Self> {
+        crontab
+            .windows(landmark_comment.lines().count() + 1)
+            .map(|w| w.split_last().expect("window size always >= 2"))
+            .filter(|(_, comments)| comments.iter().map(Line::text).eq(landmark_comment.lines()))
+            .map(|(rule, comments)| Entry {
+                comments: comments.to_vec(),
+                rule: rule.clone(),
+            })
+            .collect()
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.comments.iter().chain(iter::once(&self.rule))
+    }
+
+    /// The comment and rule of each entry for a detailed description
+    fn describe_all(entries: &[Self]) -> String {
+        entries
+            .iter()
+            .map(|Entry { comments, rule }| {
+                let comment = comments.iter().map(|line| format!("\n|\t{line}")).join("");
+                format!("\n| comment:{comment}\n| rule:\n|\t{rule}")
+            })
+            .join("")
+    }
+}
+
 #[must_use]
 fn crontab_lines(text: &str) -> Vec<Line> {
     const HEADER_ADDED_BY_LIST_CMD: &str = "# DO NOT EDIT THIS FILE";
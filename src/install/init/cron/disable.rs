@@ -12,7 +12,7 @@ use sysinfo::Signal;
 
 use crate::install::init::autogenerated_comment;
 use crate::install::init::cron::setup::RemovePrevious;
-use crate::install::init::cron::Line;
+use crate::install::init::cron::{Entry, Line};
 use crate::install::InstallError;
 use crate::install::InstallStep;
 use crate::install::RollbackError;
@@ -48,16 +48,12 @@ pub(crate) fn step(
         .expect("file name is valid ascii");
     let landmark_comment = autogenerated_comment(bin_name);
 
-    let previous_install = crontab
-        .windows(landmark_comment.lines().count() + 1)
-        .map(|w| w.split_last().expect("window size always >= 2"))
-        .find(|(_, comments)| comments.iter().map(Line::text).eq(landmark_comment.lines()));
+    let previous_install = Entry::find_all(&crontab, &landmark_comment);
 
-    if let Some((rule, comment)) = previous_install {
+    if !previous_install.is_empty() {
         Ok(vec![
             Box::new(RemovePrevious {
-                comments: comment.to_vec(),
-                rule: rule.clone(),
+                entries: previous_install,
                 user: run_as.map(String::from),
             }) as Box<dyn InstallStep>,
             Box::new(Kill { pid }) as Box<dyn InstallStep>,
@@ -80,8 +76,8 @@ pub(crate) fn step(
     }
 }
 
-struct Kill {
-    pid: Pid,
+pub(super) struct Kill {
+    pub(super) pid: Pid,
 }
 
 impl InstallStep for Kill {
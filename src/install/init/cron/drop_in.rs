@@ -0,0 +1,721 @@
+//! System wide jobs as a dedicated file in `/etc/cron.d`. Unlike editing
+//! root's crontab our rule is kept apart from the admin's own rules and every
+//! change is a plain file operation that can be rolled back.
+
+use std::borrow::Cow;
+use std::fs::{self, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use itertools::Itertools;
+use sysinfo::Pid;
+
+use crate::install::init::secrets::{self, RemoveSecrets};
+use crate::install::init::{
+    autogenerated_comment, ExeLocation, Params, RSteps, SetupError, Steps, TearDownError,
+};
+use crate::install::{InstallError, InstallStep, Mode, RemoveError, RemoveStep};
+use crate::install::{RollbackError, RollbackStep, Tense};
+use crate::schedule::Schedule;
+
+use super::disable::Kill;
+use super::setup::{self, boot_command, command, in_local_time, jitter_delay, time_spec};
+use super::teardown::{path_from_command, secrets_from_command};
+use super::{current_crontab, job_name, CatchUp};
+
+const DIR: &str = "/etc/cron.d";
+const ANACRONTAB: &str = "/etc/anacrontab";
+const PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("/etc/cron.d only holds system wide jobs, user installs use the crontab")]
+    UserModeUnsupported,
+    #[error("cron can not restart a job that fails, it runs again on the next scheduled time")]
+    RestartUnsupported,
+    #[error("Could not write cron.d file at: {path}")]
+    Writing {
+        #[source]
+        e: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not remove cron.d file")]
+    Removing(#[source] std::io::Error),
+    #[error("Could not read the cron.d directory")]
+    ReadingDir(#[source] std::io::Error),
+    #[error("Could not read cron.d file at: {path}")]
+    Reading {
+        #[source]
+        e: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("cron has no escapes for environment variables, can not pass `{0}`. Names may only contain ASCII letters, digits and `_` and values must fit on one line and not contain both kinds of quotes")]
+    UnsupportedEnvVar(String),
+    #[error("No rule in the cron.d file we created at: {0}")]
+    NoRule(PathBuf),
+    #[error("The cron.d file at {0} changed since preparing, you should manually verify it")]
+    Changed(PathBuf),
+}
+
+pub(crate) fn not_available() -> bool {
+    super::not_available() || !Path::new(DIR).is_dir()
+}
+
+struct WriteDropIn {
+    content: String,
+    path: PathBuf,
+    catch_up: Option<CatchUp>,
+    /// `None` when started on boot
+    schedule: Option<Schedule>,
+    /// Set through `CRON_TZ=`
+    timezone: Option<String>,
+    /// Timezone the schedule was converted from
+    converted_from: Option<String>,
+}
+
+impl InstallStep for WriteDropIn {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let missed_runs = self
+            .catch_up
+            .as_ref()
+            .map(CatchUp::describe)
+            .unwrap_or_default();
+        let converted_from = super::converted_from(self.converted_from.as_deref());
+        format!(
+            "{verb} cron.d file{}\n\t| path: {path}{missed_runs}{converted_from}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.content.trim_end().replace('\n', "\n|\t");
+        let next_runs = super::next_runs(self.schedule.as_ref(), self.timezone.as_deref());
+        format!(
+            "{verb} cron.d file{}\n| path:\n|\t{path}\n| content:\n|\t{content}{next_runs}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let previous = if self.path.is_file() {
+            Some(read(&self.path)?)
+        } else {
+            None
+        };
+        write(&self.path, &self.content)?;
+
+        let path = self.path.clone();
+        Ok(Some(match previous {
+            Some(content) => Box::new(Restore { content, path }) as Box<dyn RollbackStep>,
+            None => Box::new(RemoveDropIn { path }),
+        }))
+    }
+}
+
+/// Removes a cron.d file left by a previous install under a different name
+struct RemovePrevious {
+    path: PathBuf,
+}
+
+impl InstallStep for RemovePrevious {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} cron.d file from previous installation{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let content = read(&self.path)?;
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(Some(Box::new(Restore {
+            content,
+            path: self.path.clone(),
+        })))
+    }
+}
+
+/// Comments out a rule starting the executable we are about to replace
+struct CommentOutRule {
+    path: PathBuf,
+    rule: String,
+}
+
+impl InstallStep for CommentOutRule {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Commented out",
+            Tense::Questioning => "Comment out",
+            Tense::Future => "Will comment out",
+            Tense::Active => "Commenting out",
+        };
+        format!(
+            "{verb} a cron.d rule that is preventing the installation{}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Commented out",
+            Tense::Questioning => "Comment out",
+            Tense::Future => "Will comment out",
+            Tense::Active => "Commenting out",
+        };
+        let Self { path, rule } = self;
+        let path = path.display();
+        format!(
+            "{verb} a cron.d rule that is preventing the installation{}\n| path:\n|\t{path}\n| rule:\n|\t{rule}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let original = read(&self.path)?;
+        let mut found = false;
+        let changed = original
+            .lines()
+            .map(|line| {
+                if line.trim() == self.rule {
+                    found = true;
+                    format!("# {line}")
+                } else {
+                    line.to_owned()
+                }
+            })
+            .join("\n")
+            + "\n";
+        if !found {
+            return Err(Error::Changed(self.path.clone()).into());
+        }
+        write(&self.path, &changed)?;
+
+        Ok(Some(Box::new(Restore {
+            content: original,
+            path: self.path.clone(),
+        })))
+    }
+}
+
+/// Replaces the anacron job of a previous install, or removes it if `entry`
+/// is `None`
+struct SetAnacronJob {
+    bin_name: String,
+    entry: Option<String>,
+}
+
+impl InstallStep for SetAnacronJob {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match (&tense, &self.entry) {
+            (Tense::Past, Some(_)) => "Registered",
+            (Tense::Questioning, Some(_)) => "Register",
+            (Tense::Future, Some(_)) => "Will register",
+            (Tense::Active, Some(_)) => "Registering",
+            (Tense::Past, None) => "Removed",
+            (Tense::Questioning, None) => "Remove",
+            (Tense::Future, None) => "Will remove",
+            (Tense::Active, None) => "Removing",
+        };
+        let what = if self.entry.is_some() {
+            "anacron job to catch up on missed runs"
+        } else {
+            "anacron job from previous installation"
+        };
+        format!("{verb} {what}{}\n\t| path: {ANACRONTAB}", tense.punct())
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        match &self.entry {
+            Some(entry) => format!("{}\n| job:\n|\t{entry}", self.describe(tense)),
+            None => self.describe(tense),
+        }
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let path = Path::new(ANACRONTAB);
+        let original = read(path)?;
+        let mut anacrontab =
+            without_anacron_job(&original, &self.bin_name).unwrap_or_else(|| original.clone());
+        if let Some(entry) = &self.entry {
+            let comment = autogenerated_comment(&self.bin_name);
+            anacrontab.push_str(&format!("{comment}\n{entry}\n"));
+        }
+        write(path, &anacrontab)?;
+
+        Ok(Some(Box::new(Restore {
+            content: original,
+            path: path.to_owned(),
+        })))
+    }
+}
+
+struct RemoveAnacronJob {
+    bin_name: String,
+}
+
+impl RemoveStep for RemoveAnacronJob {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        format!("{verb} anacron job{} from:\n|\t{ANACRONTAB}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        let path = Path::new(ANACRONTAB);
+        let anacrontab = read(path)?;
+        let anacrontab = without_anacron_job(&anacrontab, &self.bin_name)
+            .ok_or_else(|| Error::Changed(path.to_owned()))?;
+        write(path, &anacrontab)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct RemoveDropIn {
+    path: PathBuf,
+}
+
+impl RemoveStep for RemoveDropIn {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} cron.d file{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+struct Restore {
+    content: String,
+    path: PathBuf,
+}
+
+impl RollbackStep for Restore {
+    fn perform(&mut self) -> Result<(), RollbackError> {
+        write(&self.path, &self.content).map_err(RollbackError::RestoringCronD)
+    }
+
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Restored",
+            Tense::Questioning => "Restore",
+            Tense::Future => "Will restore",
+            Tense::Active => "Restoring",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} the original file{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+}
+
+pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.mode.is_user() {
+        return Err(Error::UserModeUnsupported.into());
+    }
+    if params.restart.when.is_some() {
+        return Err(Error::RestartUnsupported.into());
+    }
+
+    let converted_from = params.timezone.clone();
+    // the catch up guard compares against the local time
+    let params = &*if params.timezone.is_some() && !params.run_if_missed && is_cronie() {
+        Cow::Borrowed(params)
+    } else {
+        in_local_time(params)?
+    };
+    let converted_from = converted_from.filter(|_| params.timezone.is_none());
+
+    let job = job_name(&params.name);
+    let path = Path::new(DIR).join(&job);
+    let mut steps: Steps = params
+        .secrets
+        .iter()
+        .map(|secrets| secrets.step(params.bin_name, params.run_as.as_deref()))
+        .chain(
+            our_files(params.bin_name)?
+                .into_iter()
+                .map(|(previous, _)| previous)
+                .filter(|previous| *previous != path)
+                .map(|path| Box::new(RemovePrevious { path }) as Box<dyn InstallStep>),
+        )
+        .collect();
+    // installs from before cron.d was supported went into the crontab, if
+    // left there the job would run twice
+    let crontab = current_crontab(params.run_as.as_deref()).map_err(setup::Error::GetCrontab)?;
+    if let Some(previous) =
+        setup::RemovePrevious::find(&crontab, params.bin_name, params.run_as.as_deref())
+    {
+        steps.push(Box::new(previous));
+    }
+
+    // anacron can only mark the job as done if the rule runs as root
+    let anacron = Path::new(ANACRONTAB).is_file();
+    let catch_up = CatchUp::needed(params).map(|time| {
+        if anacron && params.run_as.is_none() {
+            CatchUp::Anacron { job: job.clone() }
+        } else {
+            CatchUp::StampFile {
+                name: job.clone(),
+                time,
+            }
+        }
+    });
+    if anacron {
+        let entry = match catch_up {
+            Some(CatchUp::Anacron { .. }) => Some(format!(
+                "1\t5\t{job}\t{}",
+                command(params, true, jitter_delay(params))
+            )),
+            _ => None,
+        };
+        let previous = without_anacron_job(&read(Path::new(ANACRONTAB))?, params.bin_name);
+        if entry.is_some() || previous.is_some() {
+            steps.push(Box::new(SetAnacronJob {
+                bin_name: params.bin_name.to_owned(),
+                entry,
+            }));
+        }
+    }
+
+    steps.push(Box::new(WriteDropIn {
+        content: render(params, catch_up.as_ref())?,
+        path,
+        catch_up,
+        schedule: params.trigger.schedule().cloned(),
+        timezone: params.timezone.clone(),
+        converted_from,
+    }));
+    Ok(steps)
+}
+
+/// Also removes the crontab rules of an install from before cron.d was
+/// supported, `prepare_remove` stops at the first init system with an
+/// install.
+pub(crate) fn tear_down_steps(
+    bin_name: &str,
+    mode: Mode,
+    user: Option<&str>,
+) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    if mode.is_user() || !Path::new(DIR).is_dir() {
+        return Ok(None);
+    }
+
+    let mut steps: RSteps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for (path, content) in our_files(bin_name)? {
+        let exe_path = rules(&content)
+            .find_map(exe_from_rule)
+            .ok_or_else(|| Error::NoRule(path.clone()))?;
+        exe_paths.push(exe_path);
+        let secrets = rules(&content)
+            .filter_map(command_from_rule)
+            .find_map(secrets_from_command)
+            .filter(|path| secrets::written_by(path, bin_name));
+        steps.push(Box::new(RemoveDropIn { path }));
+        if let Some(path) = secrets {
+            steps.push(Box::new(RemoveSecrets { path }));
+        }
+    }
+
+    if Path::new(ANACRONTAB).is_file()
+        && without_anacron_job(&read(Path::new(ANACRONTAB))?, bin_name).is_some()
+    {
+        steps.push(Box::new(RemoveAnacronJob {
+            bin_name: bin_name.to_owned(),
+        }));
+    }
+    if let Some((crontab_steps, exe_path)) = super::tear_down_steps(bin_name, mode, user)? {
+        steps.extend(crontab_steps);
+        exe_paths.push(exe_path);
+    }
+
+    exe_paths.sort();
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+/// Returns `None` if no cron.d rule starts the target
+pub(crate) fn disable_step(target: &Path, pid: Pid) -> Result<Option<Steps>, Error> {
+    if !Path::new(DIR).is_dir() {
+        return Ok(None);
+    }
+
+    for path in files()? {
+        let content = read(&path)?;
+        let Some(rule) =
+            rules(&content).find(|rule| exe_from_rule(rule).as_deref() == Some(target))
+        else {
+            continue;
+        };
+        return Ok(Some(vec![
+            Box::new(CommentOutRule {
+                path,
+                rule: rule.to_owned(),
+            }),
+            Box::new(Kill { pid }),
+        ]));
+    }
+    Ok(None)
+}
+
+fn render(params: &Params, catch_up: Option<&CatchUp>) -> Result<String, SetupError> {
+    let Params {
+        environment,
+        trigger,
+        run_as,
+        ..
+    } = params;
+
+    let mut vars: Vec<_> = environment.iter().collect();
+    vars.sort();
+    let env_lines: String = vars
+        .into_iter()
+        .map(|(key, val)| env_line(key, val).ok_or_else(|| Error::UnsupportedEnvVar(key.clone())))
+        .collect::<Result<_, _>>()?;
+
+    // an unescaped % ends the command in a cron rule
+    let command = command(params, false, jitter_delay(params)).replace('%', "\\%");
+    let guard = catch_up.map(CatchUp::guard).unwrap_or_default();
+    let user = run_as.as_deref().unwrap_or("root");
+    let boot_rule = boot_command(params, false)
+        .map(|command| format!("@reboot {user} {}\n", command.replace('%', "\\%")))
+        .unwrap_or_default();
+
+    let when = match catch_up.and_then(CatchUp::time_spec) {
+        Some(when) => when.to_owned(),
+        None => time_spec(trigger)?,
+    };
+    let cron_tz = params
+        .timezone
+        .as_ref()
+        .map(|timezone| format!("CRON_TZ={timezone}\n"))
+        .unwrap_or_default();
+    let comment = autogenerated_comment(params.bin_name);
+    Ok(format!(
+        "{comment}
+SHELL=/bin/sh
+PATH={PATH}
+{cron_tz}{env_lines}{boot_rule}{when} {user} {guard}{command}
+"
+    ))
+}
+
+/// cron takes a quoted value up to the matching quote and knows no escapes,
+/// use the quote the value does not contain. `None` if the variable can not
+/// be set safely, a newline would start a new rule.
+fn env_line(key: &str, val: &str) -> Option<String> {
+    let valid_key = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key || val.contains(['\n', '\r']) {
+        return None;
+    }
+    let quote = if !val.contains('"') {
+        '"'
+    } else if !val.contains('\'') {
+        '\''
+    } else {
+        return None;
+    };
+    Some(format!("{key}={quote}{val}{quote}\n"))
+}
+
+/// cronie supports `CRON_TZ=`, others like vixie cron or busybox do not
+fn is_cronie() -> bool {
+    Command::new("crond")
+        .arg("-V")
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains("cronie")
+                || String::from_utf8_lossy(&output.stderr).contains("cronie")
+        })
+}
+
+/// Lines that are rules, skips comments and environment settings
+fn rules(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(|c: char| c == '@' || c == '*' || c.is_ascii_digit()))
+}
+
+fn exe_from_rule(rule: &str) -> Option<PathBuf> {
+    let command = command_from_rule(rule)?;
+    let path = path_from_command(command).display().to_string();
+    Some(PathBuf::from(path.replace("\\%", "%")))
+}
+
+fn command_from_rule(rule: &str) -> Option<&str> {
+    // time and date fields (or a single @ nickname) followed by the user
+    let fields = if rule.starts_with('@') { 2 } else { 6 };
+    let mut command = rule;
+    for _ in 0..fields {
+        let end = command.trim_start().find(char::is_whitespace)?;
+        command = &command.trim_start()[end..];
+    }
+    Some(command)
+}
+
+/// Removes the comment and job added by an install of `bin_name`. Returns
+/// `None` if there is no such job.
+fn without_anacron_job(anacrontab: &str, bin_name: &str) -> Option<String> {
+    let landmark = autogenerated_comment(bin_name);
+    let landmark: Vec<_> = landmark.lines().collect();
+    let lines: Vec<_> = anacrontab.lines().collect();
+    let start = lines
+        .windows(landmark.len())
+        .position(|window| window == landmark.as_slice())?;
+    let end = (start + landmark.len() + 1).min(lines.len());
+    let mut kept = lines[..start].iter().chain(&lines[end..]);
+    Some(kept.join("\n") + "\n")
+}
+
+fn files() -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(DIR).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Files in cron.d created by an install of `bin_name` and their content
+fn our_files(bin_name: &str) -> Result<Vec<(PathBuf, String)>, Error> {
+    let landmark = autogenerated_comment(bin_name);
+    let mut ours = Vec::new();
+    for path in files()? {
+        let content = read(&path)?;
+        if content.contains(&landmark) {
+            ours.push((path, content));
+        }
+    }
+    Ok(ours)
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|e| Error::Reading {
+        e,
+        path: path.to_owned(),
+    })
+}
+
+/// cron refuses files that are writable by anyone but their owner
+fn write(path: &Path, content: &str) -> Result<(), Error> {
+    fs::write(path, content)
+        .and_then(|()| fs::set_permissions(path, Permissions::from_mode(0o644)))
+        .map_err(|e| Error::Writing {
+            e,
+            path: path.to_owned(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn boot_rule() {
+        let params = Params {
+            bin_name: "weather_checker",
+            exe_args: vec!["--threshold".to_owned(), "90%".to_owned()],
+            environment: HashMap::from([("RUST_LOG".to_owned(), "debug".to_owned())]),
+            working_dir: Some(PathBuf::from("/var/lib/weather")),
+            run_as: Some("weather".to_owned()),
+            ..Params::for_test("weather.checker", "/usr/bin/weather 100% checker")
+        };
+        let content = render(&params, None).unwrap();
+        let landmark = autogenerated_comment("weather_checker");
+        assert_eq!(
+            content,
+            format!(
+                "{landmark}\nSHELL=/bin/sh\n\
+                PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin\n\
+                RUST_LOG=\"debug\"\n\
+                @reboot weather cd /var/lib/weather && '/usr/bin/weather 100\\% checker' --threshold '90\\%'\n"
+            )
+        );
+
+        // `%` ends the command in cron.d, it is escaped
+        let exe = rules(&content).next().and_then(exe_from_rule);
+        assert_eq!(exe, Some(params.exe_path));
+        assert_eq!(job_name(&params.name), "weather_checker");
+    }
+
+    #[test]
+    fn migrates_crontab_install() {
+        let landmark = autogenerated_comment("weather_checker");
+        let crontab = super::super::crontab_lines(&format!(
+            "0 * * * * /bin/other\n{landmark}\n@reboot /usr/bin/weather_checker\n"
+        ));
+        let previous = setup::RemovePrevious::find(&crontab, "weather_checker", None).unwrap();
+        assert_eq!(
+            super::super::teardown::filter_out(&crontab, &previous.entries).unwrap(),
+            ["0 * * * * /bin/other"]
+        );
+        assert!(setup::RemovePrevious::find(&crontab, "other", None).is_none());
+    }
+
+    #[test]
+    fn env_values_can_not_add_rules() {
+        assert_eq!(
+            env_line("GREETING", "say \"hi\"").as_deref(),
+            Some("GREETING='say \"hi\"'\n")
+        );
+        assert_eq!(
+            env_line("GREETING", "it's").as_deref(),
+            Some("GREETING=\"it's\"\n")
+        );
+        assert_eq!(env_line("GREETING", "hi\n* * * * * root /bin/evil"), None);
+        assert_eq!(env_line("GREETING", "\"it's\""), None);
+        assert_eq!(env_line("PATH=/tmp GREETING", "hi"), None);
+
+        let params = Params {
+            environment: HashMap::from([("GREETING".to_owned(), "hi\nthere".to_owned())]),
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert!(render(&params, None).is_err());
+    }
+}
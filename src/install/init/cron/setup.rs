@@ -1,17 +1,24 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::iter;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use itertools::Itertools;
+use time::{Time, Weekday};
 
 use super::{teardown, Params, SetupError, Steps};
-use crate::install::builder::Trigger;
+use crate::install::builder::{Jitter, Trigger};
+use crate::install::init::secrets::Secrets;
 use crate::install::init::{autogenerated_comment, ShellEscape};
 use crate::install::{InstallError, InstallStep, RollbackStep, Tense};
+use crate::schedule::expr::Expr;
+use crate::schedule::timezone;
 use crate::schedule::Schedule;
 
-use super::Line;
 use super::RollbackImpossible;
 use super::{current_crontab, set_crontab};
+use super::{job_name, CatchUp, Entry, Line};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -31,35 +38,130 @@ pub enum Error {
     CrontabChanged,
     #[error("Could not find an existing install in crontab")]
     NoExistingInstallFound,
+    #[error("Could not get the current offset from UTC of timezone `{0}` using `date`")]
+    TimezoneOffset(String),
+    #[error("cron can not restart a job that fails, it runs again on the next scheduled time")]
+    RestartUnsupported,
+    #[error("The schedule can not be expressed as a cron rule, {0}")]
+    ScheduleUnsupported(&'static str),
+    #[error("cron can only run at intervals of whole minutes that evenly divide an hour or a day, a day or a week, got: {0:?}")]
+    IntervalUnsupported(Duration),
 }
 
-pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+/// The time and date fields of a cron rule, or `@reboot`. When starting on
+/// boot and on a schedule these are for the schedule, the boot rule comes
+/// from [`boot_command`].
+pub(super) fn time_spec(trigger: &Trigger) -> Result<String, Error> {
     use Schedule as S;
-    use Trigger::{OnBoot, OnSchedule};
 
-    let current = current_crontab(params.run_as.as_deref()).map_err(Error::GetCrontab)?;
-    let landmark_comment = autogenerated_comment(params.bin_name);
+    let Some(schedule) = trigger.schedule() else {
+        return Ok("@reboot".to_owned());
+    };
+    Ok(match schedule {
+        S::Daily(time) => format!("{} {} * * *", time.minute(), time.hour()),
+        S::DailyAt(times) => daily_at(times)?,
+        S::Hourly { minute } => format!("{minute} * * * *"),
+        S::Weekly { days, time } => {
+            let days = days
+                .iter()
+                .copied()
+                .map(Weekday::number_days_from_sunday)
+                .join(",");
+            format!("{} {} * * {days}", time.minute(), time.hour())
+        }
+        S::Monthly { day, time } => {
+            format!("{} {} {day} * *", time.minute(), time.hour())
+        }
+        S::Every(dur) => every(*dur)?,
+        S::Cron(expr) => {
+            Expr::parse_cron(expr).map_err(Error::ScheduleUnsupported)?;
+            expr.trim().to_owned()
+        }
+        S::Calendar(expr) => Expr::parse_calendar(expr)
+            .and_then(|expr| expr.to_cron())
+            .map_err(Error::ScheduleUnsupported)?,
+    })
+}
 
-    let to_remove = current
-        .windows(landmark_comment.lines().count() + 1)
-        .map(|w| w.split_last().expect("window size always >= 2"))
-        .find(|(_, comments)| comments.iter().map(Line::text).eq(landmark_comment.lines()));
+/// cron has no intervals, only steps through the minutes, hours or days of a
+/// clock. A step only gives a fixed interval if it evenly divides the next
+/// larger unit.
+fn every(interval: Duration) -> Result<String, Error> {
+    const HOUR: u64 = 60;
+    const DAY: u64 = 24 * HOUR;
+    const WEEK: u64 = 7 * DAY;
 
-    let mut steps = Vec::new();
-    if let Some((rule, comment)) = to_remove {
-        steps.push(Box::new(RemovePrevious {
-            comments: comment.to_vec(),
-            rule: rule.clone(),
-            user: params.run_as.clone(),
-        }) as Box<dyn InstallStep>);
+    let secs = interval.as_secs();
+    if secs == 0 || !secs.is_multiple_of(60) || interval.subsec_nanos() != 0 {
+        return Err(Error::IntervalUnsupported(interval));
     }
 
-    let when = match params.trigger {
-        OnSchedule(S::Daily(time)) => format!("{} {} * * *", time.minute(), time.hour()),
-        OnSchedule(S::Every(dur)) => format!("{}, * * * *", dur.as_secs()),
-        OnBoot => "@reboot".to_owned(),
-    };
+    Ok(match secs / 60 {
+        1 => "* * * * *".to_owned(),
+        minutes if minutes < HOUR && HOUR.is_multiple_of(minutes) => format!("*/{minutes} * * * *"),
+        HOUR => "0 * * * *".to_owned(),
+        minutes if minutes < DAY && minutes.is_multiple_of(HOUR) && DAY.is_multiple_of(minutes) => {
+            format!("0 */{} * * *", minutes / HOUR)
+        }
+        DAY => "0 0 * * *".to_owned(),
+        WEEK => "0 0 * * 0".to_owned(),
+        _ => return Err(Error::IntervalUnsupported(interval)),
+    })
+}
 
+/// A cron rule runs at every combination of its hours and minutes
+fn daily_at(times: &[Time]) -> Result<String, Error> {
+    let hours: BTreeSet<_> = times.iter().copied().map(Time::hour).collect();
+    let minutes: BTreeSet<_> = times.iter().copied().map(Time::minute).collect();
+    let wanted: BTreeSet<_> = times.iter().map(|t| (t.hour(), t.minute())).collect();
+    if wanted.len() != hours.len() * minutes.len() {
+        return Err(Error::ScheduleUnsupported(
+            "every hour in the schedule must run at the same minutes",
+        ));
+    }
+    Ok(format!(
+        "{} {} * * *",
+        minutes.iter().join(","),
+        hours.iter().join(",")
+    ))
+}
+
+/// Seconds to wait before starting the service. Cron can not pick a new
+/// random delay every run, instead this is derived from the hostname and
+/// service name. Never longer than the interval of [`Schedule::Every`].
+pub(super) fn jitter_delay(params: &Params) -> Option<u64> {
+    let schedule = params.trigger.schedule()?;
+    let Jitter { max, .. } = params.jitter?;
+    let max = match schedule {
+        Schedule::Every(interval) => max.min(*interval),
+        _ => max,
+    }
+    .as_secs();
+    if max == 0 {
+        return None;
+    }
+
+    let host = sysinfo::System::host_name().unwrap_or_default();
+    Some(stable_hash(&host, &params.name) % max)
+}
+
+/// FNV-1a, unlike the std hasher it gives the same delay no matter which
+/// Rust release built the installer
+fn stable_hash(host: &str, name: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    host.bytes()
+        .chain([0])
+        .chain(name.bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
+
+/// Starts the executable after sleeping `sleep` seconds, optionally exports
+/// the environment variables first. Secret variables are always sourced from
+/// their file.
+pub(super) fn command(params: &Params, export_env: bool, sleep: Option<u64>) -> String {
     let exe_path = params.exe_path.shell_escaped();
     let exe_args: String = params.exe_args.iter().map(String::shell_escaped).join(" ");
     let set_working_dir = params
@@ -68,25 +170,122 @@ pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
         .map(PathBuf::shell_escaped)
         .map(|dir| format!("cd {dir} && "))
         .unwrap_or_default();
-    let set_env_vars = if params.environment.is_empty() {
+    let sleep = sleep
+        .map(|secs| format!("sleep {secs} && "))
+        .unwrap_or_default();
+    let source_secrets = params
+        .secrets
+        .as_ref()
+        .map(Secrets::source_command)
+        .unwrap_or_default();
+    let set_env_vars = if !export_env || params.environment.is_empty() {
         String::new()
     } else {
-        "export".to_owned()
-            + &params
-                .environment
-                .iter()
-                .map(|(key, val)| format!("{}={}", key.shell_escaped(), val.shell_escaped()))
-                .join(" ")
-            + ";"
+        let vars = params
+            .environment
+            .iter()
+            .map(|(key, val)| format!("{}={}", key.shell_escaped(), val.shell_escaped()))
+            .join(" ");
+        format!("export {vars} && ")
+    };
+
+    let limits: String = params
+        .limits
+        .wrapper()
+        .into_iter()
+        .map(|word| format!("{word} "))
+        .collect();
+
+    format!("{sleep}{source_secrets}{set_env_vars}{set_working_dir}{limits}{exe_path} {exe_args}")
+}
+
+/// The command for the extra `@reboot` rule needed when starting on boot and
+/// on a schedule
+pub(super) fn boot_command(params: &Params, export_env: bool) -> Option<String> {
+    let Trigger::OnBootAndSchedule { boot_delay, .. } = &params.trigger else {
+        return None;
+    };
+    let sleep = boot_delay
+        .map(|delay| delay.as_secs())
+        .filter(|secs| *secs > 0);
+    Some(command(params, export_env, sleep))
+}
+
+/// Cron only knows local time. Returns the params with the schedule moved
+/// from its timezone to local time using the current offsets. The moved
+/// schedule is off by an hour while only one of the two timezones observes
+/// daylight saving time.
+pub(super) fn in_local_time(params: &Params) -> Result<Cow<'_, Params>, Error> {
+    let (Some(timezone), Some(schedule)) = (&params.timezone, params.trigger.schedule()) else {
+        return Ok(Cow::Borrowed(params));
+    };
+    let offset = |timezone| {
+        timezone::current_offset(timezone)
+            .ok_or_else(|| Error::TimezoneOffset(timezone.unwrap_or("local").to_owned()))
     };
+    let schedule = timezone::shift(schedule, offset(Some(timezone))?, offset(None)?)
+        .map_err(Error::ScheduleUnsupported)?;
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        "Cron does not support timezones, converted the schedule from {timezone} to local time. \
+        It will be off by an hour while only one of the two observes daylight saving time"
+    );
 
-    let command = format!("{set_env_vars}{set_working_dir}{exe_path} {exe_args}");
-    let rule = format!("{when} {command}");
+    let mut params = params.clone();
+    params.trigger = match params.trigger {
+        Trigger::OnBootAndSchedule { boot_delay, .. } => Trigger::OnBootAndSchedule {
+            schedule,
+            boot_delay,
+        },
+        _ => Trigger::OnSchedule(schedule),
+    };
+    params.timezone = None;
+    Ok(Cow::Owned(params))
+}
+
+pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.restart.when.is_some() {
+        return Err(Error::RestartUnsupported.into());
+    }
+    let converted_from = params.timezone.clone();
+    let params = &*in_local_time(params)?;
+    let converted_from = converted_from.filter(|_| params.timezone.is_none());
+    let current = current_crontab(params.run_as.as_deref()).map_err(Error::GetCrontab)?;
+    let landmark_comment = autogenerated_comment(params.bin_name);
+
+    let mut steps: Steps = params
+        .secrets
+        .iter()
+        .map(|secrets| secrets.step(params.bin_name, params.run_as.as_deref()))
+        .collect();
+    if let Some(previous) =
+        RemovePrevious::find(&current, params.bin_name, params.run_as.as_deref())
+    {
+        steps.push(Box::new(previous));
+    }
+
+    let catch_up = CatchUp::needed(params).map(|time| CatchUp::StampFile {
+        name: job_name(&params.name),
+        time,
+    });
+    let when = match catch_up.as_ref().and_then(CatchUp::time_spec) {
+        Some(when) => when.to_owned(),
+        None => time_spec(&params.trigger)?,
+    };
+    let guard = catch_up.as_ref().map(CatchUp::guard).unwrap_or_default();
+    let command = command(params, true, jitter_delay(params));
+    let mut rules = vec![format!("{when} {guard}{command}")];
+    if let Some(command) = boot_command(params, true) {
+        rules.insert(0, format!("@reboot {command}"));
+    }
 
     steps.push(Box::new(Add {
         user: params.run_as.clone(),
         comment: landmark_comment,
-        rule,
+        rules,
+        catch_up,
+        schedule: params.trigger.schedule().cloned(),
+        converted_from,
     }));
     Ok(steps)
 }
@@ -95,7 +294,33 @@ pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
 pub(crate) struct Add {
     pub(crate) user: Option<String>,
     pub(crate) comment: String,
-    pub(crate) rule: String,
+    /// Each rule is preceded by the comment
+    pub(crate) rules: Vec<String>,
+    pub(crate) catch_up: Option<CatchUp>,
+    /// `None` when started on boot
+    pub(crate) schedule: Option<Schedule>,
+    /// Timezone the schedule was converted from
+    pub(crate) converted_from: Option<String>,
+}
+
+impl Add {
+    /// How missed runs are handled and whether the schedule was converted
+    fn notes(&self) -> String {
+        let missed_runs = self
+            .catch_up
+            .as_ref()
+            .map(CatchUp::describe)
+            .unwrap_or_default();
+        missed_runs + &super::converted_from(self.converted_from.as_deref())
+    }
+
+    fn what(&self) -> &'static str {
+        if self.rules.len() > 1 {
+            "comments and rules"
+        } else {
+            "comment and rule"
+        }
+    }
 }
 
 impl InstallStep for Add {
@@ -106,13 +331,15 @@ impl InstallStep for Add {
             Tense::Future => "Will append",
             Tense::Active => "Appending",
         };
+        let notes = self.notes();
+        let what = self.what();
         if let Some(run_as) = &self.user {
             format!(
-                "{verb} comment and rule to {run_as}'s crontab{}",
+                "{verb} {what} to {run_as}'s crontab{}{notes}",
                 tense.punct()
             )
         } else {
-            format!("{verb} comment and rule to crontab{}", tense.punct())
+            format!("{verb} {what} to crontab{}{notes}", tense.punct())
         }
     }
 
@@ -123,19 +350,24 @@ impl InstallStep for Add {
             Tense::Future => "Will append",
             Tense::Active => "Appending",
         };
+        let notes = self.notes().replace("\n\t", "\n");
+        let next_runs = super::next_runs(self.schedule.as_ref(), None);
+        let what = self.what();
         let Self {
             comment,
-            rule,
+            rules,
             user,
+            ..
         } = self;
         let comment = comment.replace('\n', "\n|\t");
+        let rules = rules.join("\n|\t");
         if let Some(run_as) = user {
             format!(
-                "{verb} comment and rule to {run_as}'s crontab{}\n| comment:\n|\t{comment}\n| rule:\n|\t{rule}", tense.punct()
+                "{verb} {what} to {run_as}'s crontab{}\n| comment:\n|\t{comment}\n| rules:\n|\t{rules}{next_runs}{notes}", tense.punct()
             )
         } else {
             format!(
-                "{verb} comment and rule to crontab{}\n| comment:\n|\t{comment}\n| rule:\n|\t{rule}", tense.punct()
+                "{verb} {what} to crontab{}\n| comment:\n|\t{comment}\n| rules:\n|\t{rules}{next_runs}{notes}", tense.punct()
             )
         }
     }
@@ -143,15 +375,19 @@ impl InstallStep for Add {
     fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
         let Self {
             comment,
-            rule,
+            rules,
             user,
+            ..
         } = self.clone();
         let current_crontab = current_crontab(user.as_deref())?;
         let new_crontab: String = current_crontab
             .iter()
             .map(Line::text)
-            .chain(iter::once(comment.as_str()))
-            .chain(iter::once(rule.as_str()))
+            .chain(
+                rules
+                    .iter()
+                    .flat_map(|rule| [comment.as_str(), rule.as_str()]),
+            )
             .interleave_shortest(iter::once("\n").cycle())
             .chain(iter::once("\n")) // some say cron likes a newline at the end
             .collect();
@@ -161,10 +397,21 @@ impl InstallStep for Add {
     }
 }
 pub(crate) struct RemovePrevious {
-    pub(crate) comments: Vec<Line>,
-    pub(crate) rule: Line,
+    pub(crate) entries: Vec<Entry>,
     pub(crate) user: Option<String>,
 }
+
+impl RemovePrevious {
+    /// `None` if `crontab` has no rules added by an install of `bin_name`
+    pub(crate) fn find(crontab: &[Line], bin_name: &str, user: Option<&str>) -> Option<Self> {
+        let entries = Entry::find_all(crontab, &autogenerated_comment(bin_name));
+        (!entries.is_empty()).then(|| Self {
+            entries,
+            user: user.map(str::to_owned),
+        })
+    }
+}
+
 impl InstallStep for RemovePrevious {
     fn describe(&self, tense: Tense) -> String {
         let verb = match tense {
@@ -196,25 +443,18 @@ impl InstallStep for RemovePrevious {
             .as_ref()
             .map(|n| format!("{n}'s "))
             .unwrap_or_default();
-        #[allow(clippy::format_collect)]
-        let comment: String = self
-            .comments
-            .iter()
-            .map(|Line { pos, text }| format!("\n|\t{pos}: {text}"))
-            .collect();
-        let rule = format!("|\t{}: {}", self.rule.pos, self.rule.text);
-        format!("{verb} a comment and rule from previous installation from {user}crontab{}\n| comment:\t{comment}\n| rule:\n{rule}", tense.punct())
+        let entries = Entry::describe_all(&self.entries);
+        format!(
+            "{verb} comment and rule from previous installation from {user}crontab{}{entries}",
+            tense.punct()
+        )
     }
 
     fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
-        let Self {
-            comments,
-            rule,
-            user,
-        } = self;
+        let Self { entries, user } = self;
         let current_crontab = current_crontab(user.as_deref())?;
 
-        let new_lines = teardown::filter_out(&current_crontab, rule, comments)?;
+        let new_lines = teardown::filter_out(&current_crontab, entries)?;
 
         let new_crontab: String = new_lines
             .into_iter()
@@ -225,3 +465,127 @@ impl InstallStep for RemovePrevious {
         Ok(Some(Box::new(RollbackImpossible)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use time::Time;
+
+    use super::*;
+
+    fn cron(schedule: Schedule) -> Result<String, Error> {
+        time_spec(&Trigger::OnSchedule(schedule))
+    }
+
+    #[test]
+    fn calendar_schedules() {
+        let time = Time::from_hms(10, 42, 0).unwrap();
+        assert_eq!(cron(Schedule::Daily(time)).unwrap(), "42 10 * * *");
+        assert_eq!(cron(Schedule::Hourly { minute: 5 }).unwrap(), "5 * * * *");
+        let days = vec![Weekday::Sunday, Weekday::Wednesday];
+        assert_eq!(
+            cron(Schedule::Weekly { days, time }).unwrap(),
+            "42 10 * * 0,3"
+        );
+        assert_eq!(
+            cron(Schedule::Monthly { day: 15, time }).unwrap(),
+            "42 10 15 * *"
+        );
+    }
+
+    #[test]
+    fn intervals() {
+        let minutes = |m: u64| cron(Schedule::Every(Duration::from_secs(m * 60)));
+        let table = [
+            (1, "* * * * *"),
+            (5, "*/5 * * * *"),
+            (15, "*/15 * * * *"),
+            (30, "*/30 * * * *"),
+            (60, "0 * * * *"),
+            (2 * 60, "0 */2 * * *"),
+            (6 * 60, "0 */6 * * *"),
+            (12 * 60, "0 */12 * * *"),
+            (24 * 60, "0 0 * * *"),
+            (7 * 24 * 60, "0 0 * * 0"),
+        ];
+        for (interval, rule) in table {
+            assert_eq!(minutes(interval).unwrap(), rule, "every {interval} minutes");
+        }
+
+        for unsupported in [0, 7, 45, 90, 5 * 60, 2 * 24 * 60] {
+            assert!(
+                matches!(minutes(unsupported), Err(Error::IntervalUnsupported(_))),
+                "every {unsupported} minutes"
+            );
+        }
+        assert!(matches!(
+            cron(Schedule::Every(Duration::from_secs(90))),
+            Err(Error::IntervalUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn multiple_times_per_day() {
+        let at = |h, m| Time::from_hms(h, m, 0).unwrap();
+        let times = vec![at(8, 0), at(8, 30), at(20, 0), at(20, 30)];
+        assert_eq!(cron(Schedule::DailyAt(times)).unwrap(), "0,30 8,20 * * *");
+
+        let times = vec![at(8, 0), at(20, 30)];
+        assert!(matches!(
+            cron(Schedule::DailyAt(times)),
+            Err(Error::ScheduleUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn jitter_sleeps_less_than_the_interval() {
+        let mut params = Params {
+            trigger: Trigger::OnSchedule(Schedule::Every(Duration::from_secs(5 * 60))),
+            jitter: Some(Jitter {
+                max: Duration::from_secs(3600),
+                fixed: false,
+            }),
+            mode: crate::install::Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let delay = jitter_delay(&params).unwrap();
+        assert!(delay < 5 * 60);
+        assert_eq!(
+            jitter_delay(&params),
+            Some(delay),
+            "same delay every install"
+        );
+        let command = command(&params, false, jitter_delay(&params));
+        assert!(command.starts_with(&format!("sleep {delay} && ")));
+
+        params.trigger = Trigger::OnBoot;
+        assert_eq!(jitter_delay(&params), None);
+    }
+
+    #[test]
+    fn jitter_hash_is_stable() {
+        let hash = stable_hash("weatherstation", "weather_checker");
+        assert_eq!(hash, 17_244_158_477_331_086_177);
+        assert_eq!(hash % 3600, 977);
+    }
+
+    #[test]
+    fn boot_and_schedule() {
+        let schedule = Schedule::Daily(Time::from_hms(10, 42, 0).unwrap());
+        let mut params = Params {
+            trigger: Trigger::OnBootAndSchedule {
+                schedule: schedule.clone(),
+                boot_delay: Some(Duration::from_secs(90)),
+            },
+            mode: crate::install::Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert_eq!(time_spec(&params.trigger).unwrap(), "42 10 * * *");
+        assert_eq!(
+            boot_command(&params, false).unwrap(),
+            "sleep 90 && /usr/bin/weather_checker "
+        );
+
+        params.trigger = Trigger::OnSchedule(schedule);
+        assert_eq!(boot_command(&params, false), None);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::iter;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -5,11 +6,12 @@ use std::str::FromStr;
 use itertools::Itertools;
 
 use crate::install::init::extract_path;
+use crate::install::init::secrets::{self, RemoveSecrets};
 use crate::install::init::{autogenerated_comment, ExeLocation, RSteps, TearDownError};
 use crate::install::{Mode, Tense};
 use crate::install::{RemoveError, RemoveStep};
 
-use super::Line;
+use super::{Entry, Line};
 use super::{current_crontab, set_crontab, GetCrontabError};
 
 #[derive(Debug, thiserror::Error)]
@@ -25,17 +27,46 @@ pub enum Error {
 }
 
 pub(crate) fn path_from_rule(rule: &str) -> PathBuf {
-    let command = if let Some(command) = rule.strip_prefix("@reboot") {
+    path_from_command(&command_from_rule(rule))
+}
+
+fn command_from_rule(rule: &str) -> String {
+    if let Some(command) = rule.strip_prefix("@reboot") {
         command.to_string()
     } else {
         rule.splitn(5 + 1, char::is_whitespace).skip(5).collect()
+    }
+}
+
+/// The secret environment file sourced in the command part of a rule
+pub(crate) fn secrets_from_command(command: &str) -> Option<PathBuf> {
+    let command = super::without_catch_up_guard(command.trim_start());
+    let command = if command.starts_with("sleep ") {
+        command.split_once(" && ")?.1
+    } else {
+        command
     };
-    let command = match command.split_once("&&") {
-        Some((_cd, command)) => command.to_string(),
-        None => command,
-    };
+    secrets::path_from_command(command)
+}
+
+/// Path to the executable in the command part of a rule
+pub(crate) fn path_from_command(command: &str) -> PathBuf {
+    let mut command = super::without_catch_up_guard(command.trim_start());
+    for setup in ["sleep ", "set -a ", ". ", "set +a ", "export ", "cd "] {
+        if command.starts_with(setup) {
+            command = command
+                .split_once(" && ")
+                .map_or(command, |(_setup, command)| command);
+        }
+    }
+    // skip the resource limit wrappers, none of their words start with a `/`
+    while !command.starts_with('/') && !command.starts_with("'/") {
+        let Some((_wrapper, rest)) = command.split_once(' ') else {
+            break;
+        };
+        command = rest;
+    }
 
-    let command = command.trim_start();
     let command = extract_path::unshell_escape::split_unescaped_whitespace_once(command);
 
     PathBuf::from_str(&command).expect("infallible")
@@ -45,6 +76,7 @@ pub(crate) fn path_from_rule(rule: &str) -> PathBuf {
 mod test {
     use std::path::Path;
 
+    use super::super::CatchUp;
     use super::*;
 
     #[test]
@@ -55,6 +87,59 @@ mod test {
             Path::new("/home/david/.local/hi bin/cron_only")
         )
     }
+
+    #[test]
+    fn test_from_rule_with_catch_up() {
+        let exe = Path::new("/home/david/.local/hi bin/cron_only");
+        let command = "sleep 42 && export RUST_LOG=debug && cd /tmp && '/home/david/.local/hi bin/cron_only' -v";
+        let stamp_file = CatchUp::StampFile {
+            name: "cron_only".to_owned(),
+            time: time::Time::from_hms(10, 10, 0).unwrap(),
+        };
+        let case = format!("* * * * * {}{command}", stamp_file.guard());
+        assert_eq!(&path_from_rule(&case), exe);
+
+        let anacron = CatchUp::Anacron {
+            job: "cron_only".to_owned(),
+        };
+        let case = format!("10 10 * * * {}{command}", anacron.guard());
+        assert_eq!(&path_from_rule(&case), exe);
+    }
+
+    #[test]
+    fn test_from_rule_with_limits() {
+        let case = "@reboot cd /tmp && nice -n 10 prlimit --nofile=1024 '/home/david/.local/hi bin/cron_only' -v";
+        assert_eq!(
+            &path_from_rule(case),
+            Path::new("/home/david/.local/hi bin/cron_only")
+        )
+    }
+
+    #[test]
+    fn test_from_rule_with_secrets() {
+        let case = "@reboot sleep 5 && set -a && . '/home/david/.config/cron only/secrets.env' && set +a && export RUST_LOG=debug && '/home/david/.local/hi bin/cron_only' -v";
+        assert_eq!(
+            &path_from_rule(case),
+            Path::new("/home/david/.local/hi bin/cron_only")
+        );
+        assert_eq!(
+            secrets_from_command(&command_from_rule(case)).as_deref(),
+            Some(Path::new("/home/david/.config/cron only/secrets.env"))
+        );
+    }
+
+    #[test]
+    fn filter_out_all_entries() {
+        let crontab = super::super::crontab_lines(
+            "0 * * * * /bin/other\n# ours\n@reboot /bin/ours\n# ours\n0 0 * * * /bin/ours\n",
+        );
+        let entries = Entry::find_all(&crontab, "# ours");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            filter_out(&crontab, &entries).unwrap(),
+            ["0 * * * * /bin/other"]
+        );
+    }
 }
 
 pub(crate) fn tear_down_steps(
@@ -70,28 +155,27 @@ pub(crate) fn tear_down_steps(
     let current = current_crontab(user).map_err(Error::GetCrontab)?;
     let landmark_comment = autogenerated_comment(bin_name);
 
-    let to_remove = current
-        .windows(landmark_comment.lines().count() + 1)
-        .map(|w| w.split_last().expect("window size always >= 2"))
-        .find(|(_, comments)| comments.iter().map(Line::text).eq(landmark_comment.lines()));
-
-    let Some((rule, comment)) = to_remove else {
+    let entries = Entry::find_all(&current, &landmark_comment);
+    let Some(first) = entries.first() else {
         return Ok(None);
     };
 
-    let install_path = path_from_rule(&rule.text);
+    let install_path = path_from_rule(&first.rule.text);
+    let secrets = secrets_from_command(&command_from_rule(&first.rule.text));
     let step = Box::new(RemoveInstalled {
-        comments: comment.to_vec(),
-        rule: rule.clone(),
+        entries,
         user: user.map(str::to_owned),
     }) as Box<dyn RemoveStep>;
-    Ok(Some((vec![step], install_path)))
+    let mut steps = vec![step];
+    if let Some(path) = secrets.filter(|path| secrets::written_by(path, bin_name)) {
+        steps.push(Box::new(RemoveSecrets { path }));
+    }
+    Ok(Some((steps, install_path)))
 }
 
 struct RemoveInstalled {
     user: Option<String>,
-    comments: Vec<Line>,
-    rule: Line,
+    entries: Vec<Entry>,
 }
 
 impl RemoveStep for RemoveInstalled {
@@ -122,24 +206,14 @@ impl RemoveStep for RemoveInstalled {
             .as_ref()
             .map(|n| format!("{n}'s "))
             .unwrap_or_default();
-        #[allow(clippy::format_collect)]
-        let comment: String = self
-            .comments
-            .iter()
-            .map(|Line { pos, text }| format!("\n|\t{pos}: {text}"))
-            .collect();
-        let rule = format!("|\t{}: {}", self.rule.pos, self.rule.text);
-        format!("{verb} the installs comment and rule from {user}crontab:\n| comment:{comment}\n| rule:\n{rule}")
+        let entries = Entry::describe_all(&self.entries);
+        format!("{verb} the installs comment and rule from {user}crontab:{entries}")
     }
 
     fn perform(&mut self) -> Result<(), RemoveError> {
-        let Self {
-            comments,
-            rule,
-            user,
-        } = self;
+        let Self { entries, user } = self;
         let current_crontab = current_crontab(user.as_deref())?;
-        let new_lines = filter_out(&current_crontab, rule, comments)?;
+        let new_lines = filter_out(&current_crontab, entries)?;
 
         let new_crontab: String = new_lines
             .into_iter()
@@ -159,29 +233,28 @@ pub struct CrontabChanged;
 
 pub(super) fn filter_out<'a>(
     current_crontab: &'a [Line],
-    rule: &Line,
-    comments: &[Line],
+    entries: &[Entry],
 ) -> Result<Vec<&'a str>, CrontabChanged> {
     // someone could store the steps and execute later, if
     // anything changed refuse to remove lines and abort
+    let to_remove: HashMap<usize, &str> = entries
+        .iter()
+        .flat_map(Entry::lines)
+        .map(|line| (line.pos, line.text()))
+        .collect();
     let mut output = Vec::new();
-    let mut to_remove = comments.iter().chain(iter::once(rule)).fuse();
-    let mut next_to_remove = to_remove.next();
+    let mut removed = 0;
     for line in current_crontab {
-        if let Some(next) = next_to_remove {
-            if line.pos != next.pos {
-                continue;
-            }
-
-            if line.text != next.text {
-                return Err(CrontabChanged);
-            }
-
-            next_to_remove = to_remove.next();
-            continue;
+        match to_remove.get(&line.pos) {
+            Some(text) if *text == line.text => removed += 1,
+            Some(_) => return Err(CrontabChanged),
+            None => output.push(line.text.as_str()),
         }
-        output.push(line.text.as_str());
     }
 
-    Ok(output)
+    if removed == to_remove.len() {
+        Ok(output)
+    } else {
+        Err(CrontabChanged)
+    }
 }
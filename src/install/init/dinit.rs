@@ -0,0 +1,320 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{fs, io};
+
+use crate::install::files::NoHomeError;
+
+use super::{
+    ExeLocation, Mode, Params, RSteps, SetupError, Steps, TearDownError, COMMENT_PREAMBLE,
+    COMMENT_SUFFIX,
+};
+
+mod disable_existing;
+mod setup;
+mod teardown;
+
+pub(crate) use disable_existing::disable_step;
+pub use disable_existing::DisableError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("dinit can not start services on a schedule, use cron for that")]
+    ScheduleUnsupported,
+    #[error("dinit's env-file has no quoting, can not pass environment variable `{0}`. Names may only contain ASCII letters, digits and `_` and values must fit on one line")]
+    UnsupportedEnvVar(String),
+    #[error("There is no dinit instance running for {0}, could not find its control socket")]
+    NotRunning(Mode),
+    #[error("Could not write out service description to {path}")]
+    Writing {
+        #[source]
+        e: io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not remove the service description, error: {0}")]
+    Removing(#[source] io::Error),
+    #[error("Could not read the service description directory")]
+    ReadingDir(#[source] io::Error),
+    #[error("Could not verify service description was created by us, could not open it")]
+    Verifying(#[source] io::Error),
+    #[error("Could not run `{cmd}`")]
+    CouldNotRun {
+        #[source]
+        e: io::Error,
+        cmd: &'static str,
+    },
+    #[error("Command `{cmd}` failed, stderr: \"{stderr}\"")]
+    CommandFailed { cmd: String, stderr: String },
+}
+
+/// The executables location could not be found. It is needed to safely
+/// uninstall.
+#[derive(Debug, thiserror::Error)]
+pub enum FindExeError {
+    #[error("There is no `command` setting in the service description at: {0}")]
+    NoCommand(PathBuf),
+    #[error(
+        "Path to binary extracted from service description does not lead to a file, path: {0}"
+    )]
+    ExecPathNotFile(PathBuf),
+}
+
+/// Services of type `process` are direct children of dinit
+pub(crate) fn is_init_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "dinit")
+}
+
+pub(super) fn not_available() -> bool {
+    !system_socket().exists() && !user_socket().is_some_and(|s| s.exists())
+}
+
+pub(super) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported.into());
+    }
+    if let Some(var) = setup::unsupported_env_var(params) {
+        return Err(Error::UnsupportedEnvVar(var.to_owned()).into());
+    }
+    let running = match params.mode {
+        Mode::User => user_socket().is_some_and(|s| s.exists()),
+        Mode::System => system_socket().exists(),
+    };
+    if !running {
+        return Err(Error::NotRunning(params.mode).into());
+    }
+
+    let dir = match params.mode {
+        Mode::User => user_path()?,
+        Mode::System => system_path(),
+    };
+    Ok(setup::steps(&dir, params)?)
+}
+
+pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    let dir = match mode {
+        Mode::User => user_path()?,
+        Mode::System => system_path(),
+    };
+
+    let mut steps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for service in our_services(&dir)? {
+        let Some(name) = service.name() else {
+            continue;
+        };
+        steps.extend(teardown::disable_then_remove(&service, name, mode));
+        exe_paths.push(service.exe_path()?);
+    }
+
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+/// A dinit service description
+pub(crate) struct Service {
+    body: String,
+    path: PathBuf,
+}
+
+impl Service {
+    fn from_path(path: PathBuf) -> Result<Self, io::Error> {
+        Ok(Self {
+            body: fs::read_to_string(&path)?,
+            path,
+        })
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.path.file_name().and_then(OsStr::to_str)
+    }
+
+    fn our_service(&self) -> bool {
+        self.body.contains(COMMENT_PREAMBLE) && self.body.contains(COMMENT_SUFFIX)
+    }
+
+    /// Value of the first occurrence of `key`. Dinit accepts both `=` and `:`
+    /// as separator.
+    fn setting(&self, key: &str) -> Option<&str> {
+        self.body
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(['=', ':']))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, value)| value.trim())
+    }
+
+    fn exe(&self) -> Option<PathBuf> {
+        self.setting("command")
+            .map(first_word)
+            .filter(|word| !word.is_empty())
+            .map(PathBuf::from)
+    }
+
+    fn exe_path(&self) -> Result<PathBuf, FindExeError> {
+        let exe_path = self
+            .exe()
+            .ok_or_else(|| FindExeError::NoCommand(self.path.clone()))?;
+        if exe_path.is_file() {
+            Ok(exe_path)
+        } else {
+            Err(FindExeError::ExecPathNotFile(exe_path))
+        }
+    }
+
+    fn runs(&self, target: &Path) -> bool {
+        self.exe().is_some_and(|exe| exe == target)
+    }
+
+    fn env_file(&self) -> Option<PathBuf> {
+        self.setting("env-file").map(first_word).map(PathBuf::from)
+    }
+}
+
+fn our_services(dir: &Path) -> Result<Vec<Service>, Error> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut services = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let service = Service::from_path(path).map_err(Error::Verifying)?;
+        // the environment files we write next to the description carry no
+        // command and no landmark
+        if service.our_service() && service.setting("command").is_some() {
+            services.push(service);
+        }
+    }
+    Ok(services)
+}
+
+/// Quote a single argument so that dinit's command line splitting yields it
+/// unchanged. Dinit splits on whitespace, supports double quotes and uses a
+/// backslash to escape the next character.
+fn quoted(arg: &str) -> String {
+    let needs_quotes = arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | '#'));
+    if !needs_quotes {
+        return arg.to_owned();
+    }
+
+    let mut res = String::with_capacity(arg.len() + 2);
+    res.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '\\') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    res
+}
+
+/// Inverse of [`quoted`] for the first word of a dinit command line
+fn first_word(line: &str) -> String {
+    let mut word = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => word.extend(chars.next()),
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => break,
+            c => word.push(c),
+        }
+    }
+    word
+}
+
+fn user_path() -> Result<PathBuf, NoHomeError> {
+    Ok(home::home_dir().ok_or(NoHomeError)?.join(".config/dinit.d"))
+}
+
+fn system_path() -> PathBuf {
+    PathBuf::from("/etc/dinit.d")
+}
+
+fn system_socket() -> PathBuf {
+    PathBuf::from("/run/dinitctl")
+}
+
+/// A user instance puts its socket in the runtime dir, falling back to the
+/// home dir if there is none.
+fn user_socket() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("dinitctl"))
+        .filter(|socket| socket.exists())
+        .or_else(|| home::home_dir().map(|home| home.join(".dinitctl")))
+}
+
+fn dinitctl(action: &str, name: &str, mode: Mode) -> Result<(), Error> {
+    let mut command = Command::new("dinitctl");
+    command.arg(mode_arg(mode)).args([action, name]);
+    let output = command
+        .output()
+        .map_err(|e| Error::CouldNotRun { e, cmd: "dinitctl" })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let args = command.get_args().map(OsStr::to_string_lossy);
+        let cmd = std::iter::once("dinitctl".into())
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::CommandFailed { cmd, stderr })
+    }
+}
+
+fn mode_arg(mode: Mode) -> &'static str {
+    match mode {
+        Mode::User => "--user",
+        Mode::System => "--system",
+    }
+}
+
+fn is_started(name: &str, mode: Mode) -> Result<bool, Error> {
+    let output = Command::new("dinitctl")
+        .args([mode_arg(mode), "status", name])
+        .output()
+        .map_err(|e| Error::CouldNotRun { e, cmd: "dinitctl" })?;
+    // status fails if the service does not exist (or can not be loaded)
+    if !output.status.success() {
+        return Ok(false);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("State:"))
+        .any(|state| state.trim() == "STARTED"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting_round_trips() {
+        for arg in [
+            "/usr/bin/weather_checker",
+            "/usr/bin/weather checker",
+            "with \"quotes\" and \\ backslash",
+            "#not a comment",
+            "",
+        ] {
+            let line = format!("{} --some-arg", quoted(arg));
+            assert_eq!(first_word(&line), arg);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::Path;
+
+use crate::install::files::NoHomeError;
+use crate::install::{InstallError, InstallStep, RollbackError, RollbackStep};
+use crate::Tense;
+
+use super::{dinitctl, system_path, user_path, Mode, Service};
+
+struct Restart {
+    names: Vec<String>,
+    mode: Mode,
+}
+
+impl RollbackStep for Restart {
+    fn perform(&mut self) -> Result<(), RollbackError> {
+        for name in &self.names {
+            dinitctl("start", name, self.mode).map_err(RollbackError::ReEnablingDinit)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Restarted",
+            Tense::Active => "Restarting",
+            Tense::Questioning => "Restart",
+            Tense::Future => "Will restart",
+        };
+        format!(
+            "{verb} the dinit {} services that spawned the original file{}",
+            self.mode,
+            tense.punct()
+        )
+    }
+}
+
+struct Stop {
+    names: Vec<String>,
+    mode: Mode,
+}
+
+impl InstallStep for Stop {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped",
+            Tense::Active => "Stopping",
+            Tense::Questioning => "Stop",
+            Tense::Future => "Will stop",
+        };
+        format!(
+            "{verb} the dinit {} services running the file at the install location{}",
+            self.mode,
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        #[allow(clippy::format_collect)]
+        let services: String = self
+            .names
+            .iter()
+            .map(|name| format!("\n|\t- {name}"))
+            .collect();
+        format!("{}\n| services:{services}", self.describe(tense))
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let mut rollback = Box::new(Restart {
+            names: Vec::new(),
+            mode: self.mode,
+        });
+        for name in &self.names {
+            dinitctl("stop", name, self.mode)?;
+            rollback.names.push(name.clone());
+        }
+        Ok(Some(rollback as Box<dyn RollbackStep>))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisableError {
+    #[error("Could not read the service description directory")]
+    CouldNotReadDir(#[source] std::io::Error),
+    #[error("Could not find current users home dir")]
+    NoHome(
+        #[from]
+        #[source]
+        NoHomeError,
+    ),
+    #[error("Could not find the dinit service that keeps the file in use")]
+    NoServiceFound,
+}
+
+pub(crate) fn disable_step(
+    target: &Path,
+    mode: Mode,
+) -> Result<Vec<Box<dyn InstallStep>>, DisableError> {
+    let dir = match mode {
+        Mode::User => user_path()?,
+        Mode::System => system_path(),
+    };
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir).map_err(DisableError::CouldNotReadDir)? {
+        let path = entry.map_err(DisableError::CouldNotReadDir)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        // not every file in the service dir has to be readable
+        let Ok(service) = Service::from_path(path) else {
+            continue;
+        };
+        if service.runs(target) {
+            names.extend(service.name().map(str::to_owned));
+        }
+    }
+
+    if names.is_empty() {
+        return Err(DisableError::NoServiceFound);
+    }
+    names.sort();
+    Ok(vec![Box::new(Stop { names, mode })])
+}
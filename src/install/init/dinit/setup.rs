@@ -0,0 +1,343 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::install::builder::{Restart, RestartPolicy, StartLimit};
+use crate::install::init::{autogenerated_comment, Params, Steps};
+use crate::install::limits::{IoSchedulingClass, Limits};
+use crate::install::{InstallError, InstallStep, Mode, RollbackStep, Tense};
+
+use super::{dinitctl, quoted, teardown, Error};
+
+struct WriteService {
+    path: PathBuf,
+    description: String,
+    /// Path to and content of the environment file
+    env_file: Option<(PathBuf, String)>,
+}
+
+impl InstallStep for WriteService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} dinit service description{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.description.trim_end().replace('\n', "\n|\t");
+        let env_file = self
+            .env_file
+            .as_ref()
+            .map(|(path, content)| {
+                let content = content.trim_end().replace('\n', "\n|\t");
+                format!(
+                    "\n| environment file:\n|\t{}\n| environment:\n|\t{content}",
+                    path.display()
+                )
+            })
+            .unwrap_or_default();
+        format!(
+            "{verb} dinit service description{}\n| path:\n|\t{path}\n| content:\n|\t{content}{env_file}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let rollback = Box::new(teardown::RemoveService {
+            path: self.path.clone(),
+            env_file: self.env_file.as_ref().map(|(path, _)| path.clone()),
+        });
+        if let Some((path, content)) = &self.env_file {
+            write(path, content)?;
+        }
+        write(&self.path, &self.description)?;
+        Ok(Some(rollback))
+    }
+}
+
+struct EnableService {
+    name: String,
+    mode: Mode,
+    already_running: bool,
+}
+
+impl InstallStep for EnableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match (&tense, self.already_running) {
+            (Tense::Past, true) => "Reloaded, enabled and restarted",
+            (Tense::Past, false) => "Enabled and started",
+            (Tense::Questioning, true) => "Reload, enable and restart",
+            (Tense::Questioning, false) => "Enable and start",
+            (Tense::Future, true) => "Will reload, enable and restart",
+            (Tense::Future, false) => "Will enable and start",
+            (Tense::Active, true) => "Reloading, enabling and restarting",
+            (Tense::Active, false) => "Enabling and starting",
+        };
+        format!(
+            "{verb} dinit {} service: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        if self.already_running {
+            // dinit only accepts a changed description for stopped services
+            dinitctl("stop", &self.name, self.mode)?;
+            dinitctl("reload", &self.name, self.mode)?;
+        }
+        dinitctl("enable", &self.name, self.mode)?;
+        Ok(Some(Box::new(teardown::DisableService {
+            name: self.name.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
+pub(super) fn steps(dir: &Path, params: &Params) -> Result<Steps, Error> {
+    let path = dir.join(&params.name);
+    let env_file = (!params.environment.is_empty()).then(|| {
+        let path = dir.join(format!("{}.env", params.name));
+        (path, render_env_file(params))
+    });
+    let description = render_description(params, env_file.as_ref().map(|(path, _)| path.as_path()));
+    let already_running = super::is_started(&params.name, params.mode)?;
+
+    Ok(vec![
+        Box::new(WriteService {
+            path,
+            description,
+            env_file,
+        }),
+        Box::new(EnableService {
+            name: params.name.clone(),
+            mode: params.mode,
+            already_running,
+        }),
+    ])
+}
+
+fn render_description(params: &Params, env_file: Option<&Path>) -> String {
+    let Params {
+        exe_path,
+        exe_args,
+        working_dir,
+        run_as,
+        ..
+    } = params;
+
+    let command = std::iter::once(exe_path.display().to_string())
+        .chain(exe_args.iter().cloned())
+        .map(|arg| quoted(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let working_dir_line = working_dir
+        .as_ref()
+        .map(|d| format!("\nworking-dir = {}", quoted(&d.display().to_string())))
+        .unwrap_or_default();
+    let user_line = run_as
+        .as_ref()
+        .map(|user| format!("\nrun-as = {}", quoted(user)))
+        .unwrap_or_default();
+    let env_file_line = env_file
+        .map(|path| format!("\nenv-file = {}", quoted(&path.display().to_string())))
+        .unwrap_or_default();
+    let restart_lines = render_restart(&params.restart);
+    let limit_lines = render_limits(&params.limits);
+
+    let comment = autogenerated_comment(params.bin_name);
+    let description = params.description().replace('\n', " ");
+    format!(
+        "{comment}
+# {description}
+
+type = process
+command = {command}{working_dir_line}{user_line}{env_file_line}{restart_lines}{limit_lines}
+"
+    )
+}
+
+fn render_restart(restart: &RestartPolicy) -> String {
+    let when = restart
+        .when
+        .map(|when| match when {
+            Restart::OnFailure => "\nrestart = on-failure",
+            Restart::Always => "\nrestart = yes",
+        })
+        .unwrap_or_default();
+    let delay = restart
+        .delay
+        .map(|delay| format!("\nrestart-delay = {}", delay.as_secs_f64()))
+        .unwrap_or_default();
+    let limit = restart
+        .limit
+        .map(|StartLimit { burst, interval }| {
+            format!(
+                "\nrestart-limit-interval = {}\nrestart-limit-count = {burst}",
+                interval.as_secs_f64()
+            )
+        })
+        .unwrap_or_default();
+    format!("{when}{delay}{limit}")
+}
+
+fn render_limits(limits: &Limits) -> String {
+    let nice = limits
+        .nice
+        .map(|nice| format!("\nnice = {nice}"))
+        .unwrap_or_default();
+    let ioprio = limits
+        .io_scheduling_class
+        .map(|class| match class {
+            IoSchedulingClass::Realtime => "\nioprio = rt:4",
+            IoSchedulingClass::BestEffort => "\nioprio = be:4",
+            IoSchedulingClass::Idle => "\nioprio = idle",
+        })
+        .unwrap_or_default();
+    let open_files = limits
+        .open_files
+        .map(|files| format!("\nrlimit-nofile = {files}"))
+        .unwrap_or_default();
+    let memory = limits
+        .memory_max
+        .map(|bytes| format!("\nrlimit-addrspace = {bytes}"))
+        .unwrap_or_default();
+    format!("{nice}{ioprio}{open_files}{memory}")
+}
+
+/// A variable the env-file can not hold, it takes everything after the first
+/// `=` up to the end of the line as the value
+pub(super) fn unsupported_env_var(params: &Params) -> Option<&str> {
+    params
+        .environment
+        .iter()
+        .find(|(key, val)| {
+            key.is_empty()
+                || key.starts_with(|c: char| c.is_ascii_digit())
+                || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                || val.contains(['\n', '\r'])
+        })
+        .map(|(key, _)| key.as_str())
+}
+
+fn render_env_file(params: &Params) -> String {
+    let mut vars: Vec<_> = params.environment.iter().collect();
+    vars.sort();
+    let lines: String = vars
+        .into_iter()
+        .map(|(key, val)| format!("{key}={val}\n"))
+        .collect();
+    format!(
+        "# environment for the dinit service: {}\n{lines}",
+        params.name
+    )
+}
+
+fn write(path: &Path, content: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::Writing {
+            e,
+            path: path.to_owned(),
+        })?;
+    }
+    fs::write(path, content).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn description() {
+        let params = Params {
+            exe_args: vec!["--units".to_owned(), "metric # SI".to_owned()],
+            working_dir: Some(PathBuf::from("/var/lib/weather")),
+            run_as: Some("weather".to_owned()),
+            restart: RestartPolicy {
+                when: Some(Restart::Always),
+                ..RestartPolicy::default()
+            },
+            limits: Limits {
+                nice: Some(-5),
+                io_scheduling_class: Some(IoSchedulingClass::Idle),
+                open_files: Some(4096),
+                ..Limits::default()
+            },
+            ..Params::for_test("weather_checker", "/usr/bin/weather checker")
+        };
+        let env_file = Path::new("/etc/dinit.d/weather_checker.env");
+        let description = render_description(&params, Some(env_file));
+        let settings = description
+            .lines()
+            .skip_while(|line| !line.starts_with("type ="))
+            .join("\n");
+        assert_eq!(
+            settings,
+            r#"type = process
+command = "/usr/bin/weather checker" --units "metric # SI"
+working-dir = /var/lib/weather
+run-as = weather
+env-file = /etc/dinit.d/weather_checker.env
+restart = yes
+nice = -5
+ioprio = idle
+rlimit-nofile = 4096"#
+        );
+    }
+
+    #[test]
+    fn env_vars_the_env_file_can_not_hold() {
+        let mut params = Params {
+            environment: HashMap::from([("GREETING".to_owned(), "a=b #c".to_owned())]),
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert_eq!(unsupported_env_var(&params), None);
+
+        params.environment = HashMap::from([("GREETING".to_owned(), "hi\nPATH=/tmp".to_owned())]);
+        assert_eq!(unsupported_env_var(&params), Some("GREETING"));
+        params.environment = HashMap::from([("#GREETING".to_owned(), "hi".to_owned())]);
+        assert_eq!(unsupported_env_var(&params), Some("#GREETING"));
+        params.environment = HashMap::from([("A=B".to_owned(), "hi".to_owned())]);
+        assert_eq!(unsupported_env_var(&params), Some("A=B"));
+    }
+
+    #[test]
+    fn restart_policy() {
+        let restart = RestartPolicy {
+            when: Some(Restart::OnFailure),
+            delay: Some(Duration::from_millis(1500)),
+            limit: Some(StartLimit {
+                burst: 5,
+                interval: Duration::from_secs(60),
+            }),
+        };
+        assert_eq!(
+            render_restart(&restart),
+            "\nrestart = on-failure\nrestart-delay = 1.5\nrestart-limit-interval = 60\nrestart-limit-count = 5"
+        );
+        assert_eq!(render_restart(&RestartPolicy::default()), "");
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install::init::RSteps;
+use crate::install::Mode;
+use crate::install::RemoveError;
+use crate::install::RemoveStep;
+use crate::install::Tense;
+
+use super::{dinitctl, is_started, Error, Service};
+
+pub(crate) struct RemoveService {
+    pub(crate) path: PathBuf,
+    pub(crate) env_file: Option<PathBuf>,
+}
+
+impl RemoveStep for RemoveService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        let env_file = self
+            .env_file
+            .as_ref()
+            .map(|p| format!("\n|\t{}", p.display()))
+            .unwrap_or_default();
+        format!(
+            "{verb} dinit service description{} at:\n|\t{path}{env_file}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        for path in std::iter::once(&self.path).chain(&self.env_file) {
+            match fs::remove_file(path) {
+                Ok(()) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => return Err(Error::Removing(e).into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct DisableService {
+    pub(crate) name: String,
+    pub(crate) mode: Mode,
+}
+
+impl RemoveStep for DisableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Disabled and unloaded",
+            Tense::Questioning => "Disable and unload",
+            Tense::Future => "Will disable and unload",
+            Tense::Active => "Disabling and unloading",
+        };
+        format!(
+            "{verb} dinit {} service: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        // disable stops the service and removes it from the boot service
+        dinitctl("disable", &self.name, self.mode)?;
+        if is_started(&self.name, self.mode)? {
+            dinitctl("stop", &self.name, self.mode)?;
+        }
+        dinitctl("unload", &self.name, self.mode)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn disable_then_remove(service: &Service, name: &str, mode: Mode) -> RSteps {
+    vec![
+        Box::new(DisableService {
+            name: name.to_owned(),
+            mode,
+        }),
+        Box::new(RemoveService {
+            path: service.path.clone(),
+            env_file: service.env_file(),
+        }),
+    ]
+}
@@ -12,7 +12,7 @@ pub fn split_unescaped_whitespace_once(line: &str) -> String {
         return line.to_string();
     }
 
-    let mut chars = line.chars().chain(iter::repeat('_').take(3));
+    let mut chars = line.chars().chain(iter::repeat_n('_', 3));
     let mut head = [
         '_', // padding removed at the end
         '_',
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 
 type CharIter<'a> = std::str::Chars<'a>;
+type Escape = fn(&mut CharIter) -> Result<char, UnquoteError>;
 
 fn unicode_char_from_char_digits(
     n: u8,
@@ -49,14 +50,8 @@ const ESCAPES_LENGTH_ONE: [(char, char); 11] = [
     ('s', ' '),        // space
 ];
 
-const ESCAPES_LONGER_THEN_ONE: [(
-    char,
-    fn(&mut std::str::Chars<'_>) -> Result<char, UnquoteError>,
-); 4] = [
-    (
-        'x',
-        escape_hex2 as fn(&mut CharIter) -> Result<char, UnquoteError>,
-    ),
+const ESCAPES_LONGER_THEN_ONE: [(char, Escape); 4] = [
+    ('x', escape_hex2 as Escape),
     ('n', escape_oct2),
     ('u', escape_hex4),
     ('U', escape_hex8),
@@ -82,7 +77,7 @@ pub enum UnquoteError {
     MissingEndQuo(char),
 }
 
-fn decoded_unquoted_first_segment(unquoted_start: &str) -> Result<Cow<str>, UnquoteError> {
+fn decoded_unquoted_first_segment(unquoted_start: &str) -> Result<Cow<'_, str>, UnquoteError> {
     let first_segment = unquoted_start
         .split(' ')
         .next()
@@ -138,7 +133,7 @@ fn decoded_unquoted_first_segment(unquoted_start: &str) -> Result<Cow<str>, Unqu
 /// # Note
 /// This does not account for trailing backslashes and newlines. Any line
 /// with those in them might not be properly unquoted/unescaped.
-pub(crate) fn first_segement(line: &str) -> Result<Cow<str>, UnquoteError> {
+pub(crate) fn first_segement(line: &str) -> Result<Cow<'_, str>, UnquoteError> {
     let line = line.trim();
     let (line, segment_end) = if let Some(line) = line.strip_prefix('"') {
         (line, '"')
@@ -163,8 +158,6 @@ pub(crate) fn first_segement(line: &str) -> Result<Cow<str>, UnquoteError> {
                 return Ok(Cow::Owned(output));
             }
         };
-        (a, b);
-
         if a == '\\' {
             if let Some((_, unescaped)) =
                 ESCAPES_LENGTH_ONE.iter().find(|(literal, _)| *literal == b)
@@ -234,7 +227,7 @@ mod tests {
         // Hex 70 is ascii P
         // Hex 20 is ascii space
         let escaped = "\"/long/\\x70ath/with\\x20spaces\\x20/to/cmd\"";
-        let cmd = first_segement(&escaped).unwrap();
+        let cmd = first_segement(escaped).unwrap();
         assert_eq!(cmd, "/long/path/with spaces /to/cmd");
     }
 
@@ -243,7 +236,7 @@ mod tests {
         // Hex 70 is ascii P
         // Hex 20 is ascii space
         let escaped = "\"/path with spaces/cmd\" \"arg with quotes\" arg_without_quotes";
-        let cmd = first_segement(&escaped).unwrap();
+        let cmd = first_segement(escaped).unwrap();
         assert_eq!(cmd, "/path with spaces/cmd");
     }
 }
@@ -0,0 +1,225 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{fs, io};
+
+use crate::install::builder::Restart;
+use crate::install::files::NoHomeError;
+
+use super::{ExeLocation, Mode, Params, RSteps, SetupError, Steps, TearDownError};
+
+mod disable_existing;
+mod setup;
+mod teardown;
+
+use super::script::{self, Script};
+pub(crate) use disable_existing::disable_step;
+pub use disable_existing::DisableError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("OpenRC can not start services on a schedule, use cron for that")]
+    ScheduleUnsupported,
+    #[error("OpenRC's supervise-daemon restarts a service whenever it exits, it can not restart only on failure")]
+    RestartOnFailureUnsupported,
+    #[error("This OpenRC install does not manage user services (needs OpenRC 0.60 or newer and a running `openrc --user`)")]
+    UserServicesUnsupported,
+    #[error("Could not write out init script to {path}")]
+    Writing {
+        #[source]
+        e: io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not make the init script executable")]
+    SettingPermissions(#[source] io::Error),
+    #[error("Could not remove the init script, error: {0}")]
+    Removing(#[source] io::Error),
+    #[error("Could not read the init script directory")]
+    ReadingDir(#[source] io::Error),
+    #[error("Could not verify init script was created by us, could not open it")]
+    Verifying(
+        #[from]
+        #[source]
+        script::Error,
+    ),
+    #[error("Could not run `{cmd}`")]
+    CouldNotRun {
+        #[source]
+        e: io::Error,
+        cmd: &'static str,
+    },
+    #[error("Command `{cmd}` failed, stderr: \"{stderr}\"")]
+    CommandFailed { cmd: String, stderr: String },
+}
+
+pub(crate) fn is_init_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| matches!(name, "supervise-daemon" | "openrc-init" | "openrc-run"))
+}
+
+/// OpenRC keeps its runtime state in /run/openrc, if that is missing
+/// the system is not managed by it.
+pub(super) fn not_available() -> bool {
+    !Path::new("/run/openrc").is_dir()
+}
+
+pub(super) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported.into());
+    }
+    if params.restart.when == Some(Restart::OnFailure) {
+        return Err(Error::RestartOnFailureUnsupported.into());
+    }
+    if params.mode.is_user() && !user_services_running() {
+        return Err(Error::UserServicesUnsupported.into());
+    }
+
+    let path = match params.mode {
+        Mode::User => user_path()?,
+        Mode::System => system_path(),
+    }
+    .join(&params.name);
+
+    Ok(setup::steps(path, params)?)
+}
+
+pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    let dir = match mode {
+        Mode::User => user_path()?,
+        Mode::System => system_path(),
+    };
+
+    let mut steps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for script in our_scripts(&dir)? {
+        let Some(name) = script.name() else {
+            continue;
+        };
+        steps.extend(teardown::disable_then_remove(
+            script.path.clone(),
+            name,
+            mode,
+        ));
+        exe_paths.push(
+            script
+                .exe_path(EXE_VAR)
+                .map_err(TearDownError::FindingScriptExePath)?,
+        );
+    }
+
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+fn our_scripts(dir: &Path) -> Result<Vec<Script>, Error> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let script = Script::from_path(path)?;
+        if script.our_script() && script.interpreter() == Some(INTERPRETER) {
+            scripts.push(script);
+        }
+    }
+    Ok(scripts)
+}
+
+/// User services are only available when the user runs its own openrc
+/// instance, it keeps its state in the users runtime dir.
+fn user_services_running() -> bool {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .is_some_and(|dir| dir.join("openrc").is_dir())
+}
+
+fn user_path() -> Result<PathBuf, NoHomeError> {
+    Ok(home::home_dir()
+        .ok_or(NoHomeError)?
+        .join(".config/rc/init.d"))
+}
+
+fn system_path() -> PathBuf {
+    PathBuf::from("/etc/init.d")
+}
+
+const RUNLEVEL: &str = "default";
+const INTERPRETER: &str = "/sbin/openrc-run";
+/// Variable in the init script holding the path to the executable
+const EXE_VAR: &str = "command";
+
+fn run(mut command: Command, cmd: &'static str) -> Result<(), Error> {
+    let output = command
+        .output()
+        .map_err(|e| Error::CouldNotRun { e, cmd })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let args = command.get_args().map(OsStr::to_string_lossy);
+        let cmd = std::iter::once(cmd.into())
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::CommandFailed { cmd, stderr })
+    }
+}
+
+fn rc_update(action: &str, name: &str, mode: Mode) -> Result<(), Error> {
+    let mut command = Command::new("rc-update");
+    if mode.is_user() {
+        command.arg("--user");
+    }
+    command.args([action, name, RUNLEVEL]);
+    run(command, "rc-update")
+}
+
+fn rc_service(name: &str, action: &str, mode: Mode) -> Result<(), Error> {
+    let mut command = Command::new("rc-service");
+    if mode.is_user() {
+        command.arg("--user");
+    }
+    command.args([name, action]);
+    run(command, "rc-service")
+}
+
+fn is_started(name: &str, mode: Mode) -> Result<bool, Error> {
+    let mut command = Command::new("rc-service");
+    if mode.is_user() {
+        command.arg("--user");
+    }
+    command.args([name, "status"]);
+    let output = command.output().map_err(|e| Error::CouldNotRun {
+        e,
+        cmd: "rc-service",
+    })?;
+    // status exits with 0 only if the service is started
+    Ok(output.status.success())
+}
+
+fn enable(name: &str, mode: Mode, restart: bool) -> Result<(), Error> {
+    rc_update("add", name, mode)?;
+    if restart {
+        rc_service(name, "restart", mode)
+    } else {
+        rc_service(name, "start", mode)
+    }
+}
+
+fn disable(name: &str, mode: Mode) -> Result<(), Error> {
+    if is_started(name, mode)? {
+        rc_service(name, "stop", mode)?;
+    }
+    rc_update("del", name, mode)
+}
@@ -0,0 +1,272 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::install::builder::StartLimit;
+use crate::install::init::{autogenerated_comment, Params, ShellEscape, Steps};
+use crate::install::limits::Limits;
+use crate::install::{InstallError, InstallStep, Mode, RollbackStep, Tense};
+
+use super::{teardown, Error, INTERPRETER};
+
+struct WriteScript {
+    script: String,
+    path: PathBuf,
+}
+
+impl InstallStep for WriteScript {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} OpenRC init script{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.script.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} OpenRC init script{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_script(&self.path, &self.script)?;
+        Ok(Some(Box::new(teardown::RemoveScript {
+            path: self.path.clone(),
+        })))
+    }
+}
+
+struct EnableService {
+    name: String,
+    mode: Mode,
+    already_running: bool,
+}
+
+impl InstallStep for EnableService {
+    fn describe(&self, tense: Tense) -> String {
+        let enable = match tense {
+            Tense::Past => "Added",
+            Tense::Questioning => "Add",
+            Tense::Future => "Will add",
+            Tense::Active => "Adding",
+        };
+        let start = match (&tense, self.already_running) {
+            (Tense::Past, true) => "restarted",
+            (Tense::Past, false) => "started",
+            (Tense::Questioning | Tense::Future, true) => "restart",
+            (Tense::Questioning | Tense::Future, false) => "start",
+            (Tense::Active, true) => "restarting",
+            (Tense::Active, false) => "starting",
+        };
+        format!(
+            "{enable} OpenRC {} service: {} to the {} runlevel and {start} it{}",
+            self.mode,
+            self.name,
+            super::RUNLEVEL,
+            tense.punct(),
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        super::enable(&self.name, self.mode, self.already_running)?;
+        Ok(Some(Box::new(teardown::DisableService {
+            name: self.name.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
+pub(super) fn steps(path: PathBuf, params: &Params) -> Result<Steps, Error> {
+    let script = render_script(params);
+    let already_running = super::is_started(&params.name, params.mode)?;
+
+    Ok(vec![
+        Box::new(WriteScript { script, path }),
+        Box::new(EnableService {
+            name: params.name.clone(),
+            mode: params.mode,
+            already_running,
+        }),
+    ])
+}
+
+fn render_script(params: &Params) -> String {
+    let Params {
+        exe_path,
+        exe_args,
+        environment,
+        working_dir,
+        run_as,
+        ..
+    } = params;
+
+    let description = params.description().shell_escaped();
+    let command = exe_path.shell_escaped();
+    let command_args = exe_args
+        .iter()
+        .map(String::shell_escaped)
+        .join(" ")
+        .shell_escaped();
+    let working_dir_line = working_dir
+        .as_ref()
+        .map(|d| format!("\ndirectory={}", d.shell_escaped()))
+        .unwrap_or_default();
+    let user_line = run_as
+        .as_ref()
+        .map(|user| format!("\ncommand_user={}", user.shell_escaped()))
+        .unwrap_or_default();
+    let respawn_delay_line = params
+        .restart
+        .delay
+        .map(|delay| format!("\nrespawn_delay={}", delay.as_secs()))
+        .unwrap_or_default();
+    let respawn_limit_lines = params
+        .restart
+        .limit
+        .map(|StartLimit { burst, interval }| {
+            format!(
+                "\nrespawn_max={burst}\nrespawn_period={}",
+                interval.as_secs()
+            )
+        })
+        .unwrap_or_default();
+    let limit_lines = render_limits(&params.limits);
+    #[allow(clippy::format_collect)]
+    let export_lines: String = environment
+        .iter()
+        .map(|(key, val)| format!("\nexport {}={}", key.shell_escaped(), val.shell_escaped()))
+        .collect();
+
+    let comment = autogenerated_comment(params.bin_name);
+    format!(
+        "#!{INTERPRETER}
+{comment}
+
+description={description}
+supervisor=supervise-daemon
+command={command}
+command_args={command_args}{working_dir_line}{user_line}{respawn_delay_line}{respawn_limit_lines}{limit_lines}{export_lines}
+
+depend() {{
+\tafter net
+}}
+"
+    )
+}
+
+/// `supervise-daemon` sets the priorities, `ulimit` the resource limits
+fn render_limits(limits: &Limits) -> String {
+    let daemon_args = [
+        limits.nice.map(|nice| format!("--nicelevel {nice}")),
+        limits
+            .io_scheduling_class
+            .map(|class| format!("--ionice {}", class.number())),
+    ]
+    .into_iter()
+    .flatten()
+    .join(" ");
+    let ulimit_args = [
+        limits.open_files.map(|files| format!("-n {files}")),
+        limits
+            .memory_max
+            .map(|bytes| format!("-v {}", bytes.div_ceil(1024))),
+    ]
+    .into_iter()
+    .flatten()
+    .join(" ");
+
+    let mut lines = String::new();
+    if !daemon_args.is_empty() {
+        lines.push_str(&format!("\nsupervise_daemon_args=\"{daemon_args}\""));
+    }
+    if !ulimit_args.is_empty() {
+        lines.push_str(&format!("\nrc_ulimit=\"{ulimit_args}\""));
+    }
+    lines
+}
+
+fn write_script(path: &Path, script: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::Writing {
+            e,
+            path: path.to_owned(),
+        })?;
+    }
+    let mut f = fs::File::create(path).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })?;
+    f.write_all(script.as_bytes()).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(Error::SettingPermissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::install::builder::{Restart, RestartPolicy};
+
+    use super::*;
+
+    #[test]
+    fn supervise_daemon_settings() {
+        let params = Params {
+            exe_args: vec!["--station".to_owned(), "De Bilt".to_owned()],
+            run_as: Some("weather".to_owned()),
+            restart: RestartPolicy {
+                when: Some(Restart::Always),
+                delay: Some(Duration::from_secs(3)),
+                limit: Some(StartLimit {
+                    burst: 4,
+                    interval: Duration::from_secs(120),
+                }),
+            },
+            limits: Limits {
+                nice: Some(5),
+                open_files: Some(4096),
+                ..Limits::default()
+            },
+            ..Params::for_test("weather_station", "/opt/weather station/bin/station")
+        };
+        let script = render_script(&params);
+        let settings = script
+            .lines()
+            .skip_while(|line| !line.starts_with("supervisor="))
+            .take_while(|line| !line.is_empty())
+            .join("\n");
+        assert_eq!(
+            settings,
+            r#"supervisor=supervise-daemon
+command='/opt/weather station/bin/station'
+command_args='--station '\''De Bilt'\'''
+command_user=weather
+respawn_delay=3
+respawn_max=4
+respawn_period=120
+supervise_daemon_args="--nicelevel 5"
+rc_ulimit="-n 4096""#
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install::init::RSteps;
+use crate::install::Mode;
+use crate::install::RemoveError;
+use crate::install::RemoveStep;
+use crate::install::Tense;
+
+use super::{disable, Error};
+
+pub(crate) struct RemoveScript {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveScript {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} OpenRC init script{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DisableService {
+    pub(crate) name: String,
+    pub(crate) mode: Mode,
+}
+
+impl RemoveStep for DisableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped and removed",
+            Tense::Questioning => "Stop and remove",
+            Tense::Future => "Will stop and remove",
+            Tense::Active => "Stopping and removing",
+        };
+        format!(
+            "{verb} OpenRC {} service: {} from the {} runlevel{}",
+            self.mode,
+            self.name,
+            super::RUNLEVEL,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        disable(&self.name, self.mode)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn disable_then_remove(script_path: PathBuf, name: &str, mode: Mode) -> RSteps {
+    vec![
+        Box::new(DisableService {
+            name: name.to_owned(),
+            mode,
+        }),
+        Box::new(RemoveScript { path: script_path }),
+    ]
+}
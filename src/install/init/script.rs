@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use crate::install::init::{extract_path, COMMENT_PREAMBLE, COMMENT_SUFFIX};
+
+/// A shell script written by one of the script based init systems (OpenRC,
+/// runit, s6, SysV). The path of the executable is stored in a shell variable
+/// so it can be read back during removal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Script {
+    body: String,
+    pub(crate) path: PathBuf,
+}
+
+/// The executables location could not be found. It is needed to safely
+/// uninstall.
+#[derive(Debug, thiserror::Error)]
+pub enum FindExeError {
+    #[error("{var} (used to find binary) is missing from script at: {path}")]
+    VariableMissing { var: &'static str, path: PathBuf },
+    #[error("Path to binary extracted from script does not lead to a file, path: {0}")]
+    ExecPathNotFile(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not read script's content: {0}")]
+    FailedToRead(
+        #[from]
+        #[source]
+        std::io::Error,
+    ),
+}
+
+impl Script {
+    pub(crate) fn from_path(path: PathBuf) -> Result<Self, Error> {
+        Ok(Self {
+            body: std::fs::read_to_string(&path)?,
+            path,
+        })
+    }
+
+    /// The (shell escaped) path assigned to `var`
+    pub(crate) fn exe_variable(&self, var: &str) -> Option<PathBuf> {
+        self.body
+            .lines()
+            .map(str::trim)
+            .filter_map(|l| l.strip_prefix(var))
+            .find_map(|l| l.strip_prefix('='))
+            .map(extract_path::unshell_escape::split_unescaped_whitespace_once)
+            .map(PathBuf::from)
+    }
+
+    pub(crate) fn exe_path(&self, var: &'static str) -> Result<PathBuf, FindExeError> {
+        let exe_path = self
+            .exe_variable(var)
+            .ok_or_else(|| FindExeError::VariableMissing {
+                var,
+                path: self.path.clone(),
+            })?;
+        if exe_path.is_file() {
+            Ok(exe_path)
+        } else {
+            Err(FindExeError::ExecPathNotFile(exe_path))
+        }
+    }
+
+    pub(crate) fn runs(&self, var: &str, target: &Path) -> bool {
+        self.exe_variable(var).is_some_and(|exe| exe == target)
+    }
+
+    pub(crate) fn interpreter(&self) -> Option<&str> {
+        self.body.lines().next()?.strip_prefix("#!").map(str::trim)
+    }
+
+    pub(crate) fn our_script(&self) -> bool {
+        self.body.contains(COMMENT_PREAMBLE) && self.body.contains(COMMENT_SUFFIX)
+    }
+
+    /// The service name is the file name of the init script
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.path.file_name().and_then(|n| n.to_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install::init::{autogenerated_comment, ShellEscape};
+
+    use super::*;
+
+    #[test]
+    fn exe_can_be_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        for exe in [
+            "/usr/bin/weather_checker",
+            "/usr/bin/weather checker",
+            "/opt/it's/weather_checker",
+            "/opt/weather $HOME/checker",
+        ] {
+            let exe = PathBuf::from(exe);
+            let path = dir.path().join("weather_checker");
+            let landmark = autogenerated_comment("weather_checker");
+            let body = format!("#!/bin/sh\n{landmark}\n\nexe={}\n", exe.shell_escaped());
+            std::fs::write(&path, body).unwrap();
+
+            let script = Script::from_path(path).unwrap();
+            assert!(script.our_script());
+            assert_eq!(script.interpreter(), Some("/bin/sh"));
+            assert_eq!(script.exe_variable("exe"), Some(exe.clone()), "{exe:?}");
+            assert!(script.runs("exe", &exe));
+        }
+    }
+}
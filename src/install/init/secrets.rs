@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::install::files::NoHomeError;
+use crate::install::{
+    InstallError, InstallStep, Mode, RemoveError, RemoveStep, RollbackError, RollbackStep, Tense,
+};
+
+use super::{autogenerated_comment, ShellEscape};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not create the directory for the secret environment file")]
+    CreatingDir(#[source] io::Error),
+    #[error("Could not read the secret environment file of a previous install")]
+    Reading(#[source] io::Error),
+    #[error("Could not write the secret environment file")]
+    Writing(#[source] io::Error),
+    #[error("User `{0}` that should own the secret environment file does not exist")]
+    UnknownOwner(String),
+    #[error("Could not make the user running the service owner of the secret environment file")]
+    SetOwner(#[source] io::Error),
+    #[error("Could not remove the secret environment file")]
+    Removing(#[source] io::Error),
+}
+
+/// Environment variables that must not end up in world readable unit files or
+/// crontabs, set using [`Spec::secret_env_var`](crate::install::Spec::secret_env_var)
+#[derive(Clone, Default)]
+pub(crate) struct SecretEnvironment(pub(crate) HashMap<String, String>);
+
+/// Never print the values
+impl std::fmt::Debug for SecretEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+impl SecretEnvironment {
+    /// A name that can not be set from a shell or environment file
+    pub(crate) fn invalid_name(&self) -> Option<&str> {
+        self.0.keys().map(String::as_str).find(|name| {
+            name.is_empty()
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+    }
+}
+
+/// The secret environment variables and the file they are written to
+#[derive(Debug, Clone)]
+pub(crate) struct Secrets {
+    pub(crate) path: PathBuf,
+    pub(crate) vars: SecretEnvironment,
+}
+
+/// `/etc/<name>/secrets.env` for system and `~/.config/<name>/secrets.env`
+/// for user services
+pub(crate) fn path(name: &str, mode: Mode) -> Result<PathBuf, NoHomeError> {
+    let dir = match mode {
+        Mode::User => home::home_dir().ok_or(NoHomeError)?.join(".config"),
+        Mode::System => PathBuf::from("/etc"),
+    };
+    Ok(dir.join(name).join("secrets.env"))
+}
+
+impl Secrets {
+    /// Writes the file, owned by `owner` if set
+    pub(crate) fn step(&self, bin_name: &'static str, owner: Option<&str>) -> Box<dyn InstallStep> {
+        Box::new(WriteSecrets {
+            path: self.path.clone(),
+            content: self.render(bin_name),
+            names: self.vars.0.keys().sorted().cloned().collect(),
+            owner: owner.map(str::to_owned),
+        })
+    }
+
+    /// Double quoted values work both for systemd's `EnvironmentFile=` and
+    /// for sourcing from a shell
+    fn render(&self, bin_name: &'static str) -> String {
+        let lines: String = self
+            .vars
+            .0
+            .iter()
+            .sorted()
+            .map(|(name, value)| {
+                let value: String = value
+                    .chars()
+                    .flat_map(|c| {
+                        let escape = matches!(c, '"' | '\\' | '`' | '$').then_some('\\');
+                        escape.into_iter().chain([c])
+                    })
+                    .collect();
+                format!("{name}=\"{value}\"\n")
+            })
+            .collect();
+        format!("{}\n{lines}", autogenerated_comment(bin_name))
+    }
+
+    /// Shell command exporting the variables, prefixed to the cron command
+    pub(crate) fn source_command(&self) -> String {
+        format!("set -a && . {} && set +a && ", self.path.shell_escaped())
+    }
+}
+
+/// The secrets file sourced by a cron command, see
+/// [`Secrets::source_command`]
+pub(crate) fn path_from_command(command: &str) -> Option<PathBuf> {
+    let rest = command.trim_start().strip_prefix("set -a && . ")?;
+    let path = super::extract_path::unshell_escape::split_unescaped_whitespace_once(rest);
+    Some(PathBuf::from(path))
+}
+
+/// Whether the file starts with the comment we put above the secrets. The
+/// path comes from a rule that could have been edited to source a file of
+/// the user's own, that must stay.
+pub(crate) fn written_by(path: &Path, bin_name: &str) -> bool {
+    fs::read_to_string(path)
+        .is_ok_and(|content| content.starts_with(&autogenerated_comment(bin_name)))
+}
+
+struct WriteSecrets {
+    path: PathBuf,
+    content: String,
+    /// Only the names, the values are never described
+    names: Vec<String>,
+    owner: Option<String>,
+}
+
+impl InstallStep for WriteSecrets {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let names = self.names.join(", ");
+        format!(
+            "{verb} secret environment file{}\n\t| path: {path}\n\t| variables: {names}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let names = self.names.join("\n|\t");
+        let owner = self
+            .owner
+            .as_ref()
+            .map(|owner| format!("\n| owner:\n|\t{owner}"))
+            .unwrap_or_default();
+        format!(
+            "{verb} secret environment file, only readable by its owner{}\n| path:\n|\t{path}{owner}\n| variables:\n|\t{names}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(Error::CreatingDir)?;
+        }
+        // the service of a previous install still needs it if we roll back
+        let previous = if self.path.is_file() {
+            let metadata = fs::metadata(&self.path).map_err(Error::Reading)?;
+            let content = fs::read(&self.path).map_err(Error::Reading)?;
+            Some((content, (metadata.uid(), metadata.gid())))
+        } else {
+            None
+        };
+
+        let owner = self
+            .owner
+            .as_deref()
+            .map(|owner| {
+                uzers::get_user_by_name(owner)
+                    .map(|user| (user.uid(), user.primary_group_id()))
+                    .ok_or_else(|| Error::UnknownOwner(owner.to_owned()))
+            })
+            .transpose()?;
+        write(&self.path, self.content.as_bytes(), owner)?;
+
+        let path = self.path.clone();
+        Ok(Some(match previous {
+            Some((content, owner)) => Box::new(RestoreSecrets {
+                path,
+                content,
+                owner,
+            }) as Box<dyn RollbackStep>,
+            None => Box::new(RemoveSecrets { path }),
+        }))
+    }
+}
+
+/// Restricts the permissions before any secret is written, `owner` is a uid
+/// and gid
+fn write(path: &Path, content: &[u8], owner: Option<(u32, u32)>) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(Error::Writing)?;
+    // mode only applies to newly created files
+    file.set_permissions(Permissions::from_mode(0o600))
+        .map_err(Error::Writing)?;
+    if let Some((uid, gid)) = owner {
+        std::os::unix::fs::fchown(&file, Some(uid), Some(gid)).map_err(Error::SetOwner)?;
+    }
+    file.write_all(content).map_err(Error::Writing)
+}
+
+/// Puts back the secret environment file of a previous install
+struct RestoreSecrets {
+    path: PathBuf,
+    content: Vec<u8>,
+    /// uid and gid
+    owner: (u32, u32),
+}
+
+impl RollbackStep for RestoreSecrets {
+    fn perform(&mut self) -> Result<(), RollbackError> {
+        write(&self.path, &self.content, Some(self.owner)).map_err(RollbackError::RestoringSecrets)
+    }
+
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Restored",
+            Tense::Questioning => "Restore",
+            Tense::Future => "Will restore",
+            Tense::Active => "Restoring",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} the original secret environment file{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+}
+
+pub(crate) struct RemoveSecrets {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveSecrets {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} secret environment file{} at:\n|\t{path}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        // fails if the service keeps other files there, those stay
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> Secrets {
+        Secrets {
+            path: PathBuf::from("/etc/weather checker/secrets.env"),
+            vars: SecretEnvironment(HashMap::from([
+                ("API_TOKEN".to_owned(), "s3cr\"t $HOME".to_owned()),
+                ("DB_PASSWORD".to_owned(), "hunter2".to_owned()),
+            ])),
+        }
+    }
+
+    #[test]
+    fn render_escapes_for_shell_and_systemd() {
+        let rendered = secrets().render("weather_checker");
+        assert!(rendered.ends_with("\nAPI_TOKEN=\"s3cr\\\"t \\$HOME\"\nDB_PASSWORD=\"hunter2\"\n"));
+    }
+
+    #[test]
+    fn path_roundtrips_through_command() {
+        let secrets = secrets();
+        let command = format!("{}/usr/bin/weather_checker", secrets.source_command());
+        assert_eq!(path_from_command(&command), Some(secrets.path));
+        assert_eq!(path_from_command("/usr/bin/weather_checker"), None);
+    }
+
+    #[test]
+    fn only_our_files_are_written_by_us() {
+        let dir = tempfile::tempdir().unwrap();
+        let ours = dir.path().join("secrets.env");
+        fs::write(&ours, secrets().render("weather_checker")).unwrap();
+        assert!(written_by(&ours, "weather_checker"));
+        assert!(!written_by(&ours, "other_tool"));
+
+        let users = dir.path().join("defaults");
+        fs::write(&users, "API_TOKEN=abc\n").unwrap();
+        assert!(!written_by(&users, "weather_checker"));
+        assert!(!written_by(&dir.path().join("missing"), "weather_checker"));
+    }
+
+    #[test]
+    fn rollback_restores_previous_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.env");
+        fs::write(&path, "API_TOKEN=\"old\"\n").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+        let mut write = Secrets {
+            path: path.clone(),
+            ..secrets()
+        }
+        .step("weather_checker", None);
+        let mut rollback = write.perform().unwrap().unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("hunter2"));
+
+        rollback.perform().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "API_TOKEN=\"old\"\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn debug_hides_values() {
+        let debug = format!("{:?}", secrets());
+        assert!(debug.contains("API_TOKEN"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert_eq!(secrets().vars.invalid_name(), None);
+        let vars = SecretEnvironment(HashMap::from([("1TOKEN".to_owned(), String::new())]));
+        assert_eq!(vars.invalid_name(), Some("1TOKEN"));
+    }
+}
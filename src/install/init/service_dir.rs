@@ -0,0 +1,391 @@
+//! Backend for daemontools style supervisors (runit and s6). These run a
+//! supervisor for every service directory linked into a scan directory.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+use std::{fs, io, thread};
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, UpdateKind};
+
+use crate::install::builder::Restart;
+use crate::install::files::NoHomeError;
+
+use super::{ExeLocation, Mode, Params, RSteps, SetupError, Steps, TearDownError};
+
+mod disable_existing;
+mod setup;
+mod teardown;
+
+use super::script::{self, Script};
+pub(crate) use disable_existing::disable_step;
+pub use disable_existing::DisableError;
+
+/// Variable in the run script holding the path to the executable
+const EXE_VAR: &str = "exe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Flavor {
+    Runit,
+    S6,
+}
+
+impl Flavor {
+    fn name(self) -> &'static str {
+        match self {
+            Flavor::Runit => "runit",
+            Flavor::S6 => "s6",
+        }
+    }
+
+    /// The process watching the scan directory
+    fn scanner(self) -> &'static str {
+        match self {
+            Flavor::Runit => "runsvdir",
+            Flavor::S6 => "s6-svscan",
+        }
+    }
+
+    /// The scan directory in the arguments of the scanner, without the
+    /// program name
+    fn scan_dir_arg<S: AsRef<OsStr>>(self, args: &[S]) -> Option<&OsStr> {
+        match self {
+            // `runsvdir [-P] dir [log]`, the log placeholder comes after dir
+            Flavor::Runit => args
+                .iter()
+                .map(AsRef::as_ref)
+                .find(|arg| !arg.as_encoded_bytes().starts_with(b"-")),
+            // options may take their value as a separate argument, the scan
+            // dir is always the last one
+            Flavor::S6 => args.last().map(AsRef::as_ref),
+        }
+    }
+
+    /// The process supervising a single service
+    fn supervisor(self) -> &'static str {
+        match self {
+            Flavor::Runit => "runsv",
+            Flavor::S6 => "s6-supervise",
+        }
+    }
+
+    /// Program used to drop privileges before exec-ing the service
+    fn setuid(self) -> &'static str {
+        match self {
+            Flavor::Runit => "chpst -u",
+            Flavor::S6 => "s6-setuidgid",
+        }
+    }
+
+    fn definitions_dir(self, mode: Mode) -> Result<PathBuf, NoHomeError> {
+        Ok(match (self, mode) {
+            (Flavor::Runit, Mode::System) => PathBuf::from("/etc/sv"),
+            (Flavor::S6, Mode::System) => PathBuf::from("/etc/s6/sv"),
+            (Flavor::Runit, Mode::User) => home::home_dir().ok_or(NoHomeError)?.join(".config/sv"),
+            (Flavor::S6, Mode::User) => home::home_dir().ok_or(NoHomeError)?.join(".config/s6/sv"),
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0} can not start services on a schedule, use cron for that")]
+    ScheduleUnsupported(&'static str),
+    #[error("{0} restarts a service one second after it exits, it can not {1}")]
+    RestartUnsupported(&'static str, &'static str),
+    #[error("There is no {0} scan directory for this user, could not find a `{1}` process run by this user")]
+    NoScanDir(&'static str, &'static str),
+    #[error("Could not write out the run script to {path}")]
+    Writing {
+        #[source]
+        e: io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not make the run script executable")]
+    SettingPermissions(#[source] io::Error),
+    #[error("Could not link the service directory into the scan directory")]
+    Linking(#[source] io::Error),
+    #[error("Could not remove the service directory, error: {0}")]
+    Removing(#[source] io::Error),
+    #[error("Could not remove the link from the scan directory, error: {0}")]
+    Unlinking(#[source] io::Error),
+    #[error("Could not read the service definitions directory")]
+    ReadingDir(#[source] io::Error),
+    #[error("Could not verify run script was created by us, could not open it")]
+    Verifying(
+        #[from]
+        #[source]
+        script::Error,
+    ),
+    #[error("Could not run `{cmd}`")]
+    CouldNotRun {
+        #[source]
+        e: io::Error,
+        cmd: &'static str,
+    },
+    #[error("Command `{cmd}` failed, stderr: \"{stderr}\"")]
+    CommandFailed { cmd: String, stderr: String },
+    #[error("Waited longer then 15 seconds for the service to come up")]
+    TimedOut,
+}
+
+pub(crate) fn is_init_path(flavor: Flavor, path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| name == flavor.supervisor() || name == flavor.scanner())
+}
+
+pub(super) fn not_available(flavor: Flavor) -> bool {
+    scan_dirs(flavor).is_empty()
+}
+
+pub(super) fn set_up_steps(flavor: Flavor, params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported(flavor.name()).into());
+    }
+    let restart = &params.restart;
+    let unsupported = if restart.when.is_none() {
+        Some("leave it stopped, set `Restart::Always`")
+    } else if restart.when == Some(Restart::OnFailure) {
+        Some("restart only on failure")
+    } else if restart.delay.is_some() {
+        Some("wait longer before restarting")
+    } else if restart.limit.is_some() {
+        Some("limit how often it restarts")
+    } else {
+        None
+    };
+    if let Some(unsupported) = unsupported {
+        return Err(Error::RestartUnsupported(flavor.name(), unsupported).into());
+    }
+
+    let scan_dir = scan_dir(flavor, params.mode)?;
+    let service_dir = flavor.definitions_dir(params.mode)?.join(&params.name);
+    Ok(setup::steps(flavor, service_dir, scan_dir, params)?)
+}
+
+pub(super) fn tear_down_steps(
+    flavor: Flavor,
+    mode: Mode,
+) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    let definitions = flavor.definitions_dir(mode)?;
+    if !definitions.is_dir() {
+        return Ok(None);
+    }
+    let scan_dir = scan_dir(flavor, mode).ok();
+
+    let mut steps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for entry in fs::read_dir(&definitions).map_err(Error::ReadingDir)? {
+        let service_dir = entry.map_err(Error::ReadingDir)?.path();
+        let run_path = service_dir.join("run");
+        if !run_path.is_file() {
+            continue;
+        }
+        let script = Script::from_path(run_path).map_err(Error::from)?;
+        if !script.our_script() {
+            continue;
+        }
+        let Some(name) = service_dir.file_name().and_then(OsStr::to_str) else {
+            continue;
+        };
+        let link = scan_dir.as_ref().map(|dir| dir.join(name));
+        steps.extend(teardown::disable_then_remove(
+            flavor,
+            service_dir.clone(),
+            link,
+        ));
+        exe_paths.push(
+            script
+                .exe_path(EXE_VAR)
+                .map_err(TearDownError::FindingScriptExePath)?,
+        );
+    }
+
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+/// Scan directories watched by running scanners together with the uid
+/// of the user running the scanner.
+fn scan_dirs(flavor: Flavor) -> Vec<(PathBuf, Option<u32>)> {
+    let mut s = sysinfo::System::new();
+    s.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cmd(UpdateKind::Always)
+            .with_user(UpdateKind::Always),
+    );
+
+    s.processes()
+        .values()
+        .filter(|p| {
+            p.cmd()
+                .first()
+                .and_then(|cmd| Path::new(cmd).file_name())
+                .is_some_and(|name| name == flavor.scanner())
+        })
+        .filter_map(|p| {
+            let dir = flavor.scan_dir_arg(p.cmd().get(1..)?)?;
+            let uid = p.user_id().map(|uid| **uid);
+            Some((PathBuf::from(dir), uid))
+        })
+        .collect()
+}
+
+fn scan_dir(flavor: Flavor, mode: Mode) -> Result<PathBuf, Error> {
+    let uid = match mode {
+        Mode::System => 0,
+        Mode::User => uzers::get_current_uid(),
+    };
+    scan_dirs(flavor)
+        .into_iter()
+        .find(|(dir, owner)| *owner == Some(uid) && dir.is_absolute())
+        .map(|(dir, _)| dir)
+        .ok_or(Error::NoScanDir(flavor.name(), flavor.scanner()))
+}
+
+fn run(mut command: Command, cmd: &'static str) -> Result<Output, Error> {
+    let output = command
+        .output()
+        .map_err(|e| Error::CouldNotRun { e, cmd })?;
+
+    if output.status.success() {
+        Ok(output)
+    } else {
+        let args = command.get_args().map(OsStr::to_string_lossy);
+        let cmd = std::iter::once(cmd.into())
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::CommandFailed { cmd, stderr })
+    }
+}
+
+/// Control the supervisor of the service linked at `service`
+fn control(flavor: Flavor, service: &Path, action: Action) -> Result<(), Error> {
+    let (program, arg) = match (flavor, action) {
+        (Flavor::Runit, Action::Up) => ("sv", "up"),
+        (Flavor::Runit, Action::Down) => ("sv", "down"),
+        (Flavor::Runit, Action::Restart) => ("sv", "restart"),
+        (Flavor::S6, Action::Up) => ("s6-svc", "-u"),
+        (Flavor::S6, Action::Down) => ("s6-svc", "-d"),
+        (Flavor::S6, Action::Restart) => ("s6-svc", "-r"),
+    };
+    let mut command = Command::new(program);
+    command.arg(arg).arg(service);
+    run(command, program).map(|_| ())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Up,
+    Down,
+    Restart,
+}
+
+fn is_up(flavor: Flavor, service: &Path) -> bool {
+    let (program, prefix) = match flavor {
+        Flavor::Runit => ("sv", "run:"),
+        Flavor::S6 => ("s6-svstat", "up"),
+    };
+    let mut command = Command::new(program);
+    if let Flavor::Runit = flavor {
+        command.arg("status");
+    }
+    command.arg(service);
+    run(command, program)
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).starts_with(prefix))
+}
+
+/// runsvdir only checks for new services every 5 seconds, s6-svscan
+/// needs to be told to rescan.
+fn rescan(flavor: Flavor, scan_dir: &Path) -> Result<(), Error> {
+    if let Flavor::S6 = flavor {
+        let mut command = Command::new("s6-svscanctl");
+        command.arg("-an").arg(scan_dir);
+        run(command, "s6-svscanctl")?;
+    }
+    Ok(())
+}
+
+/// The supervisor creates its control fifo once it is running
+fn wait_for_supervisor(flavor: Flavor, service: &Path) -> Result<(), Error> {
+    let control = match flavor {
+        Flavor::Runit => service.join("supervise/ok"),
+        Flavor::S6 => service.join("supervise/control"),
+    };
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(15) {
+        if control.exists() {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    Err(Error::TimedOut)
+}
+
+fn wait_for_up(flavor: Flavor, service: &Path) -> Result<(), Error> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(15) {
+        if is_up(flavor, service) {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    Err(Error::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_restarts() {
+        let mut params = Params::for_test("weather_checker", "/usr/bin/weather_checker");
+        let unsupported = |params: &Params| {
+            matches!(
+                set_up_steps(Flavor::Runit, params),
+                Err(SetupError::ServiceDir(Error::RestartUnsupported(..)))
+            )
+        };
+        assert!(unsupported(&params));
+        params.restart.when = Some(Restart::OnFailure);
+        assert!(unsupported(&params));
+        params.restart.when = Some(Restart::Always);
+        assert!(!unsupported(&params));
+    }
+
+    #[test]
+    fn scan_dir_of_void_runsvdir() {
+        let args = [
+            "-P",
+            "/run/runit/service",
+            "log: ...........................",
+        ];
+        assert_eq!(
+            Flavor::Runit.scan_dir_arg(&args),
+            Some(OsStr::new("/run/runit/service"))
+        );
+        assert_eq!(
+            Flavor::Runit.scan_dir_arg(&["/etc/service"]),
+            Some(OsStr::new("/etc/service"))
+        );
+    }
+
+    #[test]
+    fn scan_dir_of_s6_svscan() {
+        let args = ["-d", "3", "-t0", "/run/service"];
+        assert_eq!(
+            Flavor::S6.scan_dir_arg(&args),
+            Some(OsStr::new("/run/service"))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::install::{InstallError, InstallStep, RollbackError, RollbackStep};
+use crate::Tense;
+
+use super::script::Script;
+use super::{control, scan_dir, Action, Flavor, Mode, EXE_VAR};
+
+struct BringUp {
+    flavor: Flavor,
+    services: Vec<PathBuf>,
+}
+
+impl RollbackStep for BringUp {
+    fn perform(&mut self) -> Result<(), RollbackError> {
+        for service in &self.services {
+            control(self.flavor, service, Action::Up)
+                .map_err(RollbackError::ReEnablingServiceDir)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Restarted",
+            Tense::Active => "Restarting",
+            Tense::Questioning => "Restart",
+            Tense::Future => "Will restart",
+        };
+        format!(
+            "{verb} the {} services that spawned the original file{}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+}
+
+struct BringDown {
+    flavor: Flavor,
+    services: Vec<PathBuf>,
+}
+
+impl InstallStep for BringDown {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped",
+            Tense::Active => "Stopping",
+            Tense::Questioning => "Stop",
+            Tense::Future => "Will stop",
+        };
+        format!(
+            "{verb} the {} services running the file at the install location{}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        #[allow(clippy::format_collect)]
+        let services: String = self
+            .services
+            .iter()
+            .map(|service| format!("\n|\t- {}", service.display()))
+            .collect();
+        format!("{}\n| services:{services}", self.describe(tense))
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let mut rollback = Box::new(BringUp {
+            flavor: self.flavor,
+            services: Vec::new(),
+        });
+        for service in &self.services {
+            control(self.flavor, service, Action::Down)?;
+            rollback.services.push(service.clone());
+        }
+        Ok(Some(rollback as Box<dyn RollbackStep>))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisableError {
+    #[error("Could not find the scan directory")]
+    NoScanDir(#[source] super::Error),
+    #[error("Could not read the scan directory")]
+    CouldNotReadDir(#[source] std::io::Error),
+    #[error("Could not find the service that keeps the file in use")]
+    NoServiceFound,
+}
+
+pub(crate) fn disable_step(
+    flavor: Flavor,
+    target: &Path,
+    mode: Mode,
+) -> Result<Vec<Box<dyn InstallStep>>, DisableError> {
+    let scan_dir = scan_dir(flavor, mode).map_err(DisableError::NoScanDir)?;
+
+    let mut services = Vec::new();
+    for entry in fs::read_dir(scan_dir).map_err(DisableError::CouldNotReadDir)? {
+        let service = entry.map_err(DisableError::CouldNotReadDir)?.path();
+        // services not set up by us might not have a (readable) run script
+        let Ok(script) = Script::from_path(service.join("run")) else {
+            continue;
+        };
+        if script.runs(EXE_VAR, target) {
+            services.push(service);
+        }
+    }
+
+    if services.is_empty() {
+        return Err(DisableError::NoServiceFound);
+    }
+    services.sort();
+    Ok(vec![Box::new(BringDown { flavor, services })])
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::install::init::{autogenerated_comment, Params, ShellEscape, Steps};
+use crate::install::{InstallError, InstallStep, RollbackStep, Tense};
+
+use super::{teardown, Action, Error, Flavor};
+
+struct WriteRunScript {
+    flavor: Flavor,
+    script: String,
+    service_dir: PathBuf,
+}
+
+impl InstallStep for WriteRunScript {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Created",
+            Tense::Questioning => "Create",
+            Tense::Future => "Will create",
+            Tense::Active => "Creating",
+        };
+        let dir = self.service_dir.display();
+        format!(
+            "{verb} {} service directory{}\n\t| path: {dir}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Created",
+            Tense::Questioning => "Create",
+            Tense::Future => "Will create",
+            Tense::Active => "Creating",
+        };
+        let dir = self.service_dir.display();
+        let content = self.script.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} {} service directory{}\n| path:\n|\t{dir}\n| run script:\n|\t{content}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_run_script(&self.service_dir, &self.script)?;
+        Ok(Some(Box::new(teardown::RemoveServiceDir {
+            flavor: self.flavor,
+            path: self.service_dir.clone(),
+        })))
+    }
+}
+
+struct EnableService {
+    flavor: Flavor,
+    service_dir: PathBuf,
+    scan_dir: PathBuf,
+    link: PathBuf,
+    already_running: bool,
+}
+
+impl InstallStep for EnableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Linked",
+            Tense::Questioning => "Link",
+            Tense::Future => "Will link",
+            Tense::Active => "Linking",
+        };
+        let start = match (&tense, self.already_running) {
+            (Tense::Past, true) => "restarted",
+            (Tense::Past, false) => "started",
+            (Tense::Questioning | Tense::Future, true) => "restart",
+            (Tense::Questioning | Tense::Future, false) => "start",
+            (Tense::Active, true) => "restarting",
+            (Tense::Active, false) => "starting",
+        };
+        format!(
+            "{verb} the service into the {} scan directory and {start} it{}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        format!(
+            "{}\n| scan directory:\n|\t{}\n| service directory:\n|\t{}",
+            self.describe(tense),
+            self.scan_dir.display(),
+            self.service_dir.display()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        if !self.link.exists() {
+            std::os::unix::fs::symlink(&self.service_dir, &self.link).map_err(Error::Linking)?;
+            super::rescan(self.flavor, &self.scan_dir)?;
+        }
+
+        let rollback = Box::new(teardown::DisableService {
+            flavor: self.flavor,
+            link: self.link.clone(),
+        });
+        // the supervisor only appears after the scanner noticed the link
+        super::wait_for_supervisor(self.flavor, &self.link)?;
+        if self.already_running {
+            super::control(self.flavor, &self.link, Action::Restart)?;
+        } else {
+            super::control(self.flavor, &self.link, Action::Up)?;
+        }
+        super::wait_for_up(self.flavor, &self.link)?;
+        Ok(Some(rollback))
+    }
+}
+
+pub(super) fn steps(
+    flavor: Flavor,
+    service_dir: PathBuf,
+    scan_dir: PathBuf,
+    params: &Params,
+) -> Result<Steps, Error> {
+    let script = render_run_script(flavor, params);
+    let link = scan_dir.join(&params.name);
+    let already_running = link.exists() && super::is_up(flavor, &link);
+
+    Ok(vec![
+        Box::new(WriteRunScript {
+            flavor,
+            script,
+            service_dir: service_dir.clone(),
+        }),
+        Box::new(EnableService {
+            flavor,
+            service_dir,
+            scan_dir,
+            link,
+            already_running,
+        }),
+    ])
+}
+
+fn render_run_script(flavor: Flavor, params: &Params) -> String {
+    let Params {
+        exe_path,
+        exe_args,
+        environment,
+        working_dir,
+        run_as,
+        ..
+    } = params;
+
+    let exe = exe_path.shell_escaped();
+    let args: String = exe_args
+        .iter()
+        .map(String::shell_escaped)
+        .map(|arg| format!(" {arg}"))
+        .collect();
+    let cd_line = working_dir
+        .as_ref()
+        .map(|d| format!("cd {}\n", d.shell_escaped()))
+        .unwrap_or_default();
+    let export_lines = environment
+        .iter()
+        .map(|(key, val)| format!("export {}={}\n", key.shell_escaped(), val.shell_escaped()))
+        .join("");
+    let limits: String = params
+        .limits
+        .wrapper()
+        .into_iter()
+        .map(|word| format!("{word} "))
+        .collect();
+    let setuid = run_as
+        .as_ref()
+        .map(|user| format!("{} {} ", flavor.setuid(), user.shell_escaped()))
+        .unwrap_or_default();
+
+    let comment = autogenerated_comment(params.bin_name);
+    format!(
+        "#!/bin/sh
+{comment}
+
+exec 2>&1
+{cd_line}{export_lines}exe={exe}
+exec {limits}{setuid}\"$exe\"{args}
+"
+    )
+}
+
+fn write_run_script(service_dir: &Path, script: &str) -> Result<(), Error> {
+    let path = service_dir.join("run");
+    fs::create_dir_all(service_dir).map_err(|e| Error::Writing {
+        e,
+        path: path.clone(),
+    })?;
+    let mut f = fs::File::create(&path).map_err(|e| Error::Writing {
+        e,
+        path: path.clone(),
+    })?;
+    f.write_all(script.as_bytes()).map_err(|e| Error::Writing {
+        e,
+        path: path.clone(),
+    })?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).map_err(Error::SettingPermissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_script_drops_privileges() {
+        let params = Params {
+            exe_args: vec!["--location".to_owned(), "Den Helder".to_owned()],
+            run_as: Some("weather".to_owned()),
+            ..Params::for_test("weather_checker", "/usr/local/bin/weather_checker")
+        };
+        let exec_line = |flavor| {
+            render_run_script(flavor, &params)
+                .lines()
+                .find(|line| line.starts_with("exec ") && *line != "exec 2>&1")
+                .map(str::to_owned)
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            exec_line(Flavor::Runit),
+            r#"exec chpst -u weather "$exe" --location 'Den Helder'"#
+        );
+        assert_eq!(
+            exec_line(Flavor::S6),
+            r#"exec s6-setuidgid weather "$exe" --location 'Den Helder'"#
+        );
+
+        let params = Params {
+            run_as: None,
+            ..params
+        };
+        assert!(render_run_script(Flavor::S6, &params).contains("\nexec \"$exe\" --location"));
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install::init::RSteps;
+use crate::install::RemoveError;
+use crate::install::RemoveStep;
+use crate::install::Tense;
+
+use super::{control, rescan, Action, Error, Flavor};
+
+pub(crate) struct RemoveServiceDir {
+    pub(crate) flavor: Flavor,
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveServiceDir {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} {} service directory{} at:\n|\t{path}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_dir_all(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DisableService {
+    pub(crate) flavor: Flavor,
+    /// link to the service directory in the scan directory
+    pub(crate) link: PathBuf,
+}
+
+impl RemoveStep for DisableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped and unlinked",
+            Tense::Questioning => "Stop and unlink",
+            Tense::Future => "Will stop and unlink",
+            Tense::Active => "Stopping and unlinking",
+        };
+        let link = self.link.display();
+        format!(
+            "{verb} {} service{} at:\n|\t{link}",
+            self.flavor.name(),
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        if fs::symlink_metadata(&self.link).is_err() {
+            return Ok(()); // already unlinked
+        }
+        control(self.flavor, &self.link, Action::Down)?;
+        fs::remove_file(&self.link).map_err(Error::Unlinking)?;
+        if let Some(scan_dir) = self.link.parent() {
+            rescan(self.flavor, scan_dir)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn disable_then_remove(
+    flavor: Flavor,
+    service_dir: PathBuf,
+    link: Option<PathBuf>,
+) -> RSteps {
+    let mut steps = Vec::new();
+    if let Some(link) = link {
+        steps.push(Box::new(DisableService { flavor, link }) as Box<dyn RemoveStep>);
+    }
+    steps.push(Box::new(RemoveServiceDir {
+        flavor,
+        path: service_dir,
+    }));
+    steps
+}
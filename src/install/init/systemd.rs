@@ -5,10 +5,12 @@
 
 use std::ffi::OsStr;
 use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 use std::{fs, io};
 
-use crate::install::builder::Trigger;
+use crate::install::builder::{ExtraDirectives, Readiness, Section, Trigger};
 use crate::install::files::NoHomeError;
+use crate::install::init::secrets::{self, RemoveSecrets};
 
 pub use self::unit::FindExeError;
 use self::unit::Unit;
@@ -16,7 +18,9 @@ use self::unit::Unit;
 use super::{ExeLocation, Mode, Params, PathCheckError, RSteps, SetupError, Steps, TearDownError};
 
 mod api;
+pub(crate) mod credentials;
 mod disable_existing;
+pub mod quadlet;
 mod setup;
 mod teardown;
 mod unit;
@@ -87,6 +91,8 @@ pub enum Error {
     WaitingForStop(#[source] api::WaitError),
     #[error("Could not reload services")]
     Reloading(#[source] api::Error),
+    #[error("The schedule can not be expressed as a systemd timer, {0}")]
+    ScheduleUnsupported(&'static str),
 }
 
 pub(crate) fn path_is_systemd(path: &Path) -> Result<bool, PathCheckError> {
@@ -102,6 +108,37 @@ pub(crate) fn path_is_systemd(path: &Path) -> Result<bool, PathCheckError> {
         .any(|c| c == "systemd"))
 }
 
+/// File name of the drop-in with the extra directives
+const DROP_IN: &str = "override.conf";
+
+const UNIT_TYPES: [&str; 11] = [
+    "service",
+    "socket",
+    "device",
+    "mount",
+    "automount",
+    "swap",
+    "target",
+    "path",
+    "timer",
+    "slice",
+    "scope",
+];
+
+/// Unit names are at most 255 characters of ASCII letters, digits, `:`, `-`,
+/// `_`, `.`, `\` and `@`, followed by the unit type. See `man systemd.unit`.
+pub(crate) fn valid_unit_name(name: &str) -> bool {
+    let Some((prefix, unit_type)) = name.rsplit_once('.') else {
+        return false;
+    };
+    name.len() <= 255
+        && !prefix.is_empty()
+        && UNIT_TYPES.contains(&unit_type)
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_' | '.' | '\\' | '@'))
+}
+
 // Check if systemd is the init system (PID 1)
 pub(super) fn not_available() -> Result<bool, SetupError> {
     use sysinfo::{Pid, System};
@@ -126,13 +163,27 @@ pub(super) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
         Mode::System => system_path(),
     }
     .join(&params.name);
+    let params = &Params {
+        credentials: params.credentials.encrypted(params.mode),
+        ..params.clone()
+    };
 
-    Ok(match params.trigger {
-        Trigger::OnSchedule(ref schedule) => {
-            setup::with_timer(&path_without_extension, params, schedule)
+    let mut steps = match &params.trigger {
+        Trigger::OnConnection(listen) => {
+            setup::with_socket(&path_without_extension, params, listen)
         }
-        Trigger::OnBoot => setup::without_timer(&path_without_extension, params)?,
-    })
+        Trigger::OnPathChange(watch) => {
+            setup::with_path_unit(&path_without_extension, params, watch)
+        }
+        trigger => match trigger.schedule() {
+            Some(schedule) => setup::with_timer(&path_without_extension, params, schedule)?,
+            None => setup::without_timer(&path_without_extension, params)?,
+        },
+    };
+    if let Some(secrets) = &params.secrets {
+        steps.insert(0, secrets.step(params.bin_name, params.run_as.as_deref()));
+    }
+    Ok(steps)
 }
 
 pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
@@ -141,12 +192,22 @@ pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)
         Mode::System => system_path(),
     };
 
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+
     let mut steps = Vec::new();
+    let mut socket_steps = Vec::new();
+    let mut drop_ins: RSteps = Vec::new();
+    let mut secrets: RSteps = Vec::new();
     let mut exe_paths = Vec::new();
 
     for entry in fs::read_dir(dir).unwrap() {
         let path = entry.unwrap().path();
         if path.is_dir() {
+            if let Some(drop_in) = our_drop_in(&path) {
+                drop_ins.push(Box::new(teardown::RemoveDropIn { path: drop_in }));
+            }
             continue;
         }
         let Some(extension) = path.extension().and_then(OsStr::to_str) else {
@@ -168,6 +229,20 @@ pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)
                     mode,
                 ));
             }
+            "path" => {
+                steps.extend(teardown::disable_then_remove_with_path_unit(
+                    unit.path.clone(),
+                    service_name,
+                    mode,
+                ));
+            }
+            "socket" => {
+                socket_steps.extend(teardown::disable_then_remove_socket(
+                    unit.path.clone(),
+                    service_name,
+                    mode,
+                ));
+            }
             "service" => {
                 steps.extend(teardown::disable_then_remove_service(
                     unit.path.clone(),
@@ -175,11 +250,25 @@ pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)
                     mode,
                 ));
                 exe_paths.push(unit.exe_path().map_err(TearDownError::FindingExePath)?);
+                // a file named by an `EnvironmentFile=` directive stays
+                if let Ok(path) = secrets::path(service_name, mode) {
+                    if unit.environment_files().any(|file| file == path) {
+                        secrets.push(Box::new(RemoveSecrets { path }));
+                    }
+                }
             }
             _ => continue,
         }
     }
 
+    // the socket goes first, it would start the service again once stopped
+    steps.splice(0..0, socket_steps);
+    // only once the units are disabled
+    if !steps.is_empty() {
+        steps.extend(drop_ins);
+        steps.extend(secrets);
+    }
+
     exe_paths.dedup();
     match (steps.len(), exe_paths.as_slice()) {
         (0, []) => Ok(None),
@@ -190,6 +279,23 @@ pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)
     }
 }
 
+/// The drop-in we wrote in this `<unit>.d` directory, only if the unit is
+/// ours too
+fn our_drop_in(dir: &Path) -> Option<PathBuf> {
+    let unit_path = dir.with_extension("");
+    let is_unit_dir = dir.extension().is_some_and(|ext| ext == "d")
+        && unit_path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| ["service", "timer", "socket", "path"].contains(&ext));
+    if !is_unit_dir {
+        return None;
+    }
+    let drop_in = dir.join(DROP_IN);
+    let ours = |path: &Path| Unit::from_path(path.to_owned()).is_ok_and(|unit| unit.our_service());
+    (ours(&unit_path) && ours(&drop_in)).then_some(drop_in)
+}
+
 /// There are other paths, but for now we return the most commonly used one
 fn user_path() -> Result<PathBuf, NoHomeError> {
     Ok(home::home_dir()
@@ -202,7 +308,89 @@ fn system_path() -> PathBuf {
     PathBuf::from("/etc/systemd/system")
 }
 
-async fn enable(unit: &str, mode: Mode, and_start: bool) -> Result<(), Error> {
+/// systemd's `DefaultTimeoutStartSec=` and `DefaultTimeoutStopSec=`
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(90);
+/// systemd fails the unit once its timeout passes, give it a moment to do
+/// so before we give up on it
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+/// How long to wait for units we know nothing about to start or stop
+const DEFAULT_WAIT: Duration = DEFAULT_TIMEOUT.saturating_add(TIMEOUT_MARGIN);
+
+/// How long to wait for the service to become active. Systemd fails it
+/// after `TimeoutStartSec=`, if that is disabled the watchdog still kills a
+/// service that hangs while starting.
+fn start_timeout(extra_directives: &ExtraDirectives, readiness: &Readiness) -> Duration {
+    let timeout = match configured_timeout(extra_directives, "TimeoutStartSec") {
+        Some(Duration::ZERO) => readiness.watchdog.unwrap_or(DEFAULT_TIMEOUT),
+        Some(timeout) => timeout,
+        None => DEFAULT_TIMEOUT,
+    };
+    timeout.saturating_add(TIMEOUT_MARGIN)
+}
+
+/// How long to wait for the service to stop, systemd kills it after
+/// `TimeoutStopSec=`
+fn stop_timeout(extra_directives: &ExtraDirectives) -> Duration {
+    match configured_timeout(extra_directives, "TimeoutStopSec") {
+        Some(Duration::ZERO) | None => DEFAULT_TIMEOUT,
+        Some(timeout) => timeout,
+    }
+    .saturating_add(TIMEOUT_MARGIN)
+}
+
+/// The timeout set using [`Spec::directive`](crate::install::Spec::directive),
+/// either as `key` or `TimeoutSec=` which sets both. As in systemd the last
+/// valid one wins. Zero means it is disabled.
+fn configured_timeout(extra_directives: &ExtraDirectives, key: &str) -> Option<Duration> {
+    extra_directives
+        .directives
+        .iter()
+        .rev()
+        .filter(|(section, name, _)| {
+            *section == Section::Service && (name == key || name == "TimeoutSec")
+        })
+        .find_map(|(.., value)| parse_time_span(value))
+}
+
+/// A time span such as `90`, `1min 30s` or `infinity`, see `systemd.time(7)`.
+/// Infinity is returned as zero as both disable a timeout.
+fn parse_time_span(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value == "infinity" {
+        return Some(Duration::ZERO);
+    }
+
+    let mut secs = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_end] {
+            "us" | "usec" => 1e-6,
+            "ms" | "msec" => 1e-3,
+            "" | "s" | "sec" | "second" | "seconds" => 1.0,
+            "m" | "min" | "minute" | "minutes" => 60.0,
+            "h" | "hr" | "hour" | "hours" => 3600.0,
+            "d" | "day" | "days" => 86400.0,
+            _ => return None,
+        };
+        secs += number * unit;
+        rest = rest[unit_end..].trim_start();
+    }
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Waits up to `timeout` for the unit to become active if it is started
+async fn enable(unit: &str, mode: Mode, and_start: bool, timeout: Duration) -> Result<(), Error> {
     api::reload(mode).await.map_err(Error::Reloading)?;
     api::enable_service(unit, mode)
         .await
@@ -211,7 +399,7 @@ async fn enable(unit: &str, mode: Mode, and_start: bool) -> Result<(), Error> {
         api::start_service(unit, mode)
             .await
             .map_err(Error::Starting)?;
-        api::wait_for_active(unit, mode)
+        api::wait_for_active(unit, mode, timeout)
             .await
             .map_err(Error::WaitingForStart)?;
     }
@@ -230,7 +418,7 @@ async fn disable(unit_file_name: &str, mode: Mode, and_stop: bool) -> Result<(),
         .map_err(Error::Disabling)?;
     if and_stop {
         stop(unit_file_name, mode).await?;
-        api::wait_for_inactive(unit_file_name, mode)
+        api::wait_for_inactive(unit_file_name, mode, DEFAULT_WAIT)
             .await
             .map_err(Error::WaitingForStop)?;
     }
@@ -248,3 +436,83 @@ async fn is_active(unit_file_name: &str, mode: Mode) -> Result<bool, Error> {
         .await
         .map_err(Error::CheckActive)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_spans() {
+        assert_eq!(parse_time_span("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_time_span("1min 30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_time_span("2h5m"), Some(Duration::from_secs(7500)));
+        assert_eq!(parse_time_span("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_time_span("infinity"), Some(Duration::ZERO));
+        assert_eq!(parse_time_span(""), None);
+        assert_eq!(parse_time_span("soon"), None);
+    }
+
+    #[test]
+    fn timeouts() {
+        let mut params = Params::for_test("weather_checker", "/usr/bin/weather_checker");
+        let timeouts = |params: &Params| {
+            (
+                start_timeout(&params.extra_directives, &params.readiness),
+                stop_timeout(&params.extra_directives),
+            )
+        };
+        assert_eq!(timeouts(&params), (DEFAULT_WAIT, DEFAULT_WAIT));
+
+        params.extra_directives.directives = vec![
+            (Section::Service, "TimeoutSec".to_owned(), "20s".to_owned()),
+            (
+                Section::Service,
+                "TimeoutStartSec".to_owned(),
+                "5min".to_owned(),
+            ),
+            (Section::Unit, "TimeoutStopSec".to_owned(), "1".to_owned()),
+        ];
+        assert_eq!(
+            timeouts(&params),
+            (Duration::from_secs(305), Duration::from_secs(25))
+        );
+
+        params.extra_directives.directives = vec![(
+            Section::Service,
+            "TimeoutStartSec".to_owned(),
+            "infinity".to_owned(),
+        )];
+        params.readiness.watchdog = Some(Duration::from_secs(600));
+        assert_eq!(timeouts(&params).0, Duration::from_secs(605));
+    }
+
+    #[test]
+    fn unit_names() {
+        assert!(valid_unit_name("network-online.target"));
+        assert!(valid_unit_name("getty@tty1.service"));
+        assert!(valid_unit_name("srv-data.mount"));
+        assert!(!valid_unit_name("postgresql"));
+        assert!(!valid_unit_name(".service"));
+        assert!(!valid_unit_name("my service.service"));
+        assert!(!valid_unit_name("db.database"));
+    }
+
+    #[test]
+    fn finds_our_drop_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let ours = format!(
+            "{}\n[Service]\n",
+            super::super::autogenerated_comment("data_collector")
+        );
+        fs::write(dir.path().join("data_collector.service"), &ours).unwrap();
+        let drop_in_dir = dir.path().join("data_collector.service.d");
+        fs::create_dir(&drop_in_dir).unwrap();
+        assert_eq!(our_drop_in(&drop_in_dir), None);
+
+        fs::write(drop_in_dir.join(DROP_IN), &ours).unwrap();
+        assert_eq!(our_drop_in(&drop_in_dir), Some(drop_in_dir.join(DROP_IN)));
+
+        fs::write(dir.path().join("data_collector.service"), "[Service]\n").unwrap();
+        assert_eq!(our_drop_in(&drop_in_dir), None);
+    }
+}
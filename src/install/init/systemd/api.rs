@@ -161,15 +161,19 @@ pub enum WaitError {
     ServiceNotFound,
     #[error("Error listing units")]
     ListUnits(#[source] Error),
-    #[error("Waited longer then 10 seconds for unit to become active")]
-    TimedOut,
+    #[error("Waited longer then {0:?} for the unit to start or stop")]
+    TimedOut(Duration),
     #[error("Unit failed")]
     UnitFailed,
 }
 
-pub(crate) async fn wait_for_active(service: &str, mode: super::Mode) -> Result<(), WaitError> {
+pub(crate) async fn wait_for_active(
+    service: &str,
+    mode: super::Mode,
+    timeout: Duration,
+) -> Result<(), WaitError> {
     let start = Instant::now();
-    while start.elapsed() < Duration::from_secs(10) {
+    while start.elapsed() < timeout {
         let unit = unit_activity(service, mode)
             .await
             .map_err(WaitError::ListUnits)?
@@ -182,12 +186,16 @@ pub(crate) async fn wait_for_active(service: &str, mode: super::Mode) -> Result<
         }
         tokio::time::sleep(Duration::from_millis(50)).await;
     }
-    Err(WaitError::TimedOut)
+    Err(WaitError::TimedOut(timeout))
 }
 
-pub(crate) async fn wait_for_inactive(service: &str, mode: super::Mode) -> Result<(), WaitError> {
+pub(crate) async fn wait_for_inactive(
+    service: &str,
+    mode: super::Mode,
+    timeout: Duration,
+) -> Result<(), WaitError> {
     let start = Instant::now();
-    while start.elapsed() < Duration::from_secs(10) {
+    while start.elapsed() < timeout {
         let unit = unit_activity(service, mode)
             .await
             .map_err(WaitError::ListUnits)?
@@ -197,5 +205,5 @@ pub(crate) async fn wait_for_inactive(service: &str, mode: super::Mode) -> Resul
         }
         tokio::time::sleep(Duration::from_millis(50)).await;
     }
-    Err(WaitError::TimedOut)
+    Err(WaitError::TimedOut(timeout))
 }
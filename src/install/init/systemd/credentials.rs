@@ -0,0 +1,232 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use itertools::Itertools;
+
+use crate::install::builder::CredentialSource;
+use crate::install::Mode;
+
+/// A credential systemd passes to the service in `$CREDENTIALS_DIRECTORY`
+#[derive(Clone)]
+pub(crate) struct Credential {
+    pub(crate) name: String,
+    pub(crate) value: Value,
+}
+
+#[derive(Clone)]
+pub(crate) enum Value {
+    /// `LoadCredential=`, read from a file when the service starts
+    Load(PathBuf),
+    /// `SetCredential=`, inline in the unit file
+    Set(Vec<u8>),
+    /// `SetCredentialEncrypted=`, base64 output of `systemd-creds encrypt`
+    SetEncrypted(String),
+}
+
+/// Never print inline values
+impl std::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match &self.value {
+            Value::Load(path) => format!("load from {}", path.display()),
+            Value::Set(_) => "inline".to_owned(),
+            Value::SetEncrypted(_) => "inline encrypted".to_owned(),
+        };
+        write!(f, "{}: {kind}", self.name)
+    }
+}
+
+/// The credentials of the service, set using
+/// [`Spec::credential`](crate::install::Spec::credential)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Credentials(pub(crate) Vec<Credential>);
+
+impl Credentials {
+    /// Replaces an earlier credential with the same name
+    pub(crate) fn insert(&mut self, name: String, source: CredentialSource) {
+        self.0.retain(|credential| credential.name != name);
+        let value = match source {
+            CredentialSource::File(path) => Value::Load(path),
+            CredentialSource::Inline(data) => Value::Set(data),
+        };
+        self.0.push(Credential { name, value });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// A name that is not a valid file name or contains the `:` separating
+    /// it from the value
+    pub(crate) fn invalid_name(&self) -> Option<&str> {
+        self.0.iter().map(|c| c.name.as_str()).find(|name| {
+            name.is_empty()
+                || name.len() > 255
+                || matches!(*name, "." | "..")
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        })
+    }
+
+    /// A file to load from that is not absolute or does not fit on one line
+    pub(crate) fn invalid_path(&self) -> Option<&Path> {
+        self.0
+            .iter()
+            .filter_map(|credential| match &credential.value {
+                Value::Load(path) => Some(path.as_path()),
+                Value::Set(_) | Value::SetEncrypted(_) => None,
+            })
+            .find(|path| {
+                !path.is_absolute() || path.as_os_str().as_encoded_bytes().contains(&b'\n')
+            })
+    }
+
+    /// The `[Service]` settings, one per line
+    pub(crate) fn directives(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|Credential { name, value }| match value {
+                Value::Load(path) => format!("LoadCredential={name}:{}", path.display()),
+                Value::Set(data) => format!("SetCredential={name}:{}", escape(data)),
+                Value::SetEncrypted(base64) => format!("SetCredentialEncrypted={name}:{base64}"),
+            })
+            .collect()
+    }
+
+    /// Inline values encrypted using `systemd-creds`. Values it can not
+    /// encrypt, for example because it is not installed or too old to
+    /// encrypt for a user service, stay as they are.
+    pub(crate) fn encrypted(&self, mode: Mode) -> Self {
+        let credentials = self
+            .0
+            .iter()
+            .map(|credential| match &credential.value {
+                Value::Set(data) => Credential {
+                    name: credential.name.clone(),
+                    value: encrypt(&credential.name, data, mode)
+                        .map_or_else(|| Value::Set(data.clone()), Value::SetEncrypted),
+                },
+                Value::Load(_) | Value::SetEncrypted(_) => credential.clone(),
+            })
+            .collect();
+        Self(credentials)
+    }
+}
+
+/// The host key encrypts system credentials, user services need systemd 256
+/// or newer to decrypt credentials encrypted for them
+fn encrypt(name: &str, data: &[u8], mode: Mode) -> Option<String> {
+    let mut command = Command::new("systemd-creds");
+    command.arg("encrypt").arg(format!("--name={name}"));
+    if let Mode::User = mode {
+        command.arg("--user");
+    }
+    let mut child = command
+        .args(["-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let written = child.stdin.take().expect("stdin is piped").write_all(data);
+    let output = child.wait_with_output().ok()?;
+    if written.is_err() || !output.status.success() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            "Could not encrypt credential {name}, systemd-creds failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return None;
+    }
+    let base64: String = String::from_utf8(output.stdout)
+        .ok()?
+        .split_whitespace()
+        .collect();
+    (!base64.is_empty()).then_some(base64)
+}
+
+/// `SetCredential=` understands C style escapes, only printable ASCII is
+/// left as is
+fn escape(data: &[u8]) -> String {
+    data.iter()
+        .map(|&byte| match byte {
+            b'\\' => "\\\\".to_owned(),
+            b'!'..=b'~' => char::from(byte).to_string(),
+            byte => format!("\\x{byte:02x}"),
+        })
+        .collect()
+}
+
+/// The unit with the values of unencrypted inline credentials hidden, for
+/// describing it
+pub(crate) fn redact(unit: &str) -> String {
+    unit.split('\n')
+        .map(|line| match line.strip_prefix("SetCredential=") {
+            Some(setting) => {
+                let name = setting.split_once(':').map_or(setting, |(name, _)| name);
+                format!("SetCredential={name}:<hidden>")
+            }
+            None => line.to_owned(),
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives() {
+        let mut credentials = Credentials::default();
+        credentials.insert(
+            "tls.key".to_owned(),
+            CredentialSource::File(PathBuf::from("/etc/ssl/private/site.key")),
+        );
+        credentials.insert(
+            "db-password".to_owned(),
+            CredentialSource::Inline(b"hunter2\\ \n".to_vec()),
+        );
+        assert_eq!(
+            credentials.directives(),
+            [
+                "LoadCredential=tls.key:/etc/ssl/private/site.key",
+                "SetCredential=db-password:hunter2\\\\\\x20\\x0a",
+            ]
+        );
+
+        let unit = credentials.directives().join("\n");
+        let redacted = redact(&unit);
+        assert!(!redacted.contains("hunter2"));
+        assert!(redacted.contains("SetCredential=db-password:<hidden>"));
+        assert!(redacted.contains("LoadCredential=tls.key:/etc/ssl/private/site.key"));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut credentials = Credentials::default();
+        credentials.insert("ok".to_owned(), CredentialSource::Inline(Vec::new()));
+        assert_eq!(credentials.invalid_name(), None);
+        credentials.insert("a:b".to_owned(), CredentialSource::Inline(Vec::new()));
+        assert_eq!(credentials.invalid_name(), Some("a:b"));
+
+        let mut credentials = Credentials::default();
+        credentials.insert(
+            "key".to_owned(),
+            CredentialSource::File(PathBuf::from("relative/key")),
+        );
+        assert_eq!(credentials.invalid_path(), Some(Path::new("relative/key")));
+    }
+
+    #[test]
+    fn debug_hides_values() {
+        let mut credentials = Credentials::default();
+        credentials.insert(
+            "token".to_owned(),
+            CredentialSource::Inline(b"hunter2".to_vec()),
+        );
+        let debug = format!("{credentials:?}");
+        assert!(debug.contains("token"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
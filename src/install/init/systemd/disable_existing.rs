@@ -21,7 +21,7 @@ impl RollbackStep for ReEnable {
     fn perform(&mut self) -> Result<(), RollbackError> {
         for unit in &self.units {
             on_seperate_tokio_thread! {{
-                super::enable(&unit.file_name, self.mode, true).await.map_err(RollbackError::ReEnabling)
+                super::enable(&unit.file_name, self.mode, true, super::DEFAULT_WAIT).await.map_err(RollbackError::ReEnabling)
             }}?;
         }
         Ok(())
@@ -0,0 +1,480 @@
+//! Runs an OCI image as a service using Podman Quadlet. We write a `.container`
+//! file, systemd's quadlet generator turns that into a service on reload.
+//! Generated services can not be enabled through systemd, instead the
+//! `[Install]` section of the `.container` file is honored by the generator.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use crate::install::builder::{
+    Dependencies, ExtraDirectives, Readiness, RestartPolicy, Section, Trigger,
+};
+use crate::install::files::NoHomeError;
+use crate::install::init::secrets::{self, RemoveSecrets, Secrets};
+use crate::install::init::{autogenerated_comment, RSteps, SetupError, Steps, SystemdEscape};
+use crate::install::limits::Limits;
+use crate::install::{InstallError, InstallStep, Mode, RemoveError, RemoveStep};
+use crate::install::{RollbackStep, Tense};
+
+use super::api::on_seperate_tokio_thread;
+use super::setup::{
+    render_dependencies, render_environment_file, render_environment_section, render_restart,
+    wanted_by,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Containers can only be started on boot, schedules are not supported")]
+    ScheduleUnsupported,
+    #[error(
+        "Containers can only be started on boot, starting them on connection is not supported"
+    )]
+    SocketActivationUnsupported,
+    #[error(
+        "Containers can only be started on boot, starting them on path change is not supported"
+    )]
+    PathTriggerUnsupported,
+    #[error("Can not run a container as another user, set the user inside the image instead")]
+    RunAsUnsupported,
+    #[error("Can not sandbox a container using hardening settings, podman isolates it already")]
+    HardeningUnsupported,
+    #[error("Can not write the extra directives of a container to a drop-in, leave them in the quadlet instead")]
+    DropInUnsupported,
+    #[error("Podman sets `WatchdogSec=` and `NotifyAccess=` of a container itself, only `notify_ready` is supported")]
+    NotifySettingsUnsupported,
+    #[error(
+        "Podman does not pass systemd credentials on to a container, use a podman secret instead"
+    )]
+    CredentialsUnsupported,
+    #[error("Could not read the quadlet directory")]
+    ReadingDir(#[source] std::io::Error),
+    #[error("Could not verify quadlet file was created by us, could not open it")]
+    Verifying(#[source] std::io::Error),
+}
+
+/// Everything needed to set up a container service
+#[derive(Debug, Clone)]
+pub(crate) struct Params {
+    pub(crate) name: String,
+    pub(crate) bin_name: &'static str,
+    pub(crate) description: Option<String>,
+
+    pub(crate) image: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) environment: HashMap<String, String>,
+    pub(crate) working_dir: Option<PathBuf>,
+
+    pub(crate) trigger: Trigger,
+    pub(crate) restart: RestartPolicy,
+    pub(crate) limits: Limits,
+    pub(crate) dependencies: Dependencies,
+    pub(crate) extra_directives: ExtraDirectives,
+    pub(crate) readiness: Readiness,
+    pub(crate) secrets: Option<Secrets>,
+    pub(crate) mode: Mode,
+}
+
+struct WriteQuadlet {
+    content: String,
+    path: PathBuf,
+    mode: Mode,
+}
+
+impl InstallStep for WriteQuadlet {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} podman quadlet container file{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.content.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} podman quadlet container file{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let write = |path: &Path| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, &self.content)
+        };
+        write(&self.path).map_err(|e| super::Error::Writing {
+            e,
+            path: self.path.clone(),
+        })?;
+        Ok(Some(Box::new(RemoveQuadlet {
+            path: self.path.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
+struct StartService {
+    name: String,
+    mode: Mode,
+    already_running: bool,
+    /// How long to wait for the service to become active, includes stopping
+    /// it if it is restarted
+    timeout: Duration,
+}
+
+impl InstallStep for StartService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match (&tense, self.already_running) {
+            (Tense::Past, true) => "Reloaded systemd and restarted",
+            (Tense::Past, false) => "Reloaded systemd and started",
+            (Tense::Questioning, true) => "Reload systemd and restart",
+            (Tense::Questioning, false) => "Reload systemd and start",
+            (Tense::Future, true) => "Will reload systemd and restart",
+            (Tense::Future, false) => "Will reload systemd and start",
+            (Tense::Active, true) => "Reloading systemd and restarting",
+            (Tense::Active, false) => "Reloading systemd and starting",
+        };
+        format!(
+            "{verb} the generated {} service: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let name = self.name.clone() + ".service";
+        on_seperate_tokio_thread! {{
+            // runs the quadlet generator
+            super::api::reload(self.mode).await.map_err(super::Error::Reloading)?;
+            if self.already_running {
+                super::restart(&name, self.mode).await?;
+            } else {
+                super::api::start_service(&name, self.mode)
+                    .await
+                    .map_err(super::Error::Starting)?;
+            }
+            super::api::wait_for_active(&name, self.mode, self.timeout)
+                .await
+                .map_err(super::Error::WaitingForStart)
+        }}?;
+        Ok(Some(Box::new(StopService {
+            name: self.name.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
+pub(crate) struct StopService {
+    name: String,
+    mode: Mode,
+}
+
+impl RemoveStep for StopService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped",
+            Tense::Questioning => "Stop",
+            Tense::Future => "Will stop",
+            Tense::Active => "Stopping",
+        };
+        format!(
+            "{verb} the generated {} service: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        let name = self.name.clone() + ".service";
+        on_seperate_tokio_thread! {{
+            if super::is_active(&name, self.mode).await? {
+                super::stop(&name, self.mode).await?;
+                super::api::wait_for_inactive(&name, self.mode, super::DEFAULT_WAIT)
+                    .await
+                    .map_err(super::Error::WaitingForStop)?;
+            }
+            Ok::<_, super::Error>(())
+        }}?;
+        Ok(())
+    }
+}
+
+pub(crate) struct RemoveQuadlet {
+    path: PathBuf,
+    mode: Mode,
+}
+
+impl RemoveStep for RemoveQuadlet {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} podman quadlet container file{} at:\n|\t{path}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(super::Error::Removing)?;
+        // makes the generated service disappear
+        on_seperate_tokio_thread! {{
+            super::api::reload(self.mode).await.map_err(super::Error::Reloading)
+        }}?;
+        Ok(())
+    }
+}
+
+pub(crate) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported.into());
+    }
+    if matches!(params.trigger, Trigger::OnConnection(_)) {
+        return Err(Error::SocketActivationUnsupported.into());
+    }
+    if matches!(params.trigger, Trigger::OnPathChange(_)) {
+        return Err(Error::PathTriggerUnsupported.into());
+    }
+    if params.extra_directives.drop_in {
+        return Err(Error::DropInUnsupported.into());
+    }
+    if !params.readiness.directives().is_empty() {
+        return Err(Error::NotifySettingsUnsupported.into());
+    }
+
+    let path = dir(params.mode)?.join(format!("{}.container", params.name));
+    let content = render(params);
+    let service = params.name.clone() + ".service";
+    let already_running = on_seperate_tokio_thread! {{
+        super::is_active(&service, params.mode).await
+    }}?;
+    let timeout = if already_running {
+        super::stop_timeout(&params.extra_directives)
+            + super::start_timeout(&params.extra_directives, &params.readiness)
+    } else {
+        super::start_timeout(&params.extra_directives, &params.readiness)
+    };
+
+    let write_secrets = params
+        .secrets
+        .as_ref()
+        .map(|secrets| secrets.step(params.bin_name, None));
+    Ok(write_secrets
+        .into_iter()
+        .chain([
+            Box::new(WriteQuadlet {
+                content,
+                path,
+                mode: params.mode,
+            }) as Box<dyn InstallStep>,
+            Box::new(StartService {
+                name: params.name.clone(),
+                mode: params.mode,
+                already_running,
+                timeout,
+            }),
+        ])
+        .collect())
+}
+
+/// Returns `None` if there are no quadlet files created by `bin_name`
+pub(crate) fn tear_down_steps(bin_name: &str, mode: Mode) -> Result<Option<RSteps>, Error> {
+    let Ok(quadlet_dir) = dir(mode) else {
+        return Ok(None);
+    };
+    if !quadlet_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut steps: RSteps = Vec::new();
+    for (path, content) in our_quadlets(&quadlet_dir, bin_name)? {
+        let Some(name) = path.file_stem().and_then(OsStr::to_str) else {
+            continue;
+        };
+        steps.push(Box::new(StopService {
+            name: name.to_owned(),
+            mode,
+        }));
+        // a file named by an `EnvironmentFile=` directive stays
+        let secrets = secrets::path(name, mode).ok().filter(|ours| {
+            content
+                .lines()
+                .filter_map(|line| line.strip_prefix("EnvironmentFile="))
+                .any(|file| Path::new(file) == ours)
+        });
+        steps.push(Box::new(RemoveQuadlet { path, mode }));
+        if let Some(path) = secrets {
+            steps.push(Box::new(RemoveSecrets { path }));
+        }
+    }
+
+    Ok((!steps.is_empty()).then_some(steps))
+}
+
+/// The quadlet files in `dir` and their content. Other programs using this
+/// crate leave the same comment with their own name, their containers stay.
+fn our_quadlets(dir: &Path, bin_name: &str) -> Result<Vec<(PathBuf, String)>, Error> {
+    let comment = autogenerated_comment(bin_name);
+    let mut quadlets = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if path.extension() != Some(OsStr::new("container")) {
+            continue;
+        }
+        let content = fs::read_to_string(&path).map_err(Error::Verifying)?;
+        if content.contains(&comment) {
+            quadlets.push((path, content));
+        }
+    }
+    Ok(quadlets)
+}
+
+fn render(params: &Params) -> String {
+    let Params {
+        name,
+        image,
+        args,
+        environment,
+        working_dir,
+        mode,
+        ..
+    } = params;
+
+    let description = params
+        .description
+        .clone()
+        .unwrap_or_else(|| format!("starts {name}"));
+    let exec_line = if args.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\nExec={}",
+            args.iter().map(String::systemd_escape).join(" ")
+        )
+    };
+    let working_dir_line = working_dir
+        .as_ref()
+        .map(|d| format!("\nWorkingDir={}", d.systemd_escape()))
+        .unwrap_or_default();
+    let environment_line = render_environment_section(environment);
+    let environment_file_line = render_environment_file(params.secrets.as_ref());
+    let notify_line = if params.readiness.notify {
+        "\nNotify=true"
+    } else {
+        ""
+    };
+    let (start_limit_lines, restart_lines) = render_restart(&params.restart);
+    let limit_lines: String = params
+        .limits
+        .directives()
+        .into_iter()
+        .map(|directive| format!("\n{directive}"))
+        .collect();
+    let extra = &params.extra_directives;
+    let extra_service_lines = extra.lines(Section::Service);
+    let service_section =
+        if restart_lines.is_empty() && limit_lines.is_empty() && extra_service_lines.is_empty() {
+            String::new()
+        } else {
+            format!("\n[Service]{restart_lines}{limit_lines}{extra_service_lines}\n")
+        };
+    let extra_unit_lines = extra.lines(Section::Unit);
+    let extra_install_lines = extra.lines(Section::Install);
+    let dependencies_lines = render_dependencies(&params.dependencies, "network-online.target");
+    let target = wanted_by(&params.dependencies, *mode);
+
+    let comment = autogenerated_comment(params.bin_name);
+    format!(
+        "{comment}
+
+[Unit]
+Description={description}{dependencies_lines}{start_limit_lines}{extra_unit_lines}
+
+[Container]
+Image={image}
+ContainerName={name}{exec_line}{working_dir_line}{environment_line}{environment_file_line}{notify_line}
+{service_section}
+[Install]
+WantedBy={target}{extra_install_lines}
+"
+    )
+}
+
+fn dir(mode: Mode) -> Result<PathBuf, NoHomeError> {
+    Ok(match mode {
+        Mode::User => home::home_dir()
+            .ok_or(NoHomeError)?
+            .join(".config/containers/systemd"),
+        Mode::System => PathBuf::from("/etc/containers/systemd"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(name: &str, bin_name: &'static str) -> Params {
+        Params {
+            name: name.to_owned(),
+            bin_name,
+            description: None,
+            image: "docker.io/library/nginx:latest".to_owned(),
+            args: Vec::new(),
+            environment: HashMap::new(),
+            working_dir: None,
+            trigger: Trigger::OnBoot,
+            restart: RestartPolicy::default(),
+            limits: Limits::default(),
+            dependencies: Dependencies::default(),
+            extra_directives: ExtraDirectives::default(),
+            readiness: Readiness::default(),
+            secrets: None,
+            mode: Mode::User,
+        }
+    }
+
+    #[test]
+    fn only_quadlets_of_our_bin() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, bin_name) in [("web", "web_manager"), ("proxy", "proxy_manager")] {
+            let path = dir.path().join(format!("{name}.container"));
+            fs::write(path, render(&params(name, bin_name))).unwrap();
+        }
+        fs::write(
+            dir.path().join("db.container"),
+            "[Container]\nImage=postgres\n",
+        )
+        .unwrap();
+
+        let ours = our_quadlets(dir.path(), "web_manager").unwrap();
+        let paths: Vec<_> = ours.into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [dir.path().join("web.container")]);
+        assert!(our_quadlets(dir.path(), "web").unwrap().is_empty());
+    }
+}
@@ -2,24 +2,44 @@ use crate::install::init::systemd;
 use crate::install::{init, InstallError, RollbackStep, Tense};
 use std::collections::HashMap;
 use std::io::{self, Write};
+use std::iter;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use itertools::Itertools;
 
-use crate::install::builder::Trigger;
+use crate::install::builder::{
+    Dependencies, Jitter, Listen, Restart, RestartPolicy, Section, StartLimit, Trigger, WatchPath,
+};
+use crate::install::init::secrets::Secrets;
 use crate::install::init::{Params, ShellEscape, Steps, SystemdEscape};
 use crate::install::InstallStep;
 use crate::install::Mode;
+use crate::schedule::expr::Expr;
 use crate::schedule::Schedule;
 
 use super::api::on_seperate_tokio_thread;
-use super::teardown::DisableTimer;
+use super::credentials;
+use super::teardown::{DisablePathUnit, DisableSocket, DisableTimer};
+use super::DROP_IN;
 use super::{teardown, Error};
 
 struct WriteService {
     unit: String,
     path: PathBuf,
+    /// Hardening settings in the unit
+    sandbox: Vec<String>,
+}
+
+impl WriteService {
+    fn new(path: PathBuf, params: &Params) -> Self {
+        Self {
+            unit: render_service(params),
+            path,
+            sandbox: params.hardening.directives(),
+        }
+    }
 }
 
 impl InstallStep for WriteService {
@@ -31,8 +51,18 @@ impl InstallStep for WriteService {
             Tense::Active => "Writing",
         };
         let path = self.path.display();
+        let sandbox = if self.sandbox.is_empty() {
+            String::new()
+        } else {
+            let settings = self
+                .sandbox
+                .iter()
+                .filter_map(|directive| directive.split_once('=').map(|(key, _)| key))
+                .join(", ");
+            format!("\n\t| sandbox: {settings}")
+        };
         format!(
-            "{verb} systemd service unit{}\n\t| path: {path}",
+            "{verb} systemd service unit{}\n\t| path: {path}{sandbox}",
             tense.punct()
         )
     }
@@ -45,9 +75,14 @@ impl InstallStep for WriteService {
             Tense::Active => "Writing",
         };
         let path = self.path.display();
-        let content = self.unit.trim_end().replace('\n', "\n|\t");
+        let sandbox = if self.sandbox.is_empty() {
+            String::new()
+        } else {
+            format!("\n| sandbox:\n|\t{}", self.sandbox.join("\n|\t"))
+        };
+        let content = credentials::redact(self.unit.trim_end()).replace('\n', "\n|\t");
         format!(
-            "{verb} systemd service unit{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            "{verb} systemd service unit{}\n| path:\n|\t{path}{sandbox}\n| content:\n|\t{content}",
             tense.punct()
         )
     }
@@ -66,6 +101,18 @@ impl InstallStep for WriteService {
 struct WriteTimer {
     unit: String,
     path: PathBuf,
+    /// Missed runs are caught up, `Persistent=true`
+    persistent: bool,
+}
+
+impl WriteTimer {
+    fn missed_runs(&self) -> &'static str {
+        if self.persistent {
+            "\n\t| missed runs: started after boot or wake up (Persistent=true)"
+        } else {
+            ""
+        }
+    }
 }
 
 impl InstallStep for WriteTimer {
@@ -77,8 +124,9 @@ impl InstallStep for WriteTimer {
             Tense::Active => "Writing",
         };
         let path = self.path.display();
+        let missed_runs = self.missed_runs();
         format!(
-            "{verb} systemd timer unit{}\n\t| path: {path}",
+            "{verb} systemd timer unit{}\n\t| path: {path}{missed_runs}",
             tense.punct()
         )
     }
@@ -109,9 +157,197 @@ impl InstallStep for WriteTimer {
     }
 }
 
+struct WriteSocket {
+    unit: String,
+    path: PathBuf,
+    listen: Vec<Listen>,
+}
+
+impl WriteSocket {
+    fn addresses(&self) -> String {
+        self.listen
+            .iter()
+            .map(|listen| match listen {
+                Listen::Stream(address) => format!("{address} (stream)"),
+                Listen::Datagram(address) => format!("{address} (datagram)"),
+            })
+            .join(", ")
+    }
+}
+
+impl InstallStep for WriteSocket {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let addresses = self.addresses();
+        format!(
+            "{verb} systemd socket unit{}\n\t| path: {path}\n\t| listen: {addresses}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.unit.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} systemd socket unit{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_unit(&self.path, &self.unit).map_err(|e| Error::Writing {
+            e,
+            path: self.path.clone(),
+        })?;
+        Ok(Some(Box::new(teardown::RemoveSocket {
+            path: self.path.clone(),
+        })))
+    }
+}
+
+struct WritePathUnit {
+    unit: String,
+    path: PathBuf,
+    watch: Vec<WatchPath>,
+}
+
+impl WritePathUnit {
+    fn watched(&self) -> String {
+        self.watch
+            .iter()
+            .map(|watch| match watch {
+                WatchPath::Changed(path) => format!("{} (changed)", path.display()),
+                WatchPath::DirectoryNotEmpty(path) => format!("{} (not empty)", path.display()),
+            })
+            .join(", ")
+    }
+}
+
+impl InstallStep for WritePathUnit {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let watched = self.watched();
+        format!(
+            "{verb} systemd path unit{}\n\t| path: {path}\n\t| watch: {watched}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.unit.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} systemd path unit{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_unit(&self.path, &self.unit).map_err(|e| Error::Writing {
+            e,
+            path: self.path.clone(),
+        })?;
+        Ok(Some(Box::new(teardown::RemovePathUnit {
+            path: self.path.clone(),
+        })))
+    }
+}
+
+struct WriteDropIn {
+    content: String,
+    path: PathBuf,
+}
+
+impl WriteDropIn {
+    /// The step writing the `override.conf` drop-in for the unit of this
+    /// type, none if there is no content
+    fn step(
+        path_without_extension: &Path,
+        unit_type: &str,
+        content: Option<String>,
+    ) -> Option<Box<dyn InstallStep>> {
+        let dir = with_added_extension(path_without_extension, &format!("{unit_type}.d"));
+        let path = dir.join(DROP_IN);
+        content.map(|content| Box::new(Self { content, path }) as Box<dyn InstallStep>)
+    }
+}
+
+impl InstallStep for WriteDropIn {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} systemd drop-in with extra directives{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.content.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} systemd drop-in with extra directives{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let write = || {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            write_unit(&self.path, &self.content)
+        };
+        write().map_err(|e| Error::Writing {
+            e,
+            path: self.path.clone(),
+        })?;
+        Ok(Some(Box::new(teardown::RemoveDropIn {
+            path: self.path.clone(),
+        })))
+    }
+}
+
 struct EnableTimer {
     name: String,
     mode: Mode,
+    schedule: Schedule,
+    timezone: Option<String>,
 }
 
 impl InstallStep for EnableTimer {
@@ -130,10 +366,26 @@ impl InstallStep for EnableTimer {
         )
     }
 
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Enabled",
+            Tense::Questioning => "Enable",
+            Tense::Future => "Will Enable",
+            Tense::Active => "Enabling",
+        };
+        let next_runs = self.schedule.preview(self.timezone.as_deref());
+        format!(
+            "{verb} systemd {} timer: {}{}\n| next runs:\n|\t{next_runs}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
     fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
         let name = self.name.clone() + ".timer";
         on_seperate_tokio_thread! {{
-            super::enable(name.as_ref(), self.mode, true).await
+            super::enable(name.as_ref(), self.mode, true, super::DEFAULT_WAIT).await
         }}?;
         Ok(Some(Box::new(DisableTimer {
             name: self.name.clone(),
@@ -142,11 +394,80 @@ impl InstallStep for EnableTimer {
     }
 }
 
+struct EnableSocket {
+    name: String,
+    mode: Mode,
+}
+
+impl InstallStep for EnableSocket {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Enabled and started",
+            Tense::Questioning => "Enable and start",
+            Tense::Future => "Will enable and start",
+            Tense::Active => "Enabling and starting",
+        };
+        format!(
+            "{verb} systemd {} socket: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let name = self.name.clone() + ".socket";
+        on_seperate_tokio_thread! {{
+            super::enable(name.as_ref(), self.mode, true, super::DEFAULT_WAIT).await
+        }}?;
+        Ok(Some(Box::new(DisableSocket {
+            name: self.name.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
+struct EnablePathUnit {
+    name: String,
+    mode: Mode,
+}
+
+impl InstallStep for EnablePathUnit {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Enabled and started",
+            Tense::Questioning => "Enable and start",
+            Tense::Future => "Will enable and start",
+            Tense::Active => "Enabling and starting",
+        };
+        format!(
+            "{verb} systemd {} path unit: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let name = self.name.clone() + ".path";
+        on_seperate_tokio_thread! {{
+            super::enable(name.as_ref(), self.mode, true, super::DEFAULT_WAIT).await
+        }}?;
+        Ok(Some(Box::new(DisablePathUnit {
+            name: self.name.clone(),
+            mode: self.mode,
+        })))
+    }
+}
+
 struct EnableService {
     name: String,
     mode: Mode,
     start: bool,
     already_running: bool,
+    /// How long to wait for the service to become active, includes stopping
+    /// it if it is restarted
+    timeout: Duration,
 }
 
 impl InstallStep for EnableService {
@@ -180,10 +501,13 @@ impl InstallStep for EnableService {
     fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
         let name = self.name.clone() + ".service";
         on_seperate_tokio_thread! {{
-            super::enable(name.as_ref(), self.mode, self.start).await?;
+            super::enable(name.as_ref(), self.mode, self.start, self.timeout).await?;
 
             if self.already_running {
                 super::restart(name.as_ref(), self.mode).await?;
+                super::api::wait_for_active(name.as_ref(), self.mode, self.timeout)
+                    .await
+                    .map_err(super::Error::WaitingForStart)?;
             }
             Ok::<_, InstallError>(())
         }}?;
@@ -207,41 +531,146 @@ pub(crate) fn with_timer(
     path_without_extension: &Path,
     params: &Params,
     schedule: &Schedule,
-) -> Steps {
-    let unit = render_service(params);
+) -> Result<Steps, Error> {
     let path = with_added_extension(path_without_extension, "service");
-    let create_service = Box::new(WriteService { unit, path });
-    let unit = render_timer(params, schedule);
+    let create_service: Box<dyn InstallStep> = Box::new(WriteService::new(path, params));
+    let service_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "service",
+        render_drop_in(params, &[Section::Unit, Section::Service]),
+    );
+    let timer_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "timer",
+        render_drop_in(params, &[Section::Timer, Section::Install]),
+    );
+    let unit = render_timer(params, schedule)?;
     let path = with_added_extension(path_without_extension, "timer");
-    let create_timer = Box::new(WriteTimer { unit, path });
+    let persistent = catches_up(params, schedule);
+    let create_timer = Box::new(WriteTimer {
+        unit,
+        path,
+        persistent,
+    });
     let enable = Box::new(EnableTimer {
         name: params.name.clone(),
         mode: params.mode,
+        schedule: schedule.clone(),
+        timezone: params.timezone.clone(),
+    });
+
+    Ok(iter::once(create_service)
+        .chain(service_drop_in)
+        .chain(iter::once(create_timer as Box<dyn InstallStep>))
+        .chain(timer_drop_in)
+        .chain(iter::once(enable as Box<dyn InstallStep>))
+        .collect())
+}
+
+pub(crate) fn with_socket(
+    path_without_extension: &Path,
+    params: &Params,
+    listen: &[Listen],
+) -> Steps {
+    let path = with_added_extension(path_without_extension, "service");
+    let create_service: Box<dyn InstallStep> = Box::new(WriteService::new(path, params));
+    let service_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "service",
+        render_drop_in(params, &[Section::Unit, Section::Service]),
+    );
+    let socket_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "socket",
+        render_drop_in(params, &[Section::Socket, Section::Install]),
+    );
+    let create_socket = Box::new(WriteSocket {
+        unit: render_socket(params, listen),
+        path: with_added_extension(path_without_extension, "socket"),
+        listen: listen.to_vec(),
+    });
+    let enable = Box::new(EnableSocket {
+        name: params.name.clone(),
+        mode: params.mode,
+    });
+
+    iter::once(create_service)
+        .chain(service_drop_in)
+        .chain(iter::once(create_socket as Box<dyn InstallStep>))
+        .chain(socket_drop_in)
+        .chain(iter::once(enable as Box<dyn InstallStep>))
+        .collect()
+}
+
+pub(crate) fn with_path_unit(
+    path_without_extension: &Path,
+    params: &Params,
+    watch: &[WatchPath],
+) -> Steps {
+    let path = with_added_extension(path_without_extension, "service");
+    let create_service: Box<dyn InstallStep> = Box::new(WriteService::new(path, params));
+    let service_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "service",
+        render_drop_in(params, &[Section::Unit, Section::Service]),
+    );
+    let path_drop_in = WriteDropIn::step(
+        path_without_extension,
+        "path",
+        render_drop_in(params, &[Section::Path, Section::Install]),
+    );
+    let create_path_unit = Box::new(WritePathUnit {
+        unit: render_path_unit(params, watch),
+        path: with_added_extension(path_without_extension, "path"),
+        watch: watch.to_vec(),
+    });
+    let enable = Box::new(EnablePathUnit {
+        name: params.name.clone(),
+        mode: params.mode,
     });
 
-    vec![create_service, create_timer, enable]
+    iter::once(create_service)
+        .chain(service_drop_in)
+        .chain(iter::once(create_path_unit as Box<dyn InstallStep>))
+        .chain(path_drop_in)
+        .chain(iter::once(enable as Box<dyn InstallStep>))
+        .collect()
 }
 
 pub(crate) fn without_timer(
     path_without_extension: &Path,
     params: &Params,
 ) -> Result<Steps, systemd::Error> {
-    let unit = render_service(params);
     let path = with_added_extension(path_without_extension, "service");
     let already_running = on_seperate_tokio_thread! {{
         systemd::is_active(&params.name, params.mode).await
     }}?;
 
-    let create_service = Box::new(WriteService { unit, path });
+    let create_service: Box<dyn InstallStep> = Box::new(WriteService::new(path, params));
+    let drop_in = WriteDropIn::step(
+        path_without_extension,
+        "service",
+        render_drop_in(params, &[Section::Unit, Section::Service, Section::Install]),
+    );
 
+    let timeout = if already_running {
+        systemd::stop_timeout(&params.extra_directives)
+            + systemd::start_timeout(&params.extra_directives, &params.readiness)
+    } else {
+        systemd::start_timeout(&params.extra_directives, &params.readiness)
+    };
     let enable = Box::new(EnableService {
         name: params.name.clone(),
         mode: params.mode,
         start: true,
         already_running,
+        timeout,
     });
 
-    Ok(vec![create_service, enable])
+    Ok(iter::once(create_service)
+        .chain(drop_in)
+        .chain(iter::once(enable as Box<dyn InstallStep>))
+        .collect())
 }
 
 fn render_service(params: &Params) -> String {
@@ -266,35 +695,155 @@ fn render_service(params: &Params) -> String {
         .map(|user| format!("\nUser={user}"))
         .unwrap_or_default();
     let environment_section = render_environment_section(environment);
+    let environment_file_section = render_environment_file(params.secrets.as_ref());
+    let (start_limit_section, restart_section) = render_restart(&params.restart);
+    let service_type = if params.readiness.notify {
+        "notify"
+    } else {
+        "simple"
+    };
+    let service_settings: String = params
+        .readiness
+        .directives()
+        .into_iter()
+        .chain(params.hardening.directives())
+        .chain(params.limits.directives())
+        .chain(params.credentials.directives())
+        .map(|directive| format!("\n{directive}"))
+        .collect();
 
     let exe_path = exe_path.systemd_escape();
     let exe_args: String = exe_args.iter().map(String::systemd_escape).join(" \\\n\t");
 
-    let target = match params.mode {
-        Mode::User => "default.target",
-        Mode::System => "multi-user.target",
-    };
+    let dependencies_section = render_dependencies(&params.dependencies, "network.target");
+    let target = wanted_by(&params.dependencies, params.mode);
 
+    let extra_unit = inline_directives(params, Section::Unit);
+    let extra_service = inline_directives(params, Section::Service);
     let install_section = match trigger {
-        Trigger::OnSchedule(_) => String::new(), // started by timer
-        Trigger::OnBoot => format!("[Install]\nWantedBy={target}\n"),
+        Trigger::OnSchedule(_) | Trigger::OnBootAndSchedule { .. } => String::new(), // started by timer
+        Trigger::OnConnection(_) => String::new(), // started by socket
+        Trigger::OnPathChange(_) => String::new(), // started by path unit
+        Trigger::OnBoot => {
+            let extra_install = inline_directives(params, Section::Install);
+            format!("[Install]\nWantedBy={target}{extra_install}\n")
+        }
     };
 
     let comment = init::autogenerated_comment(params.bin_name);
     format!(
         "{comment}\n
 [Unit]
-Description={description}
-After=network.target
+Description={description}{dependencies_section}{start_limit_section}{extra_unit}
 
 [Service]
-Type=simple{working_dir_section}{user_section}{environment_section}
-ExecStart={exe_path} {exe_args}
+Type={service_type}{working_dir_section}{user_section}{environment_section}{environment_file_section}
+ExecStart={exe_path} {exe_args}{restart_section}{service_settings}{extra_service}
 {install_section}"
     )
 }
 
-fn render_environment_section(environment: &HashMap<String, String>) -> String {
+/// The extra directives for a section of the units, empty if they go into
+/// drop-ins
+fn inline_directives(params: &Params, section: Section) -> String {
+    if params.extra_directives.drop_in {
+        String::new()
+    } else {
+        params.extra_directives.lines(section)
+    }
+}
+
+/// An `override.conf` drop-in with the extra directives for these sections,
+/// `None` if there are none
+fn render_drop_in(params: &Params, sections: &[Section]) -> Option<String> {
+    let extra = &params.extra_directives;
+    if !extra.drop_in {
+        return None;
+    }
+    let sections: String = sections
+        .iter()
+        .map(|section| (section, extra.lines(*section)))
+        .filter(|(_, lines)| !lines.is_empty())
+        .map(|(section, lines)| format!("\n[{}]{lines}\n", section.name()))
+        .collect();
+    if sections.is_empty() {
+        return None;
+    }
+    let comment = init::autogenerated_comment(params.bin_name);
+    Some(format!("{comment}\n{sections}"))
+}
+
+/// The `[Unit]` lines, `default_after` is used if no units to start after are
+/// set
+pub(super) fn render_dependencies(dependencies: &Dependencies, default_after: &str) -> String {
+    let Dependencies {
+        after,
+        wants,
+        requires,
+        binds_to,
+        requires_mounts_for,
+        ..
+    } = dependencies;
+
+    let after = if after.is_empty() {
+        default_after.to_owned()
+    } else {
+        after.join(" ")
+    };
+    let mut lines = format!("\nAfter={after}");
+    for (key, units) in [
+        ("Wants", wants),
+        ("Requires", requires),
+        ("BindsTo", binds_to),
+    ] {
+        if !units.is_empty() {
+            lines.push_str(&format!("\n{key}={}", units.join(" ")));
+        }
+    }
+    if !requires_mounts_for.is_empty() {
+        let paths = requires_mounts_for
+            .iter()
+            .map(|p| p.systemd_escape())
+            .join(" ");
+        lines.push_str(&format!("\nRequiresMountsFor={paths}"));
+    }
+    lines
+}
+
+pub(super) fn wanted_by(dependencies: &Dependencies, mode: Mode) -> &str {
+    match (&dependencies.wanted_by, mode) {
+        (Some(target), _) => target,
+        (None, Mode::User) => "default.target",
+        (None, Mode::System) => "multi-user.target",
+    }
+}
+
+/// Lines for the `[Unit]` and the `[Service]` section
+pub(super) fn render_restart(restart: &RestartPolicy) -> (String, String) {
+    let start_limit = restart
+        .limit
+        .map(|StartLimit { burst, interval }| {
+            format!(
+                "\nStartLimitBurst={burst}\nStartLimitIntervalSec={}s",
+                interval.as_secs()
+            )
+        })
+        .unwrap_or_default();
+    let when = restart
+        .when
+        .map(|when| match when {
+            Restart::OnFailure => "\nRestart=on-failure",
+            Restart::Always => "\nRestart=always",
+        })
+        .unwrap_or_default();
+    let delay = restart
+        .delay
+        .map(|delay| format!("\nRestartSec={}ms", delay.as_millis()))
+        .unwrap_or_default();
+    (start_limit, format!("{when}{delay}"))
+}
+
+pub(super) fn render_environment_section(environment: &HashMap<String, String>) -> String {
     if environment.is_empty() {
         String::new()
     } else {
@@ -306,36 +855,157 @@ fn render_environment_section(environment: &HashMap<String, String>) -> String {
     }
 }
 
-fn render_timer(params: &Params, schedule: &Schedule) -> String {
+/// `EnvironmentFile=` line with the secret environment variables, if any.
+/// systemd takes the rest of the line as path, it must not be quoted.
+pub(super) fn render_environment_file(secrets: Option<&Secrets>) -> String {
+    secrets
+        .map(|secrets| format!("\nEnvironmentFile={}", secrets.path.display()))
+        .unwrap_or_default()
+}
+
+/// `Persistent=` only works for calendar schedules
+fn catches_up(params: &Params, schedule: &Schedule) -> bool {
+    params.run_if_missed && !matches!(schedule, Schedule::Every(_))
+}
+
+fn on_calendar(date: &str, time: &time::Time) -> String {
+    format!(
+        "OnCalendar={date} {}:{}:{}",
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+fn weekday(day: time::Weekday) -> &'static str {
+    use time::Weekday as W;
+    match day {
+        W::Monday => "Mon",
+        W::Tuesday => "Tue",
+        W::Wednesday => "Wed",
+        W::Thursday => "Thu",
+        W::Friday => "Fri",
+        W::Saturday => "Sat",
+        W::Sunday => "Sun",
+    }
+}
+
+fn render_timer(params: &Params, schedule: &Schedule) -> Result<String, Error> {
     let description = params.description();
     let trigger = match schedule {
-        Schedule::Daily(time) => {
-            format!(
-                "OnCalendar=*-*-* {}:{}:{}",
-                time.hour(),
-                time.minute(),
-                time.second()
-            )
+        Schedule::Daily(time) => on_calendar("*-*-*", time),
+        Schedule::DailyAt(times) => times
+            .iter()
+            .map(|time| on_calendar("*-*-*", time))
+            .join("\n"),
+        Schedule::Hourly { minute } => format!("OnCalendar=*-*-* *:{minute}:0"),
+        Schedule::Weekly { days, time } => {
+            let days = days.iter().copied().map(weekday).join(",");
+            on_calendar(&format!("{days} *-*-*"), time)
+        }
+        Schedule::Monthly { day, time } => on_calendar(&format!("*-*-{day}"), time),
+        Schedule::Every(duration) => format!("OnUnitActiveSec={}s", duration.as_secs()),
+        Schedule::Cron(expr) => {
+            let calendar = Expr::parse_cron(expr)
+                .and_then(|expr| expr.to_calendar())
+                .map_err(Error::ScheduleUnsupported)?;
+            format!("OnCalendar={calendar}")
         }
-        Schedule::Every(duration) => format!(
-            "OnUnitActiveSec={}s\nOnBootSec={}s",
-            duration.as_secs(),
-            duration.as_secs()
-        ),
+        Schedule::Calendar(expr) => format!("OnCalendar={}", expr.trim()),
     };
 
+    let trigger = match &params.timezone {
+        Some(timezone) => trigger
+            .lines()
+            .map(|line| {
+                if line.starts_with("OnCalendar=") {
+                    format!("{line} {timezone}")
+                } else {
+                    line.to_owned()
+                }
+            })
+            .join("\n"),
+        None => trigger,
+    };
+    let on_boot = match (&params.trigger, schedule) {
+        (Trigger::OnBootAndSchedule { boot_delay, .. }, _) => Some(boot_delay.unwrap_or_default()),
+        (_, Schedule::Every(interval)) => Some(*interval),
+        _ => None,
+    };
+    let on_boot = on_boot
+        .map(|delay| format!("\nOnBootSec={}s", delay.as_secs()))
+        .unwrap_or_default();
+
+    let persistent = if catches_up(params, schedule) {
+        "\nPersistent=true"
+    } else {
+        ""
+    };
+    let jitter = match params.jitter {
+        Some(Jitter { max, fixed }) => {
+            let fixed = if fixed { "\nFixedRandomDelay=true" } else { "" };
+            format!("\nRandomizedDelaySec={}{fixed}", max.as_secs())
+        }
+        None => String::new(),
+    };
+
+    let extra_timer = inline_directives(params, Section::Timer);
+    let extra_install = inline_directives(params, Section::Install);
+
     let comment = init::autogenerated_comment(params.bin_name);
-    format!(
+    Ok(format!(
         "{comment}\n
 [Unit]
 Description={description}
 
 [Timer]
-{trigger}
-AccuracySec=60
+{trigger}{on_boot}{persistent}{jitter}
+AccuracySec=60{extra_timer}
+
+[Install]
+WantedBy=timers.target{extra_install}"
+    ))
+}
+
+fn render_socket(params: &Params, listen: &[Listen]) -> String {
+    let description = params.description();
+    let listen = listen.iter().map(Listen::directive).join("\n");
+    let extra_socket = inline_directives(params, Section::Socket);
+    let extra_install = inline_directives(params, Section::Install);
+
+    let comment = init::autogenerated_comment(params.bin_name);
+    format!(
+        "{comment}\n
+[Unit]
+Description={description}
+
+[Socket]
+{listen}{extra_socket}
+
+[Install]
+WantedBy=sockets.target{extra_install}
+"
+    )
+}
+
+fn render_path_unit(params: &Params, watch: &[WatchPath]) -> String {
+    let description = params.description();
+    let watch = watch.iter().map(WatchPath::directive).join("\n");
+    let extra_path = inline_directives(params, Section::Path);
+    let extra_install = inline_directives(params, Section::Install);
+
+    let comment = init::autogenerated_comment(params.bin_name);
+    format!(
+        "{comment}\n
+[Unit]
+Description={description}
+
+[Path]
+{watch}{extra_path}
 
 [Install]
-WantedBy=timers.target"
+WantedBy=paths.target{extra_install}
+"
     )
 }
 
@@ -347,3 +1017,308 @@ fn write_unit(path: &Path, unit: &str) -> Result<(), io::Error> {
     perm.set_mode(0o664);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::install::builder::{ExtraDirectives, NotifyAccess, Readiness};
+    use crate::install::init::secrets::SecretEnvironment;
+    use crate::install::Hardening;
+
+    use super::*;
+
+    #[test]
+    fn missed_daily_runs_are_caught_up() {
+        let schedule = Schedule::Daily(time::Time::from_hms(10, 42, 0).unwrap());
+        let mut params = Params {
+            trigger: Trigger::OnSchedule(schedule.clone()),
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert!(!render_timer(&params, &schedule)
+            .unwrap()
+            .contains("Persistent"));
+
+        params.run_if_missed = true;
+        assert!(render_timer(&params, &schedule)
+            .unwrap()
+            .contains("\nPersistent=true\n"));
+
+        let every = Schedule::Every(std::time::Duration::from_secs(60));
+        assert!(!render_timer(&params, &every)
+            .unwrap()
+            .contains("Persistent"));
+    }
+
+    #[test]
+    fn calendar_schedules() {
+        let time = time::Time::from_hms(10, 42, 0).unwrap();
+        let params = Params {
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let timer = |schedule| render_timer(&params, &schedule).unwrap();
+
+        let days = vec![time::Weekday::Monday, time::Weekday::Friday];
+        assert!(
+            timer(Schedule::Weekly { days, time }).contains("\nOnCalendar=Mon,Fri *-*-* 10:42:0\n")
+        );
+        assert!(timer(Schedule::Monthly { day: 1, time }).contains("\nOnCalendar=*-*-1 10:42:0\n"));
+        assert!(timer(Schedule::Hourly { minute: 5 }).contains("\nOnCalendar=*-*-* *:5:0\n"));
+        let times = vec![time, time::Time::from_hms(18, 0, 0).unwrap()];
+        assert!(timer(Schedule::DailyAt(times))
+            .contains("\nOnCalendar=*-*-* 10:42:0\nOnCalendar=*-*-* 18:0:0\n"));
+    }
+
+    #[test]
+    fn randomized_delay() {
+        let schedule = Schedule::Daily(time::Time::from_hms(3, 0, 0).unwrap());
+        let mut params = Params {
+            trigger: Trigger::OnSchedule(schedule.clone()),
+            jitter: Some(Jitter {
+                max: std::time::Duration::from_secs(1800),
+                fixed: false,
+            }),
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let timer = render_timer(&params, &schedule).unwrap();
+        assert!(timer.contains("\nRandomizedDelaySec=1800\n"));
+        assert!(!timer.contains("FixedRandomDelay"));
+
+        params.jitter = params.jitter.map(|jitter| Jitter {
+            fixed: true,
+            ..jitter
+        });
+        let timer = render_timer(&params, &schedule).unwrap();
+        assert!(timer.contains("\nRandomizedDelaySec=1800\nFixedRandomDelay=true\n"));
+    }
+
+    #[test]
+    fn boot_and_schedule() {
+        let every = Schedule::Every(std::time::Duration::from_secs(3600));
+        let mut params = Params {
+            trigger: Trigger::OnSchedule(every.clone()),
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let timer = |params: &Params, schedule| render_timer(params, schedule).unwrap();
+        assert!(timer(&params, &every).contains("\nOnUnitActiveSec=3600s\nOnBootSec=3600s\n"));
+
+        params.trigger = Trigger::OnBootAndSchedule {
+            schedule: every.clone(),
+            boot_delay: Some(std::time::Duration::from_secs(120)),
+        };
+        assert!(timer(&params, &every).contains("\nOnUnitActiveSec=3600s\nOnBootSec=120s\n"));
+
+        let daily = Schedule::Daily(time::Time::from_hms(10, 42, 0).unwrap());
+        params.trigger = Trigger::OnBootAndSchedule {
+            schedule: daily.clone(),
+            boot_delay: None,
+        };
+        assert!(timer(&params, &daily).contains("\nOnCalendar=*-*-* 10:42:0\nOnBootSec=0s\n"));
+        assert!(!render_service(&params).contains("[Install]"));
+    }
+
+    #[test]
+    fn timezone() {
+        let times = vec![
+            time::Time::from_hms(9, 0, 0).unwrap(),
+            time::Time::from_hms(17, 0, 0).unwrap(),
+        ];
+        let schedule = Schedule::DailyAt(times);
+        let params = Params {
+            trigger: Trigger::OnSchedule(schedule.clone()),
+            timezone: Some("Europe/Amsterdam".to_owned()),
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert!(render_timer(&params, &schedule).unwrap().contains(
+            "\nOnCalendar=*-*-* 9:0:0 Europe/Amsterdam\nOnCalendar=*-*-* 17:0:0 Europe/Amsterdam\n"
+        ));
+    }
+
+    #[test]
+    fn restart_policy() {
+        let mut params = Params {
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        assert!(!render_service(&params).contains("Restart"));
+
+        params.restart = RestartPolicy {
+            when: Some(Restart::OnFailure),
+            delay: Some(std::time::Duration::from_secs(5)),
+            limit: Some(StartLimit {
+                burst: 3,
+                interval: std::time::Duration::from_secs(60),
+            }),
+        };
+        let service = render_service(&params);
+        assert!(service
+            .contains("\nAfter=network.target\nStartLimitBurst=3\nStartLimitIntervalSec=60s\n"));
+        assert!(service.contains("\nRestart=on-failure\nRestartSec=5000ms\n"));
+    }
+
+    #[test]
+    fn hardening() {
+        let params = Params {
+            hardening: Hardening::basic(),
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let step = WriteService::new(
+            PathBuf::from("/etc/systemd/system/weather_checker.service"),
+            &params,
+        );
+        assert!(step.unit.contains(
+            "\nNoNewPrivileges=true\nProtectSystem=full\nProtectHome=read-only\nPrivateTmp=true\n"
+        ));
+        assert!(step
+            .describe(Tense::Future)
+            .ends_with("\n\t| sandbox: NoNewPrivileges, ProtectSystem, ProtectHome, PrivateTmp"));
+        assert!(step
+            .describe_detailed(Tense::Future)
+            .contains("\n| sandbox:\n|\tNoNewPrivileges=true\n|\tProtectSystem=full\n"));
+    }
+
+    #[test]
+    fn dependencies() {
+        let mut params = Params {
+            ..Params::for_test("data_collector", "/usr/bin/data_collector")
+        };
+        let service = render_service(&params);
+        assert!(service.contains("\nAfter=network.target\n"));
+        assert!(service.contains("\nWantedBy=multi-user.target\n"));
+
+        params.dependencies = Dependencies {
+            after: vec![
+                "network-online.target".to_owned(),
+                "postgresql.service".to_owned(),
+            ],
+            wants: vec!["network-online.target".to_owned()],
+            requires: vec!["postgresql.service".to_owned()],
+            binds_to: Vec::new(),
+            requires_mounts_for: vec![PathBuf::from("/srv/data")],
+            wanted_by: Some("graphical.target".to_owned()),
+        };
+        let service = render_service(&params);
+        assert!(service.contains(
+            "\nAfter=network-online.target postgresql.service\nWants=network-online.target\nRequires=postgresql.service\nRequiresMountsFor=\"/srv/data\"\n"
+        ));
+        assert!(service.contains("\nWantedBy=graphical.target\n"));
+    }
+
+    #[test]
+    fn readiness() {
+        let mut params = Params {
+            ..Params::for_test("data_collector", "/usr/bin/data_collector")
+        };
+        let service = render_service(&params);
+        assert!(service.contains("\nType=simple\n"));
+        assert!(!service.contains("Watchdog"));
+
+        params.readiness = Readiness {
+            notify: true,
+            watchdog: Some(Duration::from_secs(30)),
+            access: Some(NotifyAccess::All),
+        };
+        let service = render_service(&params);
+        assert!(service.contains("\nType=notify\n"));
+        assert!(service.contains("\nWatchdogSec=30000ms\nNotifyAccess=all\n"));
+    }
+
+    #[test]
+    fn environment_file() {
+        let mut params = Params {
+            ..Params::for_test("data_collector", "/usr/bin/data_collector")
+        };
+        assert!(!render_service(&params).contains("EnvironmentFile"));
+
+        params.secrets = Some(Secrets {
+            path: PathBuf::from("/etc/data_collector/secrets.env"),
+            vars: SecretEnvironment(HashMap::from([(
+                "API_TOKEN".to_owned(),
+                "hunter2".to_owned(),
+            )])),
+        });
+        let service = render_service(&params);
+        assert!(service.contains("\nEnvironmentFile=/etc/data_collector/secrets.env\n"));
+        assert!(!service.contains("hunter2"));
+    }
+
+    #[test]
+    fn socket() {
+        let listen = vec![
+            Listen::Stream("127.0.0.1:8080".to_owned()),
+            Listen::Datagram("/run/data_collector.sock".to_owned()),
+        ];
+        let params = Params {
+            trigger: Trigger::OnConnection(listen.clone()),
+            ..Params::for_test("data_collector", "/usr/bin/data_collector")
+        };
+        assert!(!render_service(&params).contains("[Install]"));
+
+        let socket = render_socket(&params, &listen);
+        assert!(socket.contains(
+            "\n[Socket]\nListenStream=127.0.0.1:8080\nListenDatagram=/run/data_collector.sock\n"
+        ));
+        assert!(socket.contains("\n[Install]\nWantedBy=sockets.target\n"));
+    }
+
+    #[test]
+    fn path_unit() {
+        let watch = vec![
+            WatchPath::Changed(PathBuf::from("/etc/importer.toml")),
+            WatchPath::DirectoryNotEmpty(PathBuf::from("/var/spool/importer")),
+        ];
+        let params = Params {
+            trigger: Trigger::OnPathChange(watch.clone()),
+            ..Params::for_test("importer", "/usr/bin/importer")
+        };
+        assert!(!render_service(&params).contains("[Install]"));
+
+        let unit = render_path_unit(&params, &watch);
+        assert!(unit.contains(
+            "\n[Path]\nPathChanged=/etc/importer.toml\nDirectoryNotEmpty=/var/spool/importer\n"
+        ));
+        assert!(unit.contains("\n[Install]\nWantedBy=paths.target\n"));
+    }
+
+    #[test]
+    fn extra_directives() {
+        let mut params = Params {
+            extra_directives: ExtraDirectives {
+                directives: vec![
+                    (
+                        Section::Service,
+                        "OOMScoreAdjust".to_owned(),
+                        "500".to_owned(),
+                    ),
+                    (
+                        Section::Install,
+                        "Alias".to_owned(),
+                        "collector.service".to_owned(),
+                    ),
+                ],
+                drop_in: false,
+            },
+            ..Params::for_test("data_collector", "/usr/bin/data_collector")
+        };
+        let service = render_service(&params);
+        assert!(service.contains("\nOOMScoreAdjust=500\n[Install]\nWantedBy=multi-user.target\nAlias=collector.service\n"));
+        assert_eq!(render_drop_in(&params, &[Section::Service]), None);
+
+        params.extra_directives.drop_in = true;
+        assert!(!render_service(&params).contains("OOMScoreAdjust"));
+        let drop_in = render_drop_in(
+            &params,
+            &[Section::Unit, Section::Service, Section::Install],
+        )
+        .unwrap();
+        assert!(drop_in
+            .ends_with("\n[Service]\nOOMScoreAdjust=500\n\n[Install]\nAlias=collector.service\n"));
+        assert_eq!(render_drop_in(&params, &[Section::Unit]), None);
+    }
+}
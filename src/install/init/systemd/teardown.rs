@@ -35,6 +35,32 @@ impl RemoveStep for RemoveService {
     }
 }
 
+pub(crate) struct RemoveDropIn {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveDropIn {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} systemd drop-in{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        // fails if someone else added their own drop-ins, those stay
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct DisableService {
     pub(crate) name: String,
     pub(crate) mode: Mode,
@@ -126,6 +152,108 @@ impl RemoveStep for DisableTimer {
     }
 }
 
+pub(crate) struct RemoveSocket {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveSocket {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} systemd socket{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DisableSocket {
+    pub(crate) name: String,
+    pub(crate) mode: Mode,
+}
+
+impl RemoveStep for DisableSocket {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Disabled and stopped",
+            Tense::Questioning => "Disable and stop",
+            Tense::Future => "Will disable and stop",
+            Tense::Active => "Disabling and stopping",
+        };
+        format!(
+            "{verb} systemd {} socket: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        let name = self.name.clone() + ".socket";
+        on_seperate_tokio_thread! {{
+            disable(name.as_ref(), self.mode, true).await.map_err(RemoveError::Systemd)
+        }}
+    }
+}
+
+pub(crate) struct RemovePathUnit {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemovePathUnit {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} systemd path unit{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DisablePathUnit {
+    pub(crate) name: String,
+    pub(crate) mode: Mode,
+}
+
+impl RemoveStep for DisablePathUnit {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Disabled",
+            Tense::Questioning => "Disable",
+            Tense::Future => "Will disable",
+            Tense::Active => "Disabling",
+        };
+        format!(
+            "{verb} systemd {} path unit: {}{}",
+            self.mode,
+            self.name,
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        let name = self.name.clone() + ".path";
+        on_seperate_tokio_thread! {{
+            disable(name.as_ref(), self.mode, true).await.map_err(RemoveError::Systemd)
+        }}
+    }
+}
+
 pub(crate) fn disable_then_remove_service(service_path: PathBuf, name: &str, mode: Mode) -> RSteps {
     vec![
         Box::new(DisableService {
@@ -150,3 +278,29 @@ pub(crate) fn disable_then_remove_with_timer(
         Box::new(RemoveTimer { path: timer_path }),
     ]
 }
+
+pub(crate) fn disable_then_remove_with_path_unit(
+    path_unit_path: PathBuf,
+    name: &str,
+    mode: Mode,
+) -> RSteps {
+    vec![
+        Box::new(DisablePathUnit {
+            name: name.to_owned(),
+            mode,
+        }),
+        Box::new(RemovePathUnit {
+            path: path_unit_path,
+        }),
+    ]
+}
+
+pub(crate) fn disable_then_remove_socket(socket_path: PathBuf, name: &str, mode: Mode) -> RSteps {
+    vec![
+        Box::new(DisableSocket {
+            name: name.to_owned(),
+            mode,
+        }),
+        Box::new(RemoveSocket { path: socket_path }),
+    ]
+}
@@ -74,6 +74,16 @@ impl Unit {
         }
     }
 
+    /// The files of all `EnvironmentFile=` lines, besides our secrets file
+    /// these can come from directives
+    pub(crate) fn environment_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.body
+            .lines()
+            .map(str::trim)
+            .filter_map(|l| l.strip_prefix("EnvironmentFile="))
+            .map(PathBuf::from)
+    }
+
     pub(crate) fn our_service(&self) -> bool {
         self.body.contains(COMMENT_PREAMBLE) && self.body.contains(COMMENT_SUFFIX)
     }
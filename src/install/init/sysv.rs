@@ -0,0 +1,218 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{fs, io};
+
+use super::script::{self, Script};
+use super::{ExeLocation, Mode, Params, RSteps, SetupError, Steps, TearDownError};
+
+mod disable_existing;
+mod setup;
+mod teardown;
+
+pub(crate) use disable_existing::disable_step;
+pub use disable_existing::DisableError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("SysV init can not start services on a schedule, use cron for that")]
+    ScheduleUnsupported,
+    #[error("SysV init can not restart a service that exits")]
+    RestartUnsupported,
+    #[error("SysV init scripts can only be installed system wide")]
+    UserModeUnsupported,
+    #[error("Neither `update-rc.d` nor `chkconfig` is available to register the init script")]
+    NoRegistrationTool,
+    #[error("Could not write out init script to {path}")]
+    Writing {
+        #[source]
+        e: io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not make the init script executable")]
+    SettingPermissions(#[source] io::Error),
+    #[error("Could not remove the init script, error: {0}")]
+    Removing(#[source] io::Error),
+    #[error("Could not read the init script directory")]
+    ReadingDir(#[source] io::Error),
+    #[error("Could not verify init script was created by us, could not open it")]
+    Verifying(
+        #[from]
+        #[source]
+        script::Error,
+    ),
+    #[error("Could not run `{cmd}`")]
+    CouldNotRun {
+        #[source]
+        e: io::Error,
+        cmd: String,
+    },
+    #[error("Command `{cmd}` failed, stderr: \"{stderr}\"")]
+    CommandFailed { cmd: String, stderr: String },
+}
+
+/// The tool used to add the init script to the runlevels
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Registrar {
+    /// Debian and derivatives (Devuan, Ubuntu)
+    UpdateRcD,
+    /// Red Hat and derivatives
+    ChkConfig,
+}
+
+impl Registrar {
+    fn detect() -> Option<Self> {
+        if find_program("update-rc.d").is_some() {
+            Some(Self::UpdateRcD)
+        } else if find_program("chkconfig").is_some() {
+            Some(Self::ChkConfig)
+        } else {
+            None
+        }
+    }
+
+    fn register(self, name: &str) -> Result<(), Error> {
+        match self {
+            Registrar::UpdateRcD => run(Command::new("update-rc.d").args([name, "defaults"])),
+            Registrar::ChkConfig => run(Command::new("chkconfig").args(["--add", name])),
+        }
+    }
+
+    fn unregister(self, name: &str) -> Result<(), Error> {
+        match self {
+            Registrar::UpdateRcD => run(Command::new("update-rc.d").args(["-f", name, "remove"])),
+            Registrar::ChkConfig => run(Command::new("chkconfig").args(["--del", name])),
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Registrar::UpdateRcD => "update-rc.d",
+            Registrar::ChkConfig => "chkconfig",
+        }
+    }
+}
+
+/// Services started by a SysV init script are daemonized and end up
+/// reparented to init. So does every other orphaned or daemonized process,
+/// only claim `target` if one of the init scripts starts it.
+pub(crate) fn is_init_path(path: &Path, target: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "init")
+        && disable_existing::scripts_running(&init_dir(), target)
+            .is_ok_and(|scripts| !scripts.is_empty())
+}
+
+pub(super) fn not_available() -> bool {
+    !init_dir().is_dir() || Registrar::detect().is_none()
+}
+
+pub(super) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported.into());
+    }
+    if params.restart.when.is_some() {
+        return Err(Error::RestartUnsupported.into());
+    }
+    if params.mode.is_user() {
+        return Err(Error::UserModeUnsupported.into());
+    }
+    let registrar = Registrar::detect().ok_or(Error::NoRegistrationTool)?;
+
+    let path = init_dir().join(&params.name);
+    Ok(setup::steps(path, registrar, params)?)
+}
+
+pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    if mode.is_user() || !init_dir().is_dir() {
+        return Ok(None);
+    }
+
+    let mut steps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for script in our_scripts()? {
+        let Some(name) = script.name() else {
+            continue;
+        };
+        let registrar = Registrar::detect().ok_or(Error::NoRegistrationTool)?;
+        steps.extend(teardown::disable_then_remove(
+            script.path.clone(),
+            name,
+            registrar,
+        ));
+        exe_paths.push(
+            script
+                .exe_path(EXE_VAR)
+                .map_err(TearDownError::FindingScriptExePath)?,
+        );
+    }
+
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+fn our_scripts() -> Result<Vec<Script>, Error> {
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(init_dir()).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let script = Script::from_path(path)?;
+        if script.our_script() && script.interpreter() == Some(INTERPRETER) {
+            scripts.push(script);
+        }
+    }
+    Ok(scripts)
+}
+
+fn init_dir() -> PathBuf {
+    PathBuf::from("/etc/init.d")
+}
+
+const INTERPRETER: &str = "/bin/sh";
+/// Variable in the init script holding the path to the executable
+const EXE_VAR: &str = "EXE";
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        // root's PATH is not always set when running through sudo
+        .chain(["/usr/sbin", "/sbin"].map(PathBuf::from))
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn run(command: &mut Command) -> Result<(), Error> {
+    let cmd = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(OsStr::to_string_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let output = command.output().map_err(|e| Error::CouldNotRun {
+        e,
+        cmd: cmd.clone(),
+    })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::CommandFailed { cmd, stderr })
+    }
+}
+
+fn service(script: &Path, action: &str) -> Result<(), Error> {
+    run(Command::new(script).arg(action))
+}
+
+fn is_running(script: &Path) -> bool {
+    // LSB: status exits with 0 only if the program is running
+    Command::new(script)
+        .arg("status")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
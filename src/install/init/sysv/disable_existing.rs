@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::install::{InstallError, InstallStep, RollbackError, RollbackStep};
+use crate::Tense;
+
+use super::script::Script;
+use super::{init_dir, EXE_VAR};
+
+struct Restart {
+    scripts: Vec<PathBuf>,
+}
+
+impl RollbackStep for Restart {
+    fn perform(&mut self) -> Result<(), RollbackError> {
+        for script in &self.scripts {
+            super::service(script, "start").map_err(RollbackError::ReEnablingSysV)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Restarted",
+            Tense::Active => "Restarting",
+            Tense::Questioning => "Restart",
+            Tense::Future => "Will restart",
+        };
+        format!(
+            "{verb} the SysV services that spawned the original file{}",
+            tense.punct()
+        )
+    }
+}
+
+struct Stop {
+    scripts: Vec<PathBuf>,
+}
+
+impl InstallStep for Stop {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped",
+            Tense::Active => "Stopping",
+            Tense::Questioning => "Stop",
+            Tense::Future => "Will stop",
+        };
+        format!(
+            "{verb} the SysV services running the file at the install location{}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        #[allow(clippy::format_collect)]
+        let scripts: String = self
+            .scripts
+            .iter()
+            .map(|script| format!("\n|\t- {}", script.display()))
+            .collect();
+        format!("{}\n| init scripts:{scripts}", self.describe(tense))
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        let mut rollback = Box::new(Restart {
+            scripts: Vec::new(),
+        });
+        for script in &self.scripts {
+            super::service(script, "stop")?;
+            rollback.scripts.push(script.clone());
+        }
+        Ok(Some(rollback as Box<dyn RollbackStep>))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisableError {
+    #[error("Could not read the init script directory")]
+    CouldNotReadDir(#[source] std::io::Error),
+    #[error("Could not find the init script that keeps the file in use")]
+    NoServiceFound,
+}
+
+/// The init scripts in `dir` that start `target`
+pub(super) fn scripts_running(dir: &Path, target: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        // not every file in the init dir has to be a readable script
+        let Ok(script) = Script::from_path(path) else {
+            continue;
+        };
+        if script.runs(EXE_VAR, target) {
+            scripts.push(script.path);
+        }
+    }
+    Ok(scripts)
+}
+
+pub(crate) fn disable_step(target: &Path) -> Result<Vec<Box<dyn InstallStep>>, DisableError> {
+    let mut scripts =
+        scripts_running(&init_dir(), target).map_err(DisableError::CouldNotReadDir)?;
+    if scripts.is_empty() {
+        return Err(DisableError::NoServiceFound);
+    }
+    scripts.sort();
+    Ok(vec![Box::new(Stop { scripts })])
+}
@@ -0,0 +1,283 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::install::init::{autogenerated_comment, Params, ShellEscape, Steps};
+use crate::install::{InstallError, InstallStep, RollbackStep, Tense};
+
+use super::{teardown, Error, Registrar, EXE_VAR, INTERPRETER};
+
+struct WriteScript {
+    script: String,
+    path: PathBuf,
+}
+
+impl InstallStep for WriteScript {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!("{verb} SysV init script{}\n\t| path: {path}", tense.punct())
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.script.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} SysV init script{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_script(&self.path, &self.script)?;
+        Ok(Some(Box::new(teardown::RemoveScript {
+            path: self.path.clone(),
+        })))
+    }
+}
+
+struct EnableService {
+    name: String,
+    path: PathBuf,
+    registrar: Registrar,
+    already_running: bool,
+}
+
+impl InstallStep for EnableService {
+    fn describe(&self, tense: Tense) -> String {
+        let enable = match tense {
+            Tense::Past => "Registered",
+            Tense::Questioning => "Register",
+            Tense::Future => "Will register",
+            Tense::Active => "Registering",
+        };
+        let start = match (&tense, self.already_running) {
+            (Tense::Past, true) => "restarted",
+            (Tense::Past, false) => "started",
+            (Tense::Questioning | Tense::Future, true) => "restart",
+            (Tense::Questioning | Tense::Future, false) => "start",
+            (Tense::Active, true) => "restarting",
+            (Tense::Active, false) => "starting",
+        };
+        format!(
+            "{enable} SysV service: {} using {} and {start} it{}",
+            self.name,
+            self.registrar.program(),
+            tense.punct(),
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        self.registrar.register(&self.name)?;
+        let rollback = Box::new(teardown::DisableService {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            registrar: self.registrar,
+        });
+        if self.already_running {
+            super::service(&self.path, "restart")?;
+        } else {
+            super::service(&self.path, "start")?;
+        }
+        Ok(Some(rollback))
+    }
+}
+
+pub(super) fn steps(path: PathBuf, registrar: Registrar, params: &Params) -> Result<Steps, Error> {
+    let script = render_script(params);
+    let already_running = path.is_file() && super::is_running(&path);
+
+    Ok(vec![
+        Box::new(WriteScript {
+            script,
+            path: path.clone(),
+        }),
+        Box::new(EnableService {
+            name: params.name.clone(),
+            path,
+            registrar,
+            already_running,
+        }),
+    ])
+}
+
+fn render_script(params: &Params) -> String {
+    let Params {
+        name,
+        exe_path,
+        exe_args,
+        environment,
+        working_dir,
+        run_as,
+        ..
+    } = params;
+
+    // header fields must fit on a single line
+    let description = params.description().replace('\n', " ");
+    let exe = exe_path.shell_escaped();
+    let args: String = exe_args
+        .iter()
+        .map(String::shell_escaped)
+        .map(|arg| format!(" {arg}"))
+        .collect();
+    let cd_line = working_dir
+        .as_ref()
+        .map(|d| format!("\n\t\tcd {} || exit 1", d.shell_escaped()))
+        .unwrap_or_default();
+    #[allow(clippy::format_collect)]
+    let export_lines: String = environment
+        .iter()
+        .map(|(key, val)| {
+            format!(
+                "\n\t\texport {}={}",
+                key.shell_escaped(),
+                val.shell_escaped()
+            )
+        })
+        .collect();
+    let limits: String = params
+        .limits
+        .wrapper()
+        .into_iter()
+        .map(|word| format!("{word} "))
+        .collect();
+    let exec_line = match run_as {
+        Some(user) => {
+            let command = format!("exec {exe}{args}").shell_escaped();
+            format!(
+                "exec {limits}setsid su -s /bin/sh {} -c {command}",
+                user.shell_escaped()
+            )
+        }
+        None => format!("exec {limits}setsid \"${EXE_VAR}\"{args}"),
+    };
+
+    let comment = autogenerated_comment(params.bin_name);
+    format!(
+        "#!{INTERPRETER}
+{comment}
+
+### BEGIN INIT INFO
+# Provides:          {name}
+# Required-Start:    $remote_fs $network $syslog
+# Required-Stop:     $remote_fs $network $syslog
+# Default-Start:     2 3 4 5
+# Default-Stop:      0 1 6
+# Short-Description: {description}
+### END INIT INFO
+# chkconfig: 2345 95 05
+# description: {description}
+
+NAME={name}
+{EXE_VAR}={exe}
+PIDFILE=/var/run/$NAME.pid
+
+is_running() {{
+\t[ -f \"$PIDFILE\" ] && kill -0 \"$(cat \"$PIDFILE\")\" 2>/dev/null
+}}
+
+start() {{
+\tis_running && return 0
+\t({cd_line}{export_lines}
+\t\t{exec_line}
+\t) </dev/null >/dev/null 2>&1 &
+\techo $! >\"$PIDFILE\"
+}}
+
+stop() {{
+\tif is_running; then
+\t\tkill \"$(cat \"$PIDFILE\")\"
+\t\ti=0
+\t\twhile is_running && [ $i -lt 10 ]; do
+\t\t\tsleep 1
+\t\t\ti=$((i + 1))
+\t\tdone
+\tfi
+\trm -f \"$PIDFILE\"
+}}
+
+case \"$1\" in
+\tstart) start ;;
+\tstop) stop ;;
+\trestart|force-reload) stop && start ;;
+\tstatus)
+\t\tif is_running; then
+\t\t\techo \"$NAME is running\"
+\t\telse
+\t\t\techo \"$NAME is not running\"
+\t\t\texit 3
+\t\tfi
+\t\t;;
+\t*)
+\t\techo \"Usage: $0 {{start|stop|restart|force-reload|status}}\" >&2
+\t\texit 2
+\t\t;;
+esac
+",
+    )
+}
+
+fn write_script(path: &Path, script: &str) -> Result<(), Error> {
+    let mut f = fs::File::create(path).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })?;
+    f.write_all(script.as_bytes()).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(Error::SettingPermissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install::init::sysv::disable_existing::scripts_running;
+
+    use super::*;
+
+    #[test]
+    fn su_command_is_quoted_twice() {
+        let params = Params {
+            exe_args: vec!["--greeting".to_owned(), "it's sunny".to_owned()],
+            run_as: Some("weather".to_owned()),
+            ..Params::for_test("weather_checker", "/usr/bin/weather checker")
+        };
+        let script = render_script(&params);
+        assert!(script.starts_with(&format!("#!{INTERPRETER}\n")));
+        let exec_line = script
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("exec "))
+            .unwrap();
+        // su hands the command to a second shell
+        assert_eq!(
+            exec_line,
+            r"exec setsid su -s /bin/sh weather -c 'exec '\''/usr/bin/weather checker'\'' --greeting '\''it'\''\'\'''\''s sunny'\'''"
+        );
+    }
+
+    #[test]
+    fn only_scripts_starting_the_target() {
+        let params = Params::for_test("weather_checker", "/usr/bin/weather checker");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("weather_checker");
+        write_script(&path, &render_script(&params)).unwrap();
+        fs::write(dir.path().join("README"), "not a script").unwrap();
+
+        let running = |target| scripts_running(dir.path(), Path::new(target)).unwrap();
+        assert_eq!(running("/usr/bin/weather checker"), [path]);
+        assert!(running("/usr/bin/orphaned_daemon").is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install::init::RSteps;
+use crate::install::RemoveError;
+use crate::install::RemoveStep;
+use crate::install::Tense;
+
+use super::{Error, Registrar};
+
+pub(crate) struct RemoveScript {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveScript {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} SysV init script{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct DisableService {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) registrar: Registrar,
+}
+
+impl RemoveStep for DisableService {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Stopped and unregistered",
+            Tense::Questioning => "Stop and unregister",
+            Tense::Future => "Will stop and unregister",
+            Tense::Active => "Stopping and unregistering",
+        };
+        format!(
+            "{verb} SysV service: {} using {}{}",
+            self.name,
+            self.registrar.program(),
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        if self.path.is_file() {
+            super::service(&self.path, "stop")?;
+        }
+        self.registrar.unregister(&self.name)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn disable_then_remove(
+    script_path: PathBuf,
+    name: &str,
+    registrar: Registrar,
+) -> RSteps {
+    vec![
+        Box::new(DisableService {
+            name: name.to_owned(),
+            path: script_path.clone(),
+            registrar,
+        }),
+        Box::new(RemoveScript { path: script_path }),
+    ]
+}
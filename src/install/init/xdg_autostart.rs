@@ -0,0 +1,290 @@
+//! Backend writing an XDG autostart desktop entry. The desktop session starts
+//! these programs on login. Useful for tray apps and agents that need to run
+//! inside the graphical session.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::install::files::NoHomeError;
+
+use super::{
+    ExeLocation, Mode, Params, RSteps, SetupError, Steps, TearDownError, COMMENT_PREAMBLE,
+    COMMENT_SUFFIX,
+};
+
+mod setup;
+mod teardown;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("XDG autostart can not start programs on a schedule, use cron for that")]
+    ScheduleUnsupported,
+    #[error("XDG autostart can not restart a program that exits")]
+    RestartUnsupported,
+    #[error("XDG autostart entries are only installed for the current user")]
+    SystemModeUnsupported,
+    #[error("Could not write out desktop entry to {path}")]
+    Writing {
+        #[source]
+        e: io::Error,
+        path: PathBuf,
+    },
+    #[error("Could not remove the desktop entry, error: {0}")]
+    Removing(#[source] io::Error),
+    #[error("Could not read the autostart directory")]
+    ReadingDir(#[source] io::Error),
+    #[error("Could not verify desktop entry was created by us, could not open it")]
+    Verifying(#[source] io::Error),
+}
+
+/// The executables location could not be found. It is needed to safely
+/// uninstall.
+#[derive(Debug, thiserror::Error)]
+pub enum FindExeError {
+    #[error("There is no `Exec` key in the desktop entry at: {0}")]
+    NoExec(PathBuf),
+    #[error("Path to binary extracted from desktop entry does not lead to a file, path: {0}")]
+    ExecPathNotFile(PathBuf),
+}
+
+/// Autostarted programs are children of the session manager which does not
+/// restart them. The generic handling for processes without an init suffices
+/// when replacing a running install.
+pub(crate) fn is_init_path(_: &Path) -> bool {
+    false
+}
+
+/// Without a graphical session there is nothing to run the autostart entries
+pub(super) fn not_available() -> bool {
+    let in_session = [
+        "XDG_CURRENT_DESKTOP",
+        "DESKTOP_SESSION",
+        "WAYLAND_DISPLAY",
+        "DISPLAY",
+    ]
+    .into_iter()
+    .any(|var| std::env::var_os(var).is_some_and(|val| !val.is_empty()));
+    !in_session
+}
+
+pub(super) fn set_up_steps(params: &Params) -> Result<Steps, SetupError> {
+    if params.trigger.schedule().is_some() {
+        return Err(Error::ScheduleUnsupported.into());
+    }
+    if params.restart.when.is_some() {
+        return Err(Error::RestartUnsupported.into());
+    }
+    if let Mode::System = params.mode {
+        return Err(Error::SystemModeUnsupported.into());
+    }
+
+    let path = autostart_dir()?.join(format!("{}.desktop", params.name));
+    Ok(setup::steps(path, params))
+}
+
+pub(super) fn tear_down_steps(mode: Mode) -> Result<Option<(RSteps, ExeLocation)>, TearDownError> {
+    if let Mode::System = mode {
+        return Ok(None);
+    }
+
+    let mut steps = Vec::new();
+    let mut exe_paths = Vec::new();
+    for entry in our_entries(&autostart_dir()?)? {
+        exe_paths.push(entry.exe_path()?);
+        steps.push(Box::new(teardown::RemoveEntry { path: entry.path }) as _);
+    }
+
+    exe_paths.dedup();
+    match exe_paths.as_slice() {
+        [] => Ok(None),
+        [exe_path] => Ok(Some((steps, exe_path.clone()))),
+        _ => Err(TearDownError::MultipleExePaths(exe_paths)),
+    }
+}
+
+/// A desktop entry in the autostart directory
+pub(crate) struct Entry {
+    body: String,
+    path: PathBuf,
+}
+
+impl Entry {
+    fn from_path(path: PathBuf) -> Result<Self, io::Error> {
+        Ok(Self {
+            body: fs::read_to_string(&path)?,
+            path,
+        })
+    }
+
+    fn our_entry(&self) -> bool {
+        self.body.contains(COMMENT_PREAMBLE) && self.body.contains(COMMENT_SUFFIX)
+    }
+
+    /// The executable started by the `Exec` key, skipping the `env` wrapper
+    /// used to set the environment.
+    fn exe(&self) -> Option<PathBuf> {
+        let exec = self
+            .body
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "Exec")
+            .map(|(_, value)| value.trim())?;
+        // our exe path is always absolute while `env`, variable names and the
+        // resource limit wrappers in front of it can not start with a `/`
+        split_exec(&unescape_value(exec))
+            .into_iter()
+            .find(|arg| arg.starts_with('/'))
+            .map(PathBuf::from)
+    }
+
+    fn exe_path(&self) -> Result<PathBuf, FindExeError> {
+        let exe_path = self
+            .exe()
+            .ok_or_else(|| FindExeError::NoExec(self.path.clone()))?;
+        if exe_path.is_file() {
+            Ok(exe_path)
+        } else {
+            Err(FindExeError::ExecPathNotFile(exe_path))
+        }
+    }
+}
+
+fn our_entries(dir: &Path) -> Result<Vec<Entry>, Error> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::ReadingDir)? {
+        let path = entry.map_err(Error::ReadingDir)?.path();
+        if !path.is_file() || path.extension() != Some(OsStr::new("desktop")) {
+            continue;
+        }
+        let entry = Entry::from_path(path).map_err(Error::Verifying)?;
+        if entry.our_entry() {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn autostart_dir() -> Result<PathBuf, NoHomeError> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute());
+    let config = match config {
+        Some(dir) => dir,
+        None => home::home_dir().ok_or(NoHomeError)?.join(".config"),
+    };
+    Ok(config.join("autostart"))
+}
+
+/// Characters that force an argument in an `Exec` key to be quoted
+const RESERVED: &[char] = &[
+    ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')',
+    '`',
+];
+
+/// Quote an argument following the desktop entry spec's rules for `Exec`
+fn exec_quoted(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return arg;
+    }
+
+    let mut res = String::with_capacity(arg.len() + 2);
+    res.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    res
+}
+
+/// Escape a value of type string, this is applied after quoting
+fn escape_value(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+fn unescape_value(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => res.push(' '),
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some(c) => res.push(c),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+/// Split an (unescaped) `Exec` value into its arguments
+fn split_exec(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = None::<String>;
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                arg.get_or_insert_with(String::new);
+            }
+            '\\' if in_quotes => arg.get_or_insert_with(String::new).extend(chars.next()),
+            '%' => {
+                // `%%` is a literal `%`, other field codes are not expanded
+                if let Some(next) = chars.next() {
+                    if next == '%' {
+                        arg.get_or_insert_with(String::new).push('%');
+                    }
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_args_round_trip() {
+        let args = [
+            "/usr/bin/weather checker",
+            "--location=North Holland",
+            "100%",
+            "quote\" and \\ backslash",
+            "$HOME",
+            "",
+        ];
+        let exec = args.map(exec_quoted).join(" ");
+        let exec = escape_value(&exec);
+        assert_eq!(split_exec(&unescape_value(&exec)), args);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::install::init::{autogenerated_comment, Params, Steps};
+use crate::install::{InstallError, InstallStep, RollbackStep, Tense};
+
+use super::{escape_value, exec_quoted, teardown, Error};
+
+struct WriteEntry {
+    entry: String,
+    path: PathBuf,
+}
+
+impl InstallStep for WriteEntry {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        format!(
+            "{verb} XDG autostart entry, it will start on next login{}\n\t| path: {path}",
+            tense.punct()
+        )
+    }
+
+    fn describe_detailed(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Wrote",
+            Tense::Questioning => "Write",
+            Tense::Future => "Will write",
+            Tense::Active => "Writing",
+        };
+        let path = self.path.display();
+        let content = self.entry.trim_end().replace('\n', "\n|\t");
+        format!(
+            "{verb} XDG autostart entry, it will start on next login{}\n| path:\n|\t{path}\n| content:\n|\t{content}",
+            tense.punct()
+        )
+    }
+
+    fn perform(&mut self) -> Result<Option<Box<dyn RollbackStep>>, InstallError> {
+        write_entry(&self.path, &self.entry)?;
+        Ok(Some(Box::new(teardown::RemoveEntry {
+            path: self.path.clone(),
+        })))
+    }
+}
+
+pub(super) fn steps(path: PathBuf, params: &Params) -> Steps {
+    let entry = render_entry(params);
+    vec![Box::new(WriteEntry { entry, path })]
+}
+
+fn render_entry(params: &Params) -> String {
+    let Params {
+        name,
+        exe_path,
+        exe_args,
+        environment,
+        working_dir,
+        ..
+    } = params;
+
+    let mut vars: Vec<_> = environment.iter().collect();
+    vars.sort();
+    let env = vars
+        .into_iter()
+        .map(|(key, val)| format!("{key}={val}"))
+        .collect::<Vec<_>>();
+    let env_prefix = if env.is_empty() {
+        Vec::new()
+    } else {
+        std::iter::once("env".to_owned()).chain(env).collect()
+    };
+    let exec = env_prefix
+        .into_iter()
+        .chain(params.limits.wrapper())
+        .chain(std::iter::once(exe_path.display().to_string()))
+        .chain(exe_args.iter().cloned())
+        .map(|arg| exec_quoted(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let exec = escape_value(&exec);
+    let path_line = working_dir
+        .as_ref()
+        .map(|dir| format!("\nPath={}", escape_value(&dir.display().to_string())))
+        .unwrap_or_default();
+    let name = escape_value(name);
+    let description = escape_value(&params.description());
+
+    let comment = autogenerated_comment(params.bin_name);
+    format!(
+        "{comment}
+[Desktop Entry]
+Type=Application
+Name={name}
+Comment={description}
+Exec={exec}{path_line}
+Terminal=false
+NoDisplay=true
+X-GNOME-Autostart-enabled=true
+"
+    )
+}
+
+fn write_entry(path: &Path, entry: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::Writing {
+            e,
+            path: path.to_owned(),
+        })?;
+    }
+    fs::write(path, entry).map_err(|e| Error::Writing {
+        e,
+        path: path.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::install::Mode;
+
+    use super::*;
+
+    #[test]
+    fn exec_escapes_environment() {
+        let params = Params {
+            exe_args: vec!["--format".to_owned(), "%d".to_owned()],
+            environment: HashMap::from([
+                ("GREETING".to_owned(), "hi $USER".to_owned()),
+                ("WEATHER_DIR".to_owned(), "C:\\weather".to_owned()),
+            ]),
+            mode: Mode::User,
+            ..Params::for_test("weather_checker", "/usr/bin/weather_checker")
+        };
+        let entry = render_entry(&params);
+        let exec = entry.lines().find_map(|line| line.strip_prefix("Exec="));
+        // quoted for Exec, then escaped as a string value
+        assert_eq!(
+            exec,
+            Some(
+                r#"env "GREETING=hi \\$USER" "WEATHER_DIR=C:\\\\weather" /usr/bin/weather_checker --format %%d"#
+            )
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install::RemoveError;
+use crate::install::RemoveStep;
+use crate::install::Tense;
+
+use super::Error;
+
+pub(crate) struct RemoveEntry {
+    pub(crate) path: PathBuf,
+}
+
+impl RemoveStep for RemoveEntry {
+    fn describe(&self, tense: Tense) -> String {
+        let verb = match tense {
+            Tense::Past => "Removed",
+            Tense::Questioning => "Remove",
+            Tense::Future => "Will remove",
+            Tense::Active => "Removing",
+        };
+        let path = self.path.display();
+        format!("{verb} XDG autostart entry{} at:\n|\t{path}", tense.punct())
+    }
+
+    fn perform(&mut self) -> Result<(), RemoveError> {
+        fs::remove_file(&self.path).map_err(Error::Removing)?;
+        Ok(())
+    }
+}
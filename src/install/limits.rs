@@ -0,0 +1,140 @@
+/// The IO scheduling class of the service, set using
+/// [`Spec::io_scheduling_class`](super::Spec::io_scheduling_class)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoSchedulingClass {
+    /// Gets the disk first, needs root
+    Realtime,
+    /// The default, shares the disk with other processes
+    BestEffort,
+    /// Only gets the disk when no other process needs it
+    Idle,
+}
+
+/// Resource limits for the service, by default there are none
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Limits {
+    /// In bytes
+    pub(crate) memory_max: Option<u64>,
+    /// In percent of a single CPU
+    pub(crate) cpu_quota: Option<u32>,
+    pub(crate) tasks_max: Option<u32>,
+    pub(crate) nice: Option<i8>,
+    pub(crate) io_scheduling_class: Option<IoSchedulingClass>,
+    pub(crate) open_files: Option<u64>,
+}
+
+impl Limits {
+    /// The name of a limit that needs a cgroup, only systemd can apply those
+    pub(crate) fn needs_cgroup(&self) -> Option<&'static str> {
+        if self.cpu_quota.is_some() {
+            Some("CPUQuota")
+        } else if self.tasks_max.is_some() {
+            Some("TasksMax")
+        } else {
+            None
+        }
+    }
+
+    /// The systemd `[Service]` settings, one per line
+    pub(crate) fn directives(&self) -> Vec<String> {
+        let mut directives = Vec::new();
+        if let Some(bytes) = self.memory_max {
+            directives.push(format!("MemoryMax={bytes}"));
+        }
+        if let Some(percent) = self.cpu_quota {
+            directives.push(format!("CPUQuota={percent}%"));
+        }
+        if let Some(tasks) = self.tasks_max {
+            directives.push(format!("TasksMax={tasks}"));
+        }
+        if let Some(nice) = self.nice {
+            directives.push(format!("Nice={nice}"));
+        }
+        if let Some(class) = self.io_scheduling_class {
+            let class = match class {
+                IoSchedulingClass::Realtime => "realtime",
+                IoSchedulingClass::BestEffort => "best-effort",
+                IoSchedulingClass::Idle => "idle",
+            };
+            directives.push(format!("IOSchedulingClass={class}"));
+        }
+        if let Some(files) = self.open_files {
+            directives.push(format!("LimitNOFILE={files}"));
+        }
+        directives
+    }
+
+    /// Commands that apply the limits before running the rest of the command
+    /// line, for init systems and cron that can not set them themselves. The
+    /// memory limit becomes a limit on the address space which is larger than
+    /// the memory actually used.
+    ///
+    /// None of the words start with a `/`, the first one that does is the
+    /// executable.
+    pub(crate) fn wrapper(&self) -> Vec<String> {
+        let mut words = Vec::new();
+        if let Some(nice) = self.nice {
+            words.extend(["nice".to_owned(), "-n".to_owned(), nice.to_string()]);
+        }
+        if let Some(class) = self.io_scheduling_class {
+            words.extend([
+                "ionice".to_owned(),
+                "-c".to_owned(),
+                class.number().to_string(),
+            ]);
+        }
+        let rlimits: Vec<_> = [
+            self.open_files.map(|files| format!("--nofile={files}")),
+            self.memory_max.map(|bytes| format!("--as={bytes}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !rlimits.is_empty() {
+            words.push("prlimit".to_owned());
+            words.extend(rlimits);
+        }
+        words
+    }
+}
+
+impl IoSchedulingClass {
+    /// As used by `ionice -c`
+    pub(crate) fn number(self) -> u8 {
+        match self {
+            IoSchedulingClass::Realtime => 1,
+            IoSchedulingClass::BestEffort => 2,
+            IoSchedulingClass::Idle => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapper() {
+        assert!(Limits::default().wrapper().is_empty());
+        let limits = Limits {
+            memory_max: Some(64 * 1024 * 1024),
+            nice: Some(10),
+            io_scheduling_class: Some(IoSchedulingClass::Idle),
+            open_files: Some(1024),
+            ..Limits::default()
+        };
+        assert_eq!(
+            limits.wrapper().join(" "),
+            "nice -n 10 ionice -c 3 prlimit --nofile=1024 --as=67108864"
+        );
+        assert_eq!(
+            limits.directives(),
+            [
+                "MemoryMax=67108864",
+                "Nice=10",
+                "IOSchedulingClass=idle",
+                "LimitNOFILE=1024"
+            ]
+        );
+    }
+}
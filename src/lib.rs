@@ -22,6 +22,9 @@ impl Tense {
 /// Installation (or removal) configuration, steps, and errors.
 pub mod install;
 pub use install::Spec;
+/// Used by the installed service: report readiness to systemd and take over
+/// the sockets of a socket activated service
+pub mod runtime;
 /// Scheduling options
 pub mod schedule;
 pub use schedule::Schedule;
@@ -0,0 +1,240 @@
+//! Call these from the installed service when it was installed using
+//! [`Spec::notify_ready`](crate::install::Spec::notify_ready) or
+//! [`Spec::watchdog`](crate::install::Spec::watchdog). They send a
+//! notification over the socket systemd passes in `$NOTIFY_SOCKET`. When the
+//! service was not started by systemd, or not as `Type=notify`, that variable
+//! is not set and they do nothing.
+//!
+//! A service installed using
+//! [`Spec::on_connection`](crate::install::Spec::on_connection) takes over its
+//! listening sockets using [`listen_fds`]. Credentials set using
+//! [`Spec::credential`](crate::install::Spec::credential) are read using
+//! [`credential`].
+//!
+//! # Example
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use service_install::runtime;
+//!
+//! // open sockets, load config, ...
+//! runtime::notify_ready()?;
+//!
+//! if let Some(interval) = runtime::watchdog_interval() {
+//!     std::thread::spawn(move || loop {
+//!         std::thread::sleep(interval / 2);
+//!         let _ = runtime::notify_watchdog();
+//!     });
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsStr;
+use std::io;
+use std::os::fd::RawFd;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("`$NOTIFY_SOCKET` is not a valid socket address")]
+    InvalidAddress(#[source] io::Error),
+    #[error("Could not create a socket to send the notification from")]
+    CreatingSocket(#[source] io::Error),
+    #[error("Could not send the notification to systemd")]
+    Sending(#[source] io::Error),
+    #[error("Could not read credential `{name}`")]
+    ReadingCredential {
+        name: String,
+        #[source]
+        e: io::Error,
+    },
+}
+
+/// Tell systemd the service is ready, `READY=1`. Until then systemd, and the
+/// install, consider the service to be starting.
+///
+/// # Errors
+/// Returns an error if `$NOTIFY_SOCKET` is set but the notification could
+/// not be sent.
+pub fn notify_ready() -> Result<(), Error> {
+    notify("READY=1")
+}
+
+/// Tell systemd the service is still alive, `WATCHDOG=1`. Call this at least
+/// once every [`watchdog_interval`].
+///
+/// # Errors
+/// Returns an error if `$NOTIFY_SOCKET` is set but the notification could
+/// not be sent.
+pub fn notify_watchdog() -> Result<(), Error> {
+    notify("WATCHDOG=1")
+}
+
+/// The time within which systemd expects a [`notify_watchdog`] call, `None`
+/// if there is no watchdog or it watches another process. Notify about twice
+/// as often to be safe.
+#[must_use]
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var_os("WATCHDOG_USEC")?.to_str()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+/// The first file descriptor passed by systemd, `SD_LISTEN_FDS_START`
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// The listening sockets passed by systemd to a socket activated service, in
+/// the order they were given to
+/// [`Spec::on_connection`](crate::install::Spec::on_connection). Empty if the
+/// service was not socket activated.
+///
+/// Turn them into listeners using `FromRawFd`, each descriptor may only be
+/// taken over once.
+///
+/// # Example
+/// ```no_run
+/// use std::net::TcpListener;
+/// use std::os::fd::FromRawFd;
+///
+/// use service_install::runtime;
+///
+/// let listener = match runtime::listen_fds().first() {
+///     // Safety: systemd passed us this socket and we take it over only once
+///     Some(fd) => unsafe { TcpListener::from_raw_fd(*fd) },
+///     None => TcpListener::bind("127.0.0.1:8080").unwrap(),
+/// };
+/// ```
+#[must_use]
+pub fn listen_fds() -> Vec<RawFd> {
+    passed_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+    )
+}
+
+/// The variables could have been inherited from a parent, only use them if
+/// they are meant for us
+fn passed_fds(pid: Option<&str>, fds: Option<&str>) -> Vec<RawFd> {
+    let for_us = pid.and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+    let count = fds.and_then(|fds| fds.parse::<RawFd>().ok()).unwrap_or(0);
+    if !for_us || count <= 0 {
+        return Vec::new();
+    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).collect()
+}
+
+/// The value of a credential systemd passed to the service, read from
+/// `$CREDENTIALS_DIRECTORY/<name>`. `None` if the service got no credentials
+/// or none with that name.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use service_install::runtime;
+///
+/// let password = runtime::credential("db-password")?
+///     .ok_or("service installed without a database password")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// Returns an error if the credential exists but could not be read.
+pub fn credential(name: &str) -> Result<Option<Vec<u8>>, Error> {
+    let Some(dir) = std::env::var_os("CREDENTIALS_DIRECTORY") else {
+        return Ok(None);
+    };
+    read_credential(Path::new(&dir), name)
+}
+
+fn read_credential(dir: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+    match std::fs::read(dir.join(name)) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::ReadingCredential {
+            name: name.to_owned(),
+            e,
+        }),
+    }
+}
+
+fn notify(state: &str) -> Result<(), Error> {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    send(&socket, state)
+}
+
+/// An `@` at the start of the path means the socket is in the abstract
+/// namespace
+fn send(socket: &OsStr, state: &str) -> Result<(), Error> {
+    let addr = match socket.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(socket),
+    }
+    .map_err(Error::InvalidAddress)?;
+    let sender = UnixDatagram::unbound().map_err(Error::CreatingSocket)?;
+    sender
+        .send_to_addr(state.as_bytes(), &addr)
+        .map_err(Error::Sending)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passed_fds_only_for_us() {
+        let pid = std::process::id().to_string();
+        assert_eq!(passed_fds(Some(&pid), Some("2")), [3, 4]);
+        assert!(passed_fds(Some("1"), Some("2")).is_empty());
+        assert!(passed_fds(None, Some("2")).is_empty());
+        assert!(passed_fds(Some(&pid), None).is_empty());
+    }
+
+    #[test]
+    fn reads_credential() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("db-password"), "hunter2").unwrap();
+        assert_eq!(
+            read_credential(dir.path(), "db-password")
+                .unwrap()
+                .as_deref(),
+            Some(b"hunter2".as_slice())
+        );
+        assert_eq!(read_credential(dir.path(), "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn sends_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 16];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+
+    #[test]
+    fn sends_to_abstract_socket() {
+        let name = format!("service-install-test-{}", std::process::id());
+        let addr = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let receiver = UnixDatagram::bind_addr(&addr).unwrap();
+
+        send(OsStr::new(&format!("@{name}")), "WATCHDOG=1").unwrap();
+        let mut buf = [0u8; 16];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"WATCHDOG=1");
+    }
+}
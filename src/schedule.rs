@@ -1,8 +1,281 @@
+use std::collections::BTreeSet;
+use std::iter;
+
+use itertools::Itertools;
+use time::{OffsetDateTime, PrimitiveDateTime, Time, Weekday};
+
+pub(crate) mod expr;
+pub(crate) mod timezone;
+use expr::Expr;
+
 #[derive(Debug, Clone)]
 pub enum Schedule {
     /// Local time
-    Daily(time::Time),
-    /// Run once very this duration, 
-    /// note the service runs with second accuracy
+    Daily(Time),
+    /// Multiple times every day, local time
+    DailyAt(Vec<Time>),
+    /// Every hour at this minute past the hour (0-59)
+    Hourly { minute: u8 },
+    /// On the given days of the week, local time
+    Weekly { days: Vec<Weekday>, time: Time },
+    /// On a day of the month (1-31), local time. Months without that day
+    /// are skipped.
+    Monthly { day: u8, time: Time },
+    /// Run once very this duration,
+    /// note the service runs with second accuracy. Cron can only run
+    /// whole minute intervals that evenly divide an hour or a day, a day or a
+    /// week. These are aligned to the clock instead of the install time.
     Every(std::time::Duration),
+    /// A cron expression: five fields (minute hour day-of-month month
+    /// day-of-week) or a nickname like `@daily`, local time. Create it using
+    /// [`Schedule::cron`] to validate the expression right away.
+    Cron(String),
+    /// A systemd calendar expression like `Mon *-*-1..7 03:15`, local time.
+    /// See `man systemd.time`, a year or the last days of a month (`~`) are
+    /// not supported. Create it using [`Schedule::calendar`] to validate the
+    /// expression right away.
+    Calendar(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("A schedule needs at least one time of day to run at")]
+    NoTimes,
+    #[error("A weekly schedule needs at least one day to run on")]
+    NoDays,
+    #[error("Minute must be between 0 and 59, got: {0}")]
+    MinuteOutOfRange(u8),
+    #[error("Day of the month must be between 1 and 31, got: {0}")]
+    DayOutOfRange(u8),
+    #[error("Invalid cron expression `{expr}`, {reason}")]
+    InvalidCron { expr: String, reason: &'static str },
+    #[error("Invalid or unsupported calendar expression `{expr}`, {reason}")]
+    InvalidCalendar { expr: String, reason: &'static str },
+}
+
+impl Schedule {
+    /// A [`Schedule::Cron`], returns an error if the expression is invalid.
+    /// Cron expressions are converted to calendar expressions on systemd.
+    ///
+    /// # Errors
+    /// If the expression is not valid cron syntax.
+    ///
+    /// # Example
+    /// ```
+    /// use service_install::Schedule;
+    ///
+    /// let schedule = Schedule::cron("*/15 8-17 * * mon-fri").unwrap();
+    /// ```
+    pub fn cron(expr: impl Into<String>) -> Result<Self, Error> {
+        let schedule = Self::Cron(expr.into());
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    /// A [`Schedule::Calendar`], returns an error if the expression is invalid
+    /// or unsupported. Calendar expressions are converted to cron rules where
+    /// possible.
+    ///
+    /// # Errors
+    /// If the expression is not valid calendar syntax or uses unsupported
+    /// features.
+    ///
+    /// # Example
+    /// ```
+    /// use service_install::Schedule;
+    ///
+    /// // first Monday of the month
+    /// let schedule = Schedule::calendar("Mon *-*-1..7 03:15").unwrap();
+    /// ```
+    pub fn calendar(expr: impl Into<String>) -> Result<Self, Error> {
+        let schedule = Self::Calendar(expr.into());
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    /// The next `n` times this schedule runs after `from`. Times of day are
+    /// taken in the offset of `from`, pass the current local time to get local
+    /// run times. Changes to the offset, for example for daylight saving time,
+    /// are not accounted for.
+    ///
+    /// For [`Schedule::Every`] the interval is counted from `from` as if the
+    /// service was installed then. Fewer than `n` times are returned if the
+    /// schedule is invalid or never runs, like on the 30th of February.
+    ///
+    /// # Example
+    /// ```
+    /// use service_install::Schedule;
+    /// use time::{OffsetDateTime, Time, Weekday};
+    ///
+    /// let schedule = Schedule::Weekly {
+    ///     days: vec![Weekday::Tuesday, Weekday::Friday],
+    ///     time: Time::from_hms(10, 42, 0).unwrap(),
+    /// };
+    /// let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+    /// let next = schedule.next_occurrences(now, 3);
+    /// assert_eq!(next.len(), 3);
+    /// assert!(next.iter().all(|run| run.hour() == 10 && run.minute() == 42));
+    /// ```
+    #[must_use]
+    pub fn next_occurrences(&self, from: OffsetDateTime, n: usize) -> Vec<OffsetDateTime> {
+        if let Schedule::Every(interval) = self {
+            let Ok(interval) = time::Duration::try_from(*interval) else {
+                return Vec::new();
+            };
+            return iter::successors(from.checked_add(interval), |prev| {
+                prev.checked_add(interval)
+            })
+            .take(n)
+            .collect();
+        }
+
+        let exprs = self.exprs();
+        let offset = from.offset();
+        let mut after = PrimitiveDateTime::new(from.date(), from.time());
+        iter::from_fn(|| {
+            after = exprs
+                .iter()
+                .filter_map(|expr| expr.next_after(after))
+                .min()?;
+            Some(after.assume_offset(offset))
+        })
+        .take(n)
+        .collect()
+    }
+
+    /// The next few runs in local time, for example: `Tue 10:42, Wed 10:42,
+    /// Thu 10:42`. Falls back to UTC if the local offset can not be determined.
+    /// Pass the timezone the schedule is in if it is not local time.
+    pub(crate) fn preview(&self, timezone: Option<&str>) -> String {
+        let (now, zone) = match OffsetDateTime::now_local() {
+            Ok(now) => (now, ""),
+            Err(_) => (OffsetDateTime::now_utc(), " (UTC)"),
+        };
+        let in_zone = timezone
+            .and_then(|timezone| timezone::current_offset(Some(timezone)))
+            .map_or(now, |offset| now.to_offset(offset));
+        let runs = self.next_occurrences(in_zone, 3);
+        let more = if runs.len() == 3 { ", …" } else { "" };
+        let mut runs = runs.into_iter().map(|run| {
+            let run = run.to_offset(now.offset());
+            let weekday = &run.weekday().to_string()[..3];
+            let (hour, minute) = (run.hour(), run.minute());
+            if run - now < time::Duration::WEEK {
+                format!("{weekday} {hour:02}:{minute:02}")
+            } else {
+                let month = &run.month().to_string()[..3];
+                let day = run.day();
+                format!("{weekday} {day} {month} {hour:02}:{minute:02}")
+            }
+        });
+        let runs = runs.join(", ");
+        if runs.is_empty() {
+            "never".to_owned()
+        } else {
+            format!("{runs}{more}{zone}")
+        }
+    }
+
+    /// Every variant except [`Schedule::Every`] as one or more expressions,
+    /// the schedule runs whenever any of them matches.
+    fn exprs(&self) -> Vec<Expr> {
+        match self {
+            Schedule::Daily(time) => vec![Expr::daily(*time)],
+            Schedule::DailyAt(times) => times.iter().copied().map(Expr::daily).collect(),
+            Schedule::Hourly { minute } => Time::from_hms(0, *minute, 0)
+                .map(|time| Expr {
+                    hours: None,
+                    ..Expr::daily(time)
+                })
+                .into_iter()
+                .collect(),
+            Schedule::Weekly { days, time } => vec![Expr {
+                weekdays: Some(
+                    days.iter()
+                        .copied()
+                        .map(Weekday::number_days_from_sunday)
+                        .collect(),
+                ),
+                ..Expr::daily(*time)
+            }],
+            Schedule::Monthly { day, time } => vec![Expr {
+                days: Some(BTreeSet::from([*day])),
+                ..Expr::daily(*time)
+            }],
+            Schedule::Cron(expr) => Expr::parse_cron(expr).into_iter().collect(),
+            Schedule::Calendar(expr) => Expr::parse_calendar(expr).into_iter().collect(),
+            Schedule::Every(_) => Vec::new(),
+        }
+    }
+
+    /// Checks for schedules that can never run
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        match self {
+            Schedule::DailyAt(times) if times.is_empty() => Err(Error::NoTimes),
+            Schedule::Hourly { minute } if *minute > 59 => Err(Error::MinuteOutOfRange(*minute)),
+            Schedule::Weekly { days, .. } if days.is_empty() => Err(Error::NoDays),
+            Schedule::Monthly { day, .. } if !(1..=31).contains(day) => {
+                Err(Error::DayOutOfRange(*day))
+            }
+            Schedule::Cron(expr) => match Expr::parse_cron(expr) {
+                Ok(_) => Ok(()),
+                Err(reason) => Err(Error::InvalidCron {
+                    expr: expr.clone(),
+                    reason,
+                }),
+            },
+            Schedule::Calendar(expr) => match Expr::parse_calendar(expr) {
+                Ok(_) => Ok(()),
+                Err(reason) => Err(Error::InvalidCalendar {
+                    expr: expr.clone(),
+                    reason,
+                }),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month, UtcOffset};
+
+    fn at(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        let date = Date::from_calendar_date(2024, Month::March, day).unwrap();
+        let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+        PrimitiveDateTime::new(date, Time::from_hms(hour, minute, 0).unwrap()).assume_offset(offset)
+    }
+
+    #[test]
+    fn next_occurrences() {
+        let time = |hour, minute| Time::from_hms(hour, minute, 0).unwrap();
+        let now = at(1, 12, 0);
+
+        let schedule = Schedule::DailyAt(vec![time(18, 0), time(6, 30)]);
+        assert_eq!(
+            schedule.next_occurrences(now, 3),
+            [at(1, 18, 0), at(2, 6, 30), at(2, 18, 0)]
+        );
+
+        let schedule = Schedule::Hourly { minute: 15 };
+        assert_eq!(
+            schedule.next_occurrences(now, 2),
+            [at(1, 12, 15), at(1, 13, 15)]
+        );
+
+        let schedule = Schedule::Every(std::time::Duration::from_secs(90 * 60));
+        assert_eq!(
+            schedule.next_occurrences(now, 2),
+            [at(1, 13, 30), at(1, 15, 0)]
+        );
+
+        let schedule = Schedule::Monthly {
+            day: 31,
+            time: time(0, 0),
+        };
+        let next = schedule.next_occurrences(now, 2);
+        assert_eq!(next[0], at(31, 0, 0));
+        assert_eq!(next[1].month(), Month::May);
+    }
 }
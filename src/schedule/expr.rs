@@ -0,0 +1,446 @@
+//! Parses cron and systemd calendar expressions into one representation so
+//! they can be validated and converted into each other.
+
+use std::collections::BTreeSet;
+
+use itertools::{iproduct, Itertools};
+use time::{Date, PrimitiveDateTime, Time};
+
+/// Values a field matches, `None` matches every value
+type Field = Option<BTreeSet<u8>>;
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const FULL_WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Expr {
+    pub(crate) seconds: BTreeSet<u8>,
+    pub(crate) minutes: Field,
+    pub(crate) hours: Field,
+    /// Day of the month, 1-31
+    pub(crate) days: Field,
+    /// 1-12
+    pub(crate) months: Field,
+    /// 0 is Sunday
+    pub(crate) weekdays: Field,
+    /// When both the day of the month and the weekday are restricted cron
+    /// runs if either matches while systemd needs both to match. Cron too
+    /// needs both if one of its day fields starts with `*`, like `*/2`.
+    pub(crate) either_day: bool,
+}
+
+impl Expr {
+    /// Every day at `time`
+    pub(crate) fn daily(time: Time) -> Self {
+        Self {
+            seconds: BTreeSet::from([time.second()]),
+            minutes: Some(BTreeSet::from([time.minute()])),
+            hours: Some(BTreeSet::from([time.hour()])),
+            days: None,
+            months: None,
+            weekdays: None,
+            either_day: false,
+        }
+    }
+
+    pub(crate) fn parse_cron(expr: &str) -> Result<Self, &'static str> {
+        let expr = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            "@reboot" => return Err("@reboot is not a schedule, start on boot instead"),
+            nickname if nickname.starts_with('@') => return Err("unknown @ nickname"),
+            expr => expr,
+        };
+
+        let Some((minutes, hours, days, months, weekdays)) =
+            expr.split_whitespace().collect_tuple()
+        else {
+            return Err("expected five fields: minute hour day-of-month month day-of-week");
+        };
+
+        let either_day = !days.starts_with('*') && !weekdays.starts_with('*');
+        let weekdays = cron_field(weekdays, 0, 7, &WEEKDAYS)?.map(|days| {
+            // both 0 and 7 are Sunday
+            days.into_iter().map(|day| day % 7).collect()
+        });
+        Ok(Self {
+            seconds: BTreeSet::from([0]),
+            minutes: cron_field(minutes, 0, 59, &[])?,
+            hours: cron_field(hours, 0, 23, &[])?,
+            days: cron_field(days, 1, 31, &[])?,
+            months: cron_field(months, 1, 12, &MONTHS)?,
+            weekdays,
+            either_day,
+        })
+    }
+
+    /// Supports the weekday, date and time parts of a systemd calendar
+    /// expression (`man systemd.time`) and its shorthands. A year or the last
+    /// days of a month (`~`) are not supported.
+    pub(crate) fn parse_calendar(expr: &str) -> Result<Self, &'static str> {
+        let expr = match expr.trim() {
+            "minutely" => "*-*-* *:*:00",
+            "hourly" => "*-*-* *:00:00",
+            "daily" => "*-*-* 00:00:00",
+            "weekly" => "Mon *-*-* 00:00:00",
+            "monthly" => "*-*-01 00:00:00",
+            "quarterly" => "*-01,04,07,10-01 00:00:00",
+            "semiannually" => "*-01,07-01 00:00:00",
+            "yearly" | "annually" => "*-01-01 00:00:00",
+            expr => expr,
+        };
+
+        let mut parts = expr.split_whitespace().peekable();
+        let weekdays = match parts.next_if(|part| part.starts_with(char::is_alphabetic)) {
+            Some(weekdays) => Some(calendar_weekdays(weekdays)?),
+            None => None,
+        };
+        let date = parts.next_if(|part| part.contains('-')).unwrap_or("*-*-*");
+        let time = parts
+            .next_if(|part| part.contains(':'))
+            .unwrap_or("00:00:00");
+        if parts.next().is_some() {
+            return Err("expected at most a weekday, a date and a time");
+        }
+
+        let (months, days) = match date.split('-').collect_vec().as_slice() {
+            ["*", months, days] | [months, days] => (*months, *days),
+            [_year, _, _] => return Err("restricting the year is not supported"),
+            _ => return Err("date must look like: year-month-day"),
+        };
+        if days.contains('~') {
+            return Err("the last days of a month (~) are not supported");
+        }
+        let (hours, minutes, seconds) = match time.split(':').collect_vec().as_slice() {
+            [hours, minutes] => (*hours, *minutes, "00"),
+            [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+            _ => return Err("time must look like: hour:minute:second"),
+        };
+
+        Ok(Self {
+            seconds: calendar_field(seconds, 0, 59)?.unwrap_or_else(|| (0..=59).collect()),
+            minutes: calendar_field(minutes, 0, 59)?,
+            hours: calendar_field(hours, 0, 23)?,
+            days: calendar_field(days, 1, 31)?,
+            months: calendar_field(months, 1, 12)?,
+            weekdays,
+            either_day: false,
+        })
+    }
+
+    pub(crate) fn to_cron(&self) -> Result<String, &'static str> {
+        if self.seconds != BTreeSet::from([0]) {
+            return Err("cron can only run at the start of a minute");
+        }
+        let mut days = list(&self.days);
+        if self.days.is_some() && self.weekdays.is_some() && !self.either_day {
+            // a day field starting with `*` makes cron require both
+            days = star_step(&self.days, 1, 31).ok_or("cron runs when either the day of the month or the weekday matches, it can not require both")?;
+        }
+
+        Ok([
+            list(&self.minutes),
+            list(&self.hours),
+            days,
+            list(&self.months),
+            list(&self.weekdays),
+        ]
+        .join(" "))
+    }
+
+    pub(crate) fn to_calendar(&self) -> Result<String, &'static str> {
+        if self.days.is_some() && self.weekdays.is_some() && self.either_day {
+            return Err("systemd runs when both the day of the month and the weekday match, it can not require only one");
+        }
+
+        let weekdays = self
+            .weekdays
+            .as_ref()
+            .map(|days| {
+                let mut names = days.iter().map(|day| {
+                    let name = WEEKDAYS[usize::from(*day)];
+                    name[..1].to_uppercase() + &name[1..]
+                });
+                names.join(",") + " "
+            })
+            .unwrap_or_default();
+        let months = list(&self.months);
+        let days = list(&self.days);
+        let hours = list(&self.hours);
+        let minutes = list(&self.minutes);
+        let seconds = self.seconds.iter().join(",");
+        Ok(format!(
+            "{weekdays}*-{months}-{days} {hours}:{minutes}:{seconds}"
+        ))
+    }
+
+    /// The first moment strictly after `after` this matches. Returns `None`
+    /// if it never matches, for example on the 30th of February.
+    pub(crate) fn next_after(&self, after: PrimitiveDateTime) -> Option<PrimitiveDateTime> {
+        let hours = values(&self.hours, 23);
+        let minutes = values(&self.minutes, 59);
+
+        let mut date = after.date();
+        // a leap day on a given weekday comes around at least once every 28 years
+        for _ in 0..366 * 28 {
+            if self.matches_day(date) {
+                let next = iproduct!(&hours, &minutes, &self.seconds)
+                    .map(|(h, m, s)| Time::from_hms(*h, *m, *s).expect("fields are in range"))
+                    .map(|time| PrimitiveDateTime::new(date, time))
+                    .find(|moment| *moment > after);
+                if next.is_some() {
+                    return next;
+                }
+            }
+            date = date.next_day()?;
+        }
+        None
+    }
+
+    fn matches_day(&self, date: Date) -> bool {
+        let contains =
+            |field: &Field, value: u8| field.as_ref().is_none_or(|values| values.contains(&value));
+        let day = contains(&self.days, date.day());
+        let weekday = contains(&self.weekdays, date.weekday().number_days_from_sunday());
+        let day = if self.either_day && self.days.is_some() && self.weekdays.is_some() {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        day && contains(&self.months, u8::from(date.month()))
+    }
+}
+
+fn values(field: &Field, max: u8) -> Vec<u8> {
+    match field {
+        Some(values) => values.iter().copied().collect(),
+        None => (0..=max).collect(),
+    }
+}
+
+fn list(field: &Field) -> String {
+    match field {
+        Some(values) => values.iter().join(","),
+        None => "*".to_owned(),
+    }
+}
+
+/// `*/step` if the field holds every `step`th value from `min` on
+fn star_step(field: &Field, min: u8, max: u8) -> Option<String> {
+    let values = field.as_ref()?;
+    (1..=usize::from(max - min))
+        .find(|step| values.iter().copied().eq((min..=max).step_by(*step)))
+        .map(|step| format!("*/{step}"))
+}
+
+fn value(s: &str, min: u8, max: u8, names: &[&str]) -> Result<u8, &'static str> {
+    let value = if let Some(pos) = names.iter().position(|name| name.eq_ignore_ascii_case(s)) {
+        u8::try_from(pos).expect("less then 13 names") + min
+    } else {
+        s.parse().map_err(|_| "expected a number or name")?
+    };
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err("value out of range")
+    }
+}
+
+/// Items separated by `,`: `*`, `a` or a range, each optionally with a
+/// `/step`. Cron separates a range with `-` (`a-b`) systemd with `..`
+/// (`a..b`).
+fn field(
+    field: &str,
+    (min, max): (u8, u8),
+    names: &[&str],
+    range_sep: &str,
+) -> Result<Field, &'static str> {
+    if field == "*" {
+        return Ok(None);
+    }
+
+    let mut values = BTreeSet::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| "invalid step")?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return Err("step must be larger then zero");
+        }
+        let (start, end) = match range.split_once(range_sep) {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start, min, max, names)?, value(end, min, max, names)?),
+            None if item.contains('/') => (value(range, min, max, names)?, max),
+            None => {
+                let value = value(range, min, max, names)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err("range start is after its end");
+        }
+        values.extend((start..=end).step_by(step));
+    }
+    Ok(Some(values))
+}
+
+fn cron_field(s: &str, min: u8, max: u8, names: &[&str]) -> Result<Field, &'static str> {
+    field(s, (min, max), names, "-")
+}
+
+fn calendar_field(s: &str, min: u8, max: u8) -> Result<Field, &'static str> {
+    field(s, (min, max), &[], "..")
+}
+
+/// Weekday names (`Mon` or `Monday`, in any case) separated by `,`, or
+/// ranges: `Mon..Fri`
+fn calendar_weekdays(field: &str) -> Result<BTreeSet<u8>, &'static str> {
+    let day = |name: &str| {
+        let pos = WEEKDAYS
+            .iter()
+            .chain(&FULL_WEEKDAYS)
+            .position(|day| day.eq_ignore_ascii_case(name))
+            .ok_or("unknown weekday")?;
+        Ok::<_, &'static str>(u8::try_from(pos % 7).expect("there are 7 weekdays"))
+    };
+
+    let mut days = BTreeSet::new();
+    for item in field.split(',') {
+        match item.split_once("..") {
+            Some((start, end)) => {
+                let (start, end) = (day(start)?, day(end)?);
+                if start <= end {
+                    days.extend(start..=end);
+                } else {
+                    // for example Sat..Sun
+                    days.extend((start..=6).chain(0..=end));
+                }
+            }
+            None => {
+                days.insert(day(item)?);
+            }
+        }
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_to_calendar() {
+        let expr = Expr::parse_cron("15 3 * * mon").unwrap();
+        assert_eq!(expr.to_calendar().unwrap(), "Mon *-*-* 3:15:0");
+        assert_eq!(expr.to_cron().unwrap(), "15 3 * * 1");
+
+        let expr = Expr::parse_cron("*/20 8-10 1,15 * *").unwrap();
+        assert_eq!(expr.to_calendar().unwrap(), "*-*-1,15 8,9,10:0,20,40:0");
+
+        let expr = Expr::parse_cron("@weekly").unwrap();
+        assert_eq!(expr.to_calendar().unwrap(), "Sun *-*-* 0:0:0");
+
+        // cron runs on the 1st and every Monday, systemd can not
+        let expr = Expr::parse_cron("0 0 1 * 1").unwrap();
+        assert!(expr.to_calendar().is_err());
+
+        // a day field starting with `*` makes cron require both
+        let expr = Expr::parse_cron("0 0 */10 * 1").unwrap();
+        assert_eq!(expr.to_calendar().unwrap(), "Mon *-*-1,11,21,31 0:0:0");
+        assert_eq!(expr.to_cron().unwrap(), "0 0 */10 * 1");
+    }
+
+    #[test]
+    fn calendar_to_cron() {
+        let expr = Expr::parse_calendar("Mon..Fri *-*-* 09:30").unwrap();
+        assert_eq!(expr.to_cron().unwrap(), "30 9 * * 1,2,3,4,5");
+
+        let expr = Expr::parse_calendar("monthly").unwrap();
+        assert_eq!(expr.to_cron().unwrap(), "0 0 1 * *");
+
+        let expr = Expr::parse_calendar("*-*-* *:0/15").unwrap();
+        assert_eq!(expr.to_cron().unwrap(), "0,15,30,45 * * * *");
+
+        let expr = Expr::parse_calendar("saturday,SUN 10:00").unwrap();
+        assert_eq!(expr.to_cron().unwrap(), "0 10 * * 0,6");
+
+        // first Monday of the month
+        let expr = Expr::parse_calendar("Mon *-*-1..7 03:15:00").unwrap();
+        assert!(expr.to_cron().is_err());
+        let expr = Expr::parse_calendar("*-*-* 10:00:30").unwrap();
+        assert!(expr.to_cron().is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        for expr in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "@reboot",
+        ] {
+            assert!(Expr::parse_cron(expr).is_err(), "{expr}");
+        }
+        for expr in [
+            "Moon *-*-*",
+            "Monkey *-*-*",
+            "Frizzle",
+            "Sunshine 10:00",
+            "Mo,Tu *-*-*",
+            "Mon..Fridays",
+            "2030-01-01",
+            "*-*-~1",
+            "25:00",
+            "*-*-* 10:00 UTC",
+        ] {
+            assert!(Expr::parse_calendar(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn next_after() {
+        let at = |year, month, day, hour, minute| {
+            let month = time::Month::try_from(month).unwrap();
+            let date = Date::from_calendar_date(year, month, day).unwrap();
+            PrimitiveDateTime::new(date, Time::from_hms(hour, minute, 0).unwrap())
+        };
+
+        // Friday
+        let now = at(2024, 3, 1, 12, 0);
+        let expr = Expr::parse_cron("30 9 * * mon").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2024, 3, 4, 9, 30)));
+        let expr = Expr::parse_cron("0 12 * * *").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2024, 3, 2, 12, 0)));
+        // cron: the 15th or any Monday
+        let expr = Expr::parse_cron("0 0 15 * 1").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2024, 3, 4, 0, 0)));
+        // cron: an odd day that is a Monday, `*/2` is not a restriction
+        // to cron when combining the days
+        let expr = Expr::parse_cron("0 0 */2 * 1").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2024, 3, 11, 0, 0)));
+        // systemd: a Monday that is the 15th
+        let expr = Expr::parse_calendar("Mon *-*-15").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2024, 4, 15, 0, 0)));
+        let expr = Expr::parse_calendar("*-02-29").unwrap();
+        assert_eq!(expr.next_after(now), Some(at(2028, 2, 29, 0, 0)));
+        let expr = Expr::parse_calendar("*-02-30").unwrap();
+        assert_eq!(expr.next_after(now), None);
+    }
+}
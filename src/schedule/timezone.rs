@@ -0,0 +1,150 @@
+//! IANA timezones (`Europe/Amsterdam`) for schedules. We rely on the system's
+//! timezone database and `date` instead of shipping our own.
+
+use std::path::Path;
+use std::process::Command;
+
+use time::{Time, UtcOffset};
+
+use super::Schedule;
+
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/// Whether the system's timezone database knows this timezone
+pub(crate) fn is_known(timezone: &str) -> bool {
+    let relative = Path::new(timezone);
+    relative.is_relative()
+        && !relative.components().any(|c| c.as_os_str() == "..")
+        && Path::new(ZONEINFO).join(relative).is_file()
+}
+
+/// The offset from UTC right now in `timezone`, the local timezone if `None`
+pub(crate) fn current_offset(timezone: Option<&str>) -> Option<UtcOffset> {
+    let mut date = Command::new("date");
+    date.arg("+%z");
+    if let Some(timezone) = timezone {
+        date.env("TZ", timezone);
+    }
+    let output = date.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_offset(String::from_utf8_lossy(&output.stdout).trim())
+}
+
+/// Parses `date +%z` output: `+0200` or `-0430`
+fn parse_offset(offset: &str) -> Option<UtcOffset> {
+    let (sign, digits) = offset.split_at_checked(1)?;
+    let sign = match sign {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: i8 = digits[..2].parse().ok()?;
+    let minutes: i8 = digits[2..].parse().ok()?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+/// Moves a schedule from one offset to another. Only supports schedules
+/// built from times of day, runs on a day of the month can not be moved to
+/// another day.
+pub(crate) fn shift(
+    schedule: &Schedule,
+    from: UtcOffset,
+    to: UtcOffset,
+) -> Result<Schedule, &'static str> {
+    let minutes = (to.whole_minutes() - from.whole_minutes()).into();
+    Ok(match schedule {
+        Schedule::Daily(time) => Schedule::Daily(shift_time(*time, minutes).0),
+        Schedule::DailyAt(times) => Schedule::DailyAt(
+            times
+                .iter()
+                .map(|time| shift_time(*time, minutes).0)
+                .collect(),
+        ),
+        Schedule::Hourly { minute } => Schedule::Hourly {
+            minute: u8::try_from((i32::from(*minute) + minutes).rem_euclid(60))
+                .expect("rem_euclid(60) fits in u8"),
+        },
+        Schedule::Weekly { days, time } => {
+            let (time, days_later) = shift_time(*time, minutes);
+            let days = days
+                .iter()
+                .map(|day| match days_later {
+                    1 => day.next(),
+                    -1 => day.previous(),
+                    _ => *day,
+                })
+                .collect();
+            Schedule::Weekly { days, time }
+        }
+        Schedule::Monthly { day, time } => match shift_time(*time, minutes) {
+            (time, 0) => Schedule::Monthly { day: *day, time },
+            _ => return Err("the run would move to another day of the month"),
+        },
+        Schedule::Every(interval) => Schedule::Every(*interval),
+        Schedule::Cron(_) | Schedule::Calendar(_) => {
+            return Err("cron and calendar expressions can not be moved to another timezone")
+        }
+    })
+}
+
+/// Returns the shifted time and whether it moved to the next (1) or previous
+/// (-1) day
+fn shift_time(time: Time, minutes: i32) -> (Time, i32) {
+    const DAY: i32 = 24 * 60;
+    let total = i32::from(time.hour()) * 60 + i32::from(time.minute()) + minutes;
+    let in_day = total.rem_euclid(DAY);
+    let shifted = Time::from_hms(
+        u8::try_from(in_day / 60).expect("less than 24 hours"),
+        u8::try_from(in_day % 60).expect("less than 60 minutes"),
+        time.second(),
+    )
+    .expect("in range");
+    (shifted, total.div_euclid(DAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Weekday;
+
+    use super::*;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("+0200"), UtcOffset::from_hms(2, 0, 0).ok());
+        assert_eq!(parse_offset("-0430"), UtcOffset::from_hms(-4, -30, 0).ok());
+        assert_eq!(parse_offset("0200"), None);
+    }
+
+    #[test]
+    fn amsterdam_to_utc() {
+        let amsterdam = UtcOffset::from_hms(2, 0, 0).unwrap();
+        let time = |hour, minute| Time::from_hms(hour, minute, 0).unwrap();
+
+        let daily = Schedule::Daily(time(1, 30));
+        let Schedule::Daily(shifted) = shift(&daily, amsterdam, UtcOffset::UTC).unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(shifted, time(23, 30));
+
+        let weekly = Schedule::Weekly {
+            days: vec![Weekday::Monday],
+            time: time(1, 30),
+        };
+        let Schedule::Weekly { days, .. } = shift(&weekly, amsterdam, UtcOffset::UTC).unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(days, [Weekday::Sunday]);
+
+        let monthly = Schedule::Monthly {
+            day: 1,
+            time: time(1, 30),
+        };
+        assert!(shift(&monthly, amsterdam, UtcOffset::UTC).is_err());
+    }
+}
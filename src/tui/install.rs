@@ -55,13 +55,11 @@ pub fn start(steps: InstallSteps, detailed: bool) -> Result<(), Error> {
         }
 
         match step.options() {
-            Some(StepOptions::YesOrAbort) => {
-                if !Confirm::new().interact()? {
-                    rollback_if_user_wants_to(rollback_steps)?;
-                    return Err(Error::Canceled);
-                }
+            Some(StepOptions::YesOrAbort) if !Confirm::new().interact()? => {
+                rollback_if_user_wants_to(rollback_steps)?;
+                return Err(Error::Canceled);
             }
-            None => (),
+            Some(StepOptions::YesOrAbort) | None => (),
         }
 
         match step.perform() {